@@ -1,7 +1,14 @@
-use axum::{response::Json, routing::get, Router};
+use std::collections::HashMap;
+
+use axum::{extract::{Path, Query}, http::{header::AUTHORIZATION, HeaderMap, StatusCode}, response::Json, routing::{delete, get}, Router};
 use serde_json::{json, Value};
-use shared::websocket::ws_manager::WebsocketManager;
+use shared::{redis::{alert_manager::AlertManager, holder_manager::HolderManager, leaderboard_manager::LeaderboardManager, market_registry_manager::MarketRegistryManager, metric_and_ohlcv_manager::TimeFrame, token_symbol_manager::TokenSymbolManager}, types::{alert::{owner_of_token, NewAlertRule}, ohlcv::{CandleDenomination, CandleTimeFrame}, ranking::LeaderboardKind, wallet::TraderSort}, services::{alert_service::resolve_webhook, candle_store::CandleStore, registry_store::RegistryStore, swap_store::{SwapCursor, SwapStore}, outbound::{get_outbound_stats, BreakerState}, wallet_service::WalletService}, websocket::ws_manager::WebsocketManager};
 use dotenvy::dotenv;
+use tokio::sync::OnceCell;
+
+// one database pool for all candle requests, opened on first use
+static CANDLE_STORE: OnceCell<CandleStore> = OnceCell::const_new();
+static SWAP_STORE: OnceCell<SwapStore> = OnceCell::const_new();
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
@@ -15,6 +22,16 @@ async fn main() -> Result<(), anyhow::Error> {
         println!("❌ REDIS_URL environment variable not found");
     }
 
+    // alert rules live in redis, put back the ones it lost from their postgres copy
+    match RegistryStore::connect().await {
+        Ok(registry_store) => match registry_store.restore_alerts().await {
+            Ok(0) => {}
+            Ok(restored) => println!("✅ Restored {} alert rules from PostgreSQL", restored),
+            Err(e) => println!("❌ Error restoring alert rules : {}", e),
+        },
+        Err(e) => println!("❌ Error connecting registry store : {}", e),
+    }
+
     println!("Creating WebSocket manager...");
     let ws_manager = WebsocketManager::new();
     println!("✅ WebSocket manager created");
@@ -22,7 +39,22 @@ async fn main() -> Result<(), anyhow::Error> {
     println!("Setting up routes...");
     let api_routes = Router::new()
         .route("/", get(handler))
-        .route("/health", get(health_check));
+        .route("/health", get(health_check))
+        .route("/health/outbound", get(outbound_health))
+        .route("/tokens/{mint}", get(token_info))
+        .route("/markets/active", get(active_markets))
+        .route("/leaderboards/{window}/{kind}", get(leaderboard))
+        .route("/wallets/{wallet}", get(wallet_profile))
+        .route("/traders/top", get(top_traders))
+        .route("/swaps", get(swaps))
+        .route("/ohlcv", get(ohlcv))
+        .route("/candles", get(udf_history))
+        .route("/candles/history", get(udf_history))
+        .route("/candles/config", get(udf_config))
+        .route("/candles/symbols", get(udf_symbols))
+        .route("/candles/time", get(udf_time))
+        .route("/alerts", get(list_alerts).post(create_alert))
+        .route("/alerts/{id}", delete(delete_alert));
 
     let ws_routes = ws_manager.get_route();
 
@@ -59,6 +91,16 @@ async fn handler() -> Json<Value> {
         "status": "running",
         "endpoints": {
             "health": "/api/health",
+            "outbound_health": "/api/health/outbound",
+            "token_info": "/api/tokens/{mint}",
+            "active_markets": "/api/markets/active?since_secs=86400&limit=100",
+            "leaderboards": "/api/leaderboards/{window}/{trending|gainers|losers|volume|newest}?limit=50&offset=0",
+            "wallet_profile": "/api/wallets/{wallet}",
+            "top_traders": "/api/traders/top?pair=BONK/SOL&sort=pnl|volume&limit=50",
+            "swaps": "/api/swaps?pair=BONK/SOL|wallet={wallet}&before={next_before}&limit=100",
+            "ohlcv": "/api/ohlcv?pair=BONK/SOL&timeframe=1m&from={unix}&to={unix}&limit=500&denomination=usd|quote&gap_fill=true",
+            "candles": "/api/candles?symbol=BONK/SOL&resolution=1&from={unix}&to={unix}&countback=300 (TradingView UDF, also /config, /symbols, /time)",
+            "alerts": "/api/alerts (GET, POST), /api/alerts/{id} (DELETE), all with Authorization: Bearer {owner token}",
             "websocket": "/ws"
         }
     }))
@@ -71,3 +113,394 @@ async fn health_check() -> Json<Value> {
         "service": "transaction-tracker-api"
     }))
 }
+
+async fn outbound_health() -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let hosts = get_outbound_stats().await.map_err(|e| {
+        println!("Error reading outbound stats : {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "outbound stats unavailable" })))
+    })?;
+    let degraded = hosts.iter().any(|host| host.breaker != BreakerState::Closed);
+
+    Ok(Json(json!({
+        "status": if degraded { "degraded" } else { "healthy" },
+        "hosts": hosts,
+        "timestamp": chrono::Utc::now().to_rfc3339()
+    })))
+}
+
+async fn token_info(Path(mint): Path<String>) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let token_manager = TokenSymbolManager::new().map_err(|e| {
+        println!("Error creating token manager for token info : {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "token store unavailable" })))
+    })?;
+
+    match token_manager.get_token_metadata(&mint).await {
+        Some(metadata) => {
+            let mut token_info = json!(metadata);
+            token_info["holders"] = json!(holder_stats(&token_manager, &mint, metadata.update_authority.clone()).await);
+            Ok(Json(token_info))
+        }
+        None if token_manager.is_metadata_missing(&mint).await => Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "mint has no on-chain metadata", "mint": mint })),
+        )),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": "token not indexed yet", "mint": mint })),
+        )),
+    }
+}
+
+// holder stats are best effort, the token still resolves while the holder index is being built
+async fn holder_stats(token_manager: &TokenSymbolManager, mint: &str, dev_wallet: Option<String>) -> Option<Value> {
+    let holder_manager = HolderManager::new().ok()?;
+    let total_supply = token_manager.get_token_supply(mint).await.map(|supply| supply.total_supply);
+    match holder_manager.get_holder_stats(mint, total_supply, dev_wallet, 20).await {
+        Ok(stats) => Some(json!(stats)),
+        Err(e) => {
+            println!("Error reading holder stats for {} : {}", mint, e);
+            None
+        }
+    }
+}
+
+// markets ordered by most recent trade, `since_secs` bounds how far back a trade may be
+async fn active_markets(Query(params): Query<HashMap<String, String>>) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let since_secs = params.get("since_secs").and_then(|secs| secs.parse::<i64>().ok()).unwrap_or(86400);
+    let limit = params.get("limit").and_then(|limit| limit.parse::<isize>().ok()).unwrap_or(100).clamp(1, 1000);
+
+    let market_registry = MarketRegistryManager::new().map_err(|e| {
+        println!("Error creating market registry for active markets : {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "market registry unavailable" })))
+    })?;
+    let markets = market_registry
+        .get_active_markets(chrono::Utc::now().timestamp() - since_secs, Some(limit))
+        .await
+        .map_err(|e| {
+            println!("Error reading active markets : {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "market registry unavailable" })))
+        })?;
+
+    Ok(Json(json!({
+        "markets": markets,
+        "count": markets.len()
+    })))
+}
+
+async fn leaderboard(
+    Path((window, kind)): Path<(String, String)>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let timeframe = TimeFrame::from_label(&window).ok_or_else(|| {
+        (StatusCode::BAD_REQUEST, Json(json!({ "error": "unknown window", "window": window })))
+    })?;
+    let kind = LeaderboardKind::from_label(&kind).ok_or_else(|| {
+        (StatusCode::BAD_REQUEST, Json(json!({ "error": "unknown leaderboard", "kind": kind })))
+    })?;
+    let limit = params.get("limit").and_then(|limit| limit.parse::<isize>().ok()).unwrap_or(50).clamp(1, 500);
+    let offset = params.get("offset").and_then(|offset| offset.parse::<isize>().ok()).unwrap_or(0).max(0);
+
+    let leaderboard_manager = LeaderboardManager::new().map_err(|e| {
+        println!("Error creating leaderboard manager : {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "leaderboards unavailable" })))
+    })?;
+    let entries = leaderboard_manager
+        .get_leaderboard(&timeframe, kind, offset, limit)
+        .await
+        .map_err(|e| {
+            println!("Error reading leaderboard : {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "leaderboards unavailable" })))
+        })?;
+
+    Ok(Json(json!({
+        "window": timeframe.label(),
+        "kind": kind,
+        "entries": entries
+    })))
+}
+
+fn wallet_service() -> Result<WalletService, (StatusCode, Json<Value>)> {
+    let token_manager = TokenSymbolManager::new().map_err(|e| {
+        println!("Error creating token manager for wallets : {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "wallet store unavailable" })))
+    })?;
+    Ok(WalletService::new(token_manager))
+}
+
+async fn wallet_profile(Path(wallet): Path<String>) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let profile = wallet_service()?.get_profile(&wallet).await.map_err(|e| {
+        println!("Error reading wallet profile of {} : {}", wallet, e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "wallet store unavailable" })))
+    })?;
+    if profile.positions.is_empty() {
+        return Err((StatusCode::NOT_FOUND, Json(json!({ "error": "wallet has no tracked trades", "wallet": wallet }))));
+    }
+    Ok(Json(json!(profile)))
+}
+
+// pairs contain a slash, so the market is passed as a query parameter
+async fn top_traders(Query(params): Query<HashMap<String, String>>) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let token_pair = params.get("pair").ok_or_else(|| {
+        (StatusCode::BAD_REQUEST, Json(json!({ "error": "missing pair" })))
+    })?;
+    let sort_label = params.get("sort").map(String::as_str).unwrap_or("pnl");
+    let sort = TraderSort::from_label(sort_label).ok_or_else(|| {
+        (StatusCode::BAD_REQUEST, Json(json!({ "error": "unknown sort", "sort": sort_label })))
+    })?;
+    let limit = params.get("limit").and_then(|limit| limit.parse::<isize>().ok()).unwrap_or(50).clamp(1, 200);
+
+    let traders = wallet_service()?.get_top_traders(token_pair, sort, limit).await.map_err(|e| {
+        println!("Error reading top traders of {} : {}", token_pair, e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "wallet store unavailable" })))
+    })?;
+
+    Ok(Json(json!({
+        "pair": token_pair,
+        "sort": sort.label(),
+        "traders": traders
+    })))
+}
+
+// stored trade history of a pair or a wallet, newest first
+async fn swaps(Query(params): Query<HashMap<String, String>>) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let before = match params.get("before") {
+        Some(before) => Some(
+            SwapCursor::parse(before).ok_or_else(|| (StatusCode::BAD_REQUEST, Json(json!({ "error": "invalid before cursor" }))))?,
+        ),
+        None => None,
+    };
+    let limit = params.get("limit").and_then(|limit| limit.parse::<u64>().ok()).unwrap_or(100).clamp(1, 500);
+    let swap_store = SWAP_STORE.get_or_try_init(SwapStore::connect).await.map_err(|e| {
+        println!("Error opening swap store : {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "swap store unavailable" })))
+    })?;
+
+    let result = match (params.get("pair"), params.get("wallet")) {
+        (Some(token_pair), _) => swap_store.recent_swaps(token_pair, before.as_ref(), limit).await,
+        (None, Some(wallet)) => swap_store.wallet_swaps(wallet, before.as_ref(), limit).await,
+        (None, None) => return Err((StatusCode::BAD_REQUEST, Json(json!({ "error": "missing pair or wallet" })))),
+    };
+    let swaps = result.map_err(|e| {
+        println!("Error reading swaps : {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "swap store unavailable" })))
+    })?;
+    // a full page may have more behind it, `next_before` fetches it
+    let next_before = (swaps.len() as u64 == limit).then(|| swaps.last().map(|swap| SwapCursor::of(swap).encode())).flatten();
+    Ok(Json(json!({
+        "count": swaps.len(),
+        "swaps": swaps,
+        "next_before": next_before
+    })))
+}
+
+// candles from redis, older ranges from postgres, with OHLC and volume in usd or in the quote token
+async fn ohlcv(Query(params): Query<HashMap<String, String>>) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let token_pair = params.get("pair").ok_or_else(|| {
+        (StatusCode::BAD_REQUEST, Json(json!({ "error": "missing pair" })))
+    })?;
+    let timeframe_label = params.get("timeframe").map(String::as_str).unwrap_or("1m");
+    let timeframe = CandleTimeFrame::from_label(timeframe_label).ok_or_else(|| {
+        (StatusCode::BAD_REQUEST, Json(json!({ "error": "unknown timeframe", "timeframe": timeframe_label })))
+    })?;
+    let denomination_label = params.get("denomination").map(String::as_str).unwrap_or("usd");
+    let denomination = CandleDenomination::from_label(denomination_label).ok_or_else(|| {
+        (StatusCode::BAD_REQUEST, Json(json!({ "error": "unknown denomination", "denomination": denomination_label })))
+    })?;
+    let limit = params.get("limit").and_then(|limit| limit.parse::<usize>().ok()).unwrap_or(500).clamp(1, 2000);
+    let to = params.get("to").and_then(|to| to.parse::<i64>().ok()).unwrap_or_else(|| chrono::Utc::now().timestamp());
+    let from = params.get("from").and_then(|from| from.parse::<i64>().ok()).unwrap_or(to - limit as i64 * timeframe.to_seconds());
+    let gap_fill = params.get("gap_fill").map(|gap_fill| gap_fill != "false").unwrap_or(true);
+
+    let candles = candle_store()
+        .await?
+        .get_candles(token_pair, timeframe, from, to, gap_fill, limit)
+        .await
+        .map_err(|e| {
+            println!("Error reading candles of {} : {}", token_pair, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "candle store unavailable" })))
+        })?;
+    let candles: Vec<_> = candles.into_iter().map(|candle| candle.denominated(denomination)).collect();
+
+    Ok(Json(json!({
+        "pair": token_pair,
+        "timeframe": timeframe_label,
+        "denomination": denomination_label,
+        "candles": candles
+    })))
+}
+
+async fn candle_store() -> Result<&'static CandleStore, (StatusCode, Json<Value>)> {
+    CANDLE_STORE.get_or_try_init(CandleStore::connect).await.map_err(|e| {
+        println!("Error opening candle store : {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "candle store unavailable" })))
+    })
+}
+
+// the UDF adapter only reads `s` and `errmsg` from 200 responses, failures are reported in the body
+fn udf_error(errmsg: &str) -> (StatusCode, Json<Value>) {
+    (StatusCode::OK, Json(json!({ "s": "error", "errmsg": errmsg })))
+}
+
+const UDF_MAX_BARS: usize = 5000;
+
+async fn udf_config() -> Json<Value> {
+    let resolutions: Vec<&str> = CandleTimeFrame::all().iter().map(|timeframe| timeframe.udf_resolution()).collect();
+    Json(json!({
+        "supported_resolutions": resolutions,
+        "supports_group_request": false,
+        "supports_marks": false,
+        "supports_timescale_marks": false,
+        "supports_search": false,
+        "supports_time": true
+    }))
+}
+
+async fn udf_time() -> String {
+    chrono::Utc::now().timestamp().to_string()
+}
+
+// pairs are their own symbol, known once they have a candle
+async fn udf_symbols(Query(params): Query<HashMap<String, String>>) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let symbol = params.get("symbol").ok_or_else(|| udf_error("missing symbol"))?;
+    let last_candle = candle_store()
+        .await?
+        .get_last_candle_before(symbol, CandleTimeFrame::OneMinute, chrono::Utc::now().timestamp() + 1)
+        .await
+        .map_err(|e| {
+            println!("Error reading last candle of {} : {}", symbol, e);
+            udf_error("candle store unavailable")
+        })?
+        .ok_or_else(|| udf_error("unknown symbol"))?;
+
+    // enough decimals to show four significant digits of the last price
+    let decimals = if last_candle.close > 0.0 { (4.0 - last_candle.close.log10().floor()).clamp(2.0, 16.0) as u32 } else { 8 };
+    let resolutions: Vec<&str> = CandleTimeFrame::all().iter().map(|timeframe| timeframe.udf_resolution()).collect();
+    Ok(Json(json!({
+        "name": symbol,
+        "ticker": symbol,
+        "description": symbol,
+        "type": "crypto",
+        "session": "24x7",
+        "timezone": "Etc/UTC",
+        "exchange": "Solana",
+        "listed_exchange": "Solana",
+        "format": "price",
+        "minmov": 1,
+        "pricescale": 10u64.pow(decimals),
+        "has_intraday": true,
+        "has_seconds": true,
+        "seconds_multipliers": ["1"],
+        "has_daily": true,
+        // there are no monthly candles, the chart builds weeks and months from daily bars
+        "has_weekly_and_monthly": false,
+        "supported_resolutions": resolutions,
+        "volume_precision": 2,
+        "data_status": "streaming"
+    })))
+}
+
+/// UDF `history` : bars of `[from, to)`, or the `countback` bars before `to` when given.
+///
+/// Empty ranges answer `no_data` with `nextTime` set to the latest earlier bar, so the chart can jump to it.
+async fn udf_history(Query(params): Query<HashMap<String, String>>) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let symbol = params.get("symbol").ok_or_else(|| udf_error("missing symbol"))?;
+    let resolution = params.get("resolution").map(String::as_str).unwrap_or("1");
+    let timeframe = CandleTimeFrame::from_udf_resolution(resolution)
+        .ok_or_else(|| udf_error("unsupported resolution"))?;
+    let denomination = CandleDenomination::from_label(params.get("denomination").map(String::as_str).unwrap_or("usd"))
+        .ok_or_else(|| udf_error("unknown denomination"))?;
+    let to = params.get("to").and_then(|to| to.parse::<i64>().ok()).unwrap_or_else(|| chrono::Utc::now().timestamp()) - 1;
+    let countback = params.get("countback").and_then(|countback| countback.parse::<usize>().ok()).map(|countback| countback.clamp(1, UDF_MAX_BARS));
+    let from = match countback {
+        Some(countback) => to - countback as i64 * timeframe.to_seconds(),
+        None => params.get("from").and_then(|from| from.parse::<i64>().ok()).ok_or_else(|| udf_error("missing from"))?,
+    };
+
+    let candle_store = candle_store().await?;
+    let candles = candle_store
+        .get_candles(symbol, timeframe, from, to, true, countback.unwrap_or(UDF_MAX_BARS))
+        .await
+        .map_err(|e| {
+            println!("Error reading candle history of {} : {}", symbol, e);
+            udf_error("candle store unavailable")
+        })?;
+
+    if candles.is_empty() {
+        let previous = candle_store.get_last_candle_before(symbol, timeframe, from).await.map_err(|e| {
+            println!("Error reading previous candle of {} : {}", symbol, e);
+            udf_error("candle store unavailable")
+        })?;
+        return Ok(Json(match previous {
+            Some(previous) => json!({ "s": "no_data", "nextTime": previous.timestamp }),
+            None => json!({ "s": "no_data" }),
+        }));
+    }
+
+    let candles: Vec<_> = candles.into_iter().map(|candle| candle.denominated(denomination)).collect();
+    Ok(Json(json!({
+        "s": "ok",
+        "t": candles.iter().map(|candle| candle.timestamp).collect::<Vec<_>>(),
+        "o": candles.iter().map(|candle| candle.open).collect::<Vec<_>>(),
+        "h": candles.iter().map(|candle| candle.high).collect::<Vec<_>>(),
+        "l": candles.iter().map(|candle| candle.low).collect::<Vec<_>>(),
+        "c": candles.iter().map(|candle| candle.close).collect::<Vec<_>>(),
+        "v": candles.iter().map(|candle| candle.volume).collect::<Vec<_>>()
+    })))
+}
+
+fn alert_manager() -> Result<AlertManager, (StatusCode, Json<Value>)> {
+    AlertManager::new().map_err(|e| {
+        println!("Error creating alert manager : {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "alert store unavailable" })))
+    })
+}
+
+// the owner is whoever holds the bearer token, rules of other owners are never visible
+fn owner_from_headers(headers: &HeaderMap) -> Result<String, (StatusCode, Json<Value>)> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(owner_of_token)
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, Json(json!({ "error": "missing or invalid owner token" }))))
+}
+
+async fn create_alert(headers: HeaderMap, Json(new_rule): Json<NewAlertRule>) -> Result<(StatusCode, Json<Value>), (StatusCode, Json<Value>)> {
+    let owner = owner_from_headers(&headers)?;
+    let rule = new_rule
+        .into_rule(owner)
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))))?;
+    if let Some(webhook_url) = &rule.webhook_url {
+        resolve_webhook(webhook_url).await.map_err(|e| (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))))?;
+    }
+    alert_manager()?.save_rule(&rule).await.map_err(|e| {
+        println!("Error saving alert rule : {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "alert store unavailable" })))
+    })?;
+    Ok((StatusCode::CREATED, Json(json!(rule))))
+}
+
+async fn list_alerts(headers: HeaderMap) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let owner = owner_from_headers(&headers)?;
+    let rules = alert_manager()?.get_owner_rules(&owner).await.map_err(|e| {
+        println!("Error reading alert rules of {} : {}", owner, e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "alert store unavailable" })))
+    })?;
+    Ok(Json(json!({ "owner": owner, "alerts": rules })))
+}
+
+async fn delete_alert(
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<StatusCode, (StatusCode, Json<Value>)> {
+    let owner = owner_from_headers(&headers)?;
+    let deleted = alert_manager()?.delete_rule(&owner, &id).await.map_err(|e| {
+        println!("Error deleting alert rule {} : {}", id, e);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "alert store unavailable" })))
+    })?;
+    if deleted {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err((StatusCode::NOT_FOUND, Json(json!({ "error": "alert not found", "id": id }))))
+    }
+}
@@ -1,6 +1,6 @@
 use std::{sync::Arc, time::Duration};
 
-use shared::services::metrics_service::MetricsService;
+use shared::{redis::{pubsub_manager::PubSubManager, token_symbol_manager::TokenSymbolManager}, services::{alert_service::AlertService, metadata_resolver::MetadataResolver, metrics_service::MetricsService}};
 use tokio::time::sleep;
 
 #[tokio::main]
@@ -8,6 +8,21 @@ async fn main() {
     let metrics_service = Arc::new( MetricsService::new().expect("Error creating metrics service backgroud job"));
     println!("Starting the metrics service....");
     metrics_service.start_all_schedulers().await;
+
+    let token_manager = TokenSymbolManager::new().expect("Error creating token manager for metadata refresh");
+    let pubsub_manager = PubSubManager::new().expect("Error creating pubsub manager for metadata refresh");
+    let metadata_resolver = MetadataResolver::new(token_manager.clone(), pubsub_manager);
+    tokio::spawn(async move {
+        println!("Started token metadata refresh job");
+        metadata_resolver.start_refresh_job(token_manager).await;
+    });
+
+    let alert_pubsub = PubSubManager::new().expect("Error creating pubsub manager for alerts");
+    let alert_service = Arc::new(AlertService::new(alert_pubsub));
+    tokio::spawn(async move {
+        println!("Started alert engine");
+        alert_service.start().await;
+    });
     
     // Keep the main thread alive indefinitely
     loop {
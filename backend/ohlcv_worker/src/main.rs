@@ -1,9 +1,15 @@
+use std::{sync::Arc, time::Duration};
+
 use dotenvy::dotenv;
-// use sea_orm::Database;
 use shared::{
+    services::{candle_store::CandleStore, registry_store::RegistryStore},
     workers::ohlcv_worker::OHLCVWorker,
 };
 
+const CANDLE_FLUSH_INTERVAL: Duration = Duration::from_secs(15);
+const CANDLE_PRUNE_INTERVAL: Duration = Duration::from_secs(86400);
+const REGISTRY_FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
     dotenv().ok();
@@ -12,19 +18,46 @@ async fn main() -> Result<(), anyhow::Error> {
     println!("║          🕯️  OHLCV WORKER STARTING                  ║");
     println!("╚════════════════════════════════════════════════════╝");
 
-    // // Get database URL from environment
-    // let database_url = std::env::var("DATABASE_URL")
-    //     .expect("❌ DATABASE_URL must be set in .env file");
+    // closed candles are moved from redis to postgres in the background
+    let candle_store = Arc::new(CandleStore::connect().await?);
+    let flusher = candle_store.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(CANDLE_FLUSH_INTERVAL).await;
+            match flusher.flush_closed_candles().await {
+                Ok(0) => {}
+                Ok(flushed) => println!("Flushed {} closed candles", flushed),
+                Err(e) => println!("Error flushing closed candles : {}", e),
+            }
+        }
+    });
+    tokio::spawn(async move {
+        loop {
+            match candle_store.prune().await {
+                Ok(0) => {}
+                Ok(deleted) => println!("Pruned {} candles past retention", deleted),
+                Err(e) => println!("Error pruning candles : {}", e),
+            }
+            tokio::time::sleep(CANDLE_PRUNE_INTERVAL).await;
+        }
+    });
 
-    // // Connect to database
-    // println!("📦 Connecting to PostgreSQL...");
-    // let db = Database::connect(&database_url).await?;
-    // println!("✅ Database connected!");
-    // println!();
+    // tokens, pools and alert rules changed in redis are copied to postgres the same way
+    let registry_store = RegistryStore::connect().await?;
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(REGISTRY_FLUSH_INTERVAL).await;
+            match registry_store.flush().await {
+                Ok(0) => {}
+                Ok(flushed) => println!("Flushed {} tokens, pools and alert rules", flushed),
+                Err(e) => println!("Error flushing tokens, pools and alert rules : {}", e),
+            }
+        }
+    });
 
     // Initialize queue manager
-    let ohlcv_worker  = OHLCVWorker::new();
+    let ohlcv_worker  = Arc::new(OHLCVWorker::new());
     ohlcv_worker.start_processing(std::env::var("OHLCV_CONSUMER_GROUP").expect("unable to find consumer group from env"), std::env::var("OHLCV_WORKER").expect("unable to find consume name from env")).await;
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -1,12 +1,26 @@
 pub use sea_orm_migration::prelude::*;
 
-mod m20220101_000001_create_table;
+mod m20261019_000001_create_ohlcv_candle;
+mod m20261019_000002_add_candle_denominations;
+mod m20261019_000003_add_candle_revision;
+mod m20261019_000004_create_swaps;
+mod m20261019_000005_create_tokens;
+mod m20261019_000006_create_pools;
+mod m20261019_000007_create_alerts;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20220101_000001_create_table::Migration)]
+        vec![
+            Box::new(m20261019_000001_create_ohlcv_candle::Migration),
+            Box::new(m20261019_000002_add_candle_denominations::Migration),
+            Box::new(m20261019_000003_add_candle_revision::Migration),
+            Box::new(m20261019_000004_create_swaps::Migration),
+            Box::new(m20261019_000005_create_tokens::Migration),
+            Box::new(m20261019_000006_create_pools::Migration),
+            Box::new(m20261019_000007_create_alerts::Migration),
+        ]
     }
 }
@@ -0,0 +1,75 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(OhlcvCandle::Table)
+                    .if_not_exists()
+                    .col(string(OhlcvCandle::TokenPair))
+                    .col(string_len(OhlcvCandle::Timeframe, 8))
+                    .col(timestamp(OhlcvCandle::Timestamp))
+                    .col(double(OhlcvCandle::OpenPrice))
+                    .col(double(OhlcvCandle::HighPrice))
+                    .col(double(OhlcvCandle::LowPrice))
+                    .col(double(OhlcvCandle::ClosePrice))
+                    .col(double(OhlcvCandle::Volume))
+                    .col(double(OhlcvCandle::BuyVolume).default(0.0))
+                    .col(double(OhlcvCandle::SellVolume).default(0.0))
+                    .col(big_integer(OhlcvCandle::TradeCount).default(0))
+                    .col(timestamp(OhlcvCandle::CreatedAt).default(Expr::current_timestamp()))
+                    .col(timestamp(OhlcvCandle::UpdatedAt).default(Expr::current_timestamp()))
+                    // flushes upsert on this key, it also serves range reads of one pair and timeframe
+                    .primary_key(
+                        Index::create()
+                            .col(OhlcvCandle::TokenPair)
+                            .col(OhlcvCandle::Timeframe)
+                            .col(OhlcvCandle::Timestamp),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // retention pruning deletes by timeframe and age across all pairs
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_ohlcv_candle_timeframe_timestamp")
+                    .table(OhlcvCandle::Table)
+                    .col(OhlcvCandle::Timeframe)
+                    .col(OhlcvCandle::Timestamp)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(OhlcvCandle::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum OhlcvCandle {
+    Table,
+    TokenPair,
+    Timeframe,
+    Timestamp,
+    OpenPrice,
+    HighPrice,
+    LowPrice,
+    ClosePrice,
+    Volume,
+    BuyVolume,
+    SellVolume,
+    TradeCount,
+    CreatedAt,
+    UpdatedAt,
+}
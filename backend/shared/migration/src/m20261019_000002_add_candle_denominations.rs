@@ -0,0 +1,51 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // rows written before quote prices were tracked keep 0, which readers treat as unknown
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(OhlcvCandle::Table)
+                    .add_column_if_not_exists(double(OhlcvCandle::QuoteOpenPrice).default(0.0))
+                    .add_column_if_not_exists(double(OhlcvCandle::QuoteHighPrice).default(0.0))
+                    .add_column_if_not_exists(double(OhlcvCandle::QuoteLowPrice).default(0.0))
+                    .add_column_if_not_exists(double(OhlcvCandle::QuoteClosePrice).default(0.0))
+                    .add_column_if_not_exists(double(OhlcvCandle::QuoteVolume).default(0.0))
+                    .add_column_if_not_exists(double(OhlcvCandle::UsdVolume).default(0.0))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(OhlcvCandle::Table)
+                    .drop_column(OhlcvCandle::QuoteOpenPrice)
+                    .drop_column(OhlcvCandle::QuoteHighPrice)
+                    .drop_column(OhlcvCandle::QuoteLowPrice)
+                    .drop_column(OhlcvCandle::QuoteClosePrice)
+                    .drop_column(OhlcvCandle::QuoteVolume)
+                    .drop_column(OhlcvCandle::UsdVolume)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum OhlcvCandle {
+    Table,
+    QuoteOpenPrice,
+    QuoteHighPrice,
+    QuoteLowPrice,
+    QuoteClosePrice,
+    QuoteVolume,
+    UsdVolume,
+}
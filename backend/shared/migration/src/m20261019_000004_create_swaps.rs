@@ -0,0 +1,92 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Swaps::Table)
+                    .if_not_exists()
+                    .col(string_len(Swaps::Signature, 88))
+                    .col(integer(Swaps::Leg).default(0))
+                    .col(string(Swaps::TokenPair))
+                    .col(string(Swaps::TokenMint))
+                    .col(string(Swaps::Wallet))
+                    .col(string_len(Swaps::Side, 4))
+                    .col(double(Swaps::TokenQuantity))
+                    .col(double(Swaps::QuoteQuantity))
+                    .col(double(Swaps::TokenPrice))
+                    .col(double_null(Swaps::UsdValue))
+                    .col(string(Swaps::DexType))
+                    .col(string(Swaps::DexTag))
+                    .col(big_integer(Swaps::Slot).default(0))
+                    .col(big_integer(Swaps::TxIndex).default(0))
+                    .col(timestamp(Swaps::BlockTime))
+                    .col(timestamp(Swaps::CreatedAt).default(Expr::current_timestamp()))
+                    // inserts skip on this key, it also serves lookups by signature
+                    .primary_key(Index::create().col(Swaps::Signature).col(Swaps::Leg))
+                    .to_owned(),
+            )
+            .await?;
+
+        // recent trades of a market and of a wallet, newest first
+        for (name, column) in [
+            ("idx_swaps_token_pair_block_time", Swaps::TokenPair),
+            ("idx_swaps_wallet_block_time", Swaps::Wallet),
+        ] {
+            manager
+                .create_index(
+                    Index::create()
+                        .name(name)
+                        .table(Swaps::Table)
+                        .col(column)
+                        .col(Swaps::BlockTime)
+                        .if_not_exists()
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_swaps_block_time")
+                    .table(Swaps::Table)
+                    .col(Swaps::BlockTime)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Swaps::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Swaps {
+    Table,
+    Signature,
+    Leg,
+    TokenPair,
+    TokenMint,
+    Wallet,
+    Side,
+    TokenQuantity,
+    QuoteQuantity,
+    TokenPrice,
+    UsdValue,
+    DexType,
+    DexTag,
+    Slot,
+    TxIndex,
+    BlockTime,
+    CreatedAt,
+}
@@ -0,0 +1,77 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Tokens::Table)
+                    .if_not_exists()
+                    .col(string_len(Tokens::Mint, 44).primary_key())
+                    .col(string_null(Tokens::Symbol))
+                    .col(string_null(Tokens::Name))
+                    .col(text_null(Tokens::Uri))
+                    .col(text_null(Tokens::Image))
+                    .col(text_null(Tokens::Description))
+                    .col(text_null(Tokens::Website))
+                    .col(text_null(Tokens::Twitter))
+                    .col(text_null(Tokens::Telegram))
+                    .col(text_null(Tokens::Discord))
+                    .col(small_integer_null(Tokens::Decimals))
+                    .col(double_null(Tokens::TotalSupply))
+                    .col(double_null(Tokens::CirculatingSupply))
+                    .col(string_len_null(Tokens::UpdateAuthority, 44))
+                    .col(string_len_null(Tokens::MintAuthority, 44))
+                    .col(string_len_null(Tokens::FreezeAuthority, 44))
+                    .col(timestamp(Tokens::CreatedAt).default(Expr::current_timestamp()))
+                    .col(timestamp(Tokens::UpdatedAt).default(Expr::current_timestamp()))
+                    .to_owned(),
+            )
+            .await?;
+
+        // pairs are labelled by symbol, search goes through it
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_tokens_symbol")
+                    .table(Tokens::Table)
+                    .col(Tokens::Symbol)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Tokens::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Tokens {
+    Table,
+    Mint,
+    Symbol,
+    Name,
+    Uri,
+    Image,
+    Description,
+    Website,
+    Twitter,
+    Telegram,
+    Discord,
+    Decimals,
+    TotalSupply,
+    CirculatingSupply,
+    UpdateAuthority,
+    MintAuthority,
+    FreezeAuthority,
+    CreatedAt,
+    UpdatedAt,
+}
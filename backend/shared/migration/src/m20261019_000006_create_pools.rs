@@ -0,0 +1,84 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Pools::Table)
+                    .if_not_exists()
+                    // the pool state account, or the token-side vault when it is unknown, see `PoolInfo`
+                    .col(string_len(Pools::PoolId, 44).primary_key())
+                    .col(string_len(Pools::PoolOwner, 44))
+                    .col(string_len(Pools::ProgramId, 44).default(""))
+                    .col(string_len(Pools::DexTag, 8))
+                    .col(string(Pools::TokenPair))
+                    .col(string_len(Pools::TokenMint, 44))
+                    .col(string_len(Pools::TokenVault, 44))
+                    .col(string_len(Pools::SolVault, 44))
+                    .col(integer(Pools::TokenDecimals))
+                    .col(integer(Pools::SolDecimals))
+                    .col(double(Pools::TokenReserve).default(0.0))
+                    .col(double(Pools::SolReserve).default(0.0))
+                    .col(double(Pools::TokenFees).default(0.0))
+                    .col(double(Pools::SolFees).default(0.0))
+                    .col(double(Pools::LiquidityUsd).default(0.0))
+                    .col(double_null(Pools::ActiveLiquidityUsd))
+                    .col(timestamp(Pools::CreatedAt).default(Expr::current_timestamp()))
+                    .col(timestamp(Pools::UpdatedAt).default(Expr::current_timestamp()))
+                    .to_owned(),
+            )
+            .await?;
+
+        // pair liquidity sums every pool of a pair, token pages list the pools of a mint
+        for (name, column) in [
+            ("idx_pools_token_pair", Pools::TokenPair),
+            ("idx_pools_token_mint", Pools::TokenMint),
+        ] {
+            manager
+                .create_index(
+                    Index::create()
+                        .name(name)
+                        .table(Pools::Table)
+                        .col(column)
+                        .if_not_exists()
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Pools::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Pools {
+    Table,
+    PoolId,
+    PoolOwner,
+    ProgramId,
+    DexTag,
+    TokenPair,
+    TokenMint,
+    TokenVault,
+    SolVault,
+    TokenDecimals,
+    SolDecimals,
+    TokenReserve,
+    SolReserve,
+    TokenFees,
+    SolFees,
+    LiquidityUsd,
+    ActiveLiquidityUsd,
+    CreatedAt,
+    UpdatedAt,
+}
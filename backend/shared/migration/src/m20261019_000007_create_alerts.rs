@@ -0,0 +1,64 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Alerts::Table)
+                    .if_not_exists()
+                    .col(string_len(Alerts::Id, 32).primary_key())
+                    .col(string(Alerts::Owner))
+                    .col(string(Alerts::TokenPair))
+                    // the tagged `AlertCondition`, new condition types need no migration
+                    .col(json_binary(Alerts::Condition))
+                    .col(text_null(Alerts::WebhookUrl))
+                    .col(big_integer(Alerts::CooldownSecs).default(300))
+                    .col(timestamp_null(Alerts::LastTriggeredAt))
+                    .col(timestamp(Alerts::CreatedAt).default(Expr::current_timestamp()))
+                    .to_owned(),
+            )
+            .await?;
+
+        // rules are listed per owner and evaluated per pair
+        for (name, column) in [
+            ("idx_alerts_owner", Alerts::Owner),
+            ("idx_alerts_token_pair", Alerts::TokenPair),
+        ] {
+            manager
+                .create_index(
+                    Index::create()
+                        .name(name)
+                        .table(Alerts::Table)
+                        .col(column)
+                        .if_not_exists()
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Alerts::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Alerts {
+    Table,
+    Id,
+    Owner,
+    TokenPair,
+    Condition,
+    WebhookUrl,
+    CooldownSecs,
+    LastTriggeredAt,
+    CreatedAt,
+}
@@ -122,7 +122,7 @@ impl ElasticSearchClient {
             println!("✅ Successfully indexed pair: {}", token_pair);
             Ok(())
         } else {
-            Err(ElasticSearchError::IndexError(format!("Indexing failed")))
+            Err(ElasticSearchError::IndexError("Indexing failed".to_string()))
         }
     }
 
@@ -223,9 +223,9 @@ impl ElasticSearchClient {
             })?;
             self.parse_response_data(search_json)
         } else {
-            Err(ElasticSearchError::SearchError(format!(
-                "Unable to parse the search response for search term"
-            )))
+            Err(ElasticSearchError::SearchError(
+                "Unable to parse the search response for search term".to_string()
+            ))
         }
     }
 
@@ -237,9 +237,9 @@ impl ElasticSearchClient {
             .as_array() // we're using the same dot notation as JS, but only syntax different and we need to explicitly mention the type of value that field contains using (as_str(), as_f64, as_array...etc)
             .ok_or_else(|| {
                 println!("Unable to find hits field in the response");
-                ElasticSearchError::SearchError(format!(
-                    "Unable to retrieve hits array from response"
-                ))
+                ElasticSearchError::SearchError(
+                    "Unable to retrieve hits array from response".to_string()
+                )
             })?; // this is used for return type of Option<T> and map_err is used for Result<T,E>
 
         let search_result_array: Vec<SearchResult> = hits
@@ -1 +1,5 @@
-pub mod ohlcv_candles;
\ No newline at end of file
+pub mod ohlcv_candles;
+pub mod swaps;
+pub mod tokens;
+pub mod pools;
+pub mod alerts;
\ No newline at end of file
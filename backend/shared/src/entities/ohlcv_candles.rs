@@ -1,18 +1,17 @@
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
+// one row per pair, timeframe and bucket so a re-flushed candle upserts in place
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Deserialize, Serialize)]
 #[sea_orm(table_name = "ohlcv_candle")]
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
-    pub id: Uuid,
-    
-    #[sea_orm(indexed)]
     pub token_pair: String,
-    
+
+    #[sea_orm(primary_key, auto_increment = false)]
     pub timeframe: String,
-    
-    #[sea_orm(indexed)]
+
+    #[sea_orm(primary_key, auto_increment = false)]
     pub timestamp: DateTime,
     
     pub open_price: f64,
@@ -20,9 +19,19 @@ pub struct Model {
     pub low_price: f64,
     pub close_price: f64,
     pub volume: f64,
-    pub trade_count: u32,
+    pub buy_volume: f64,
+    pub sell_volume: f64,
+    pub trade_count: i64,
+    pub quote_open_price: f64,
+    pub quote_high_price: f64,
+    pub quote_low_price: f64,
+    pub quote_close_price: f64,
+    pub quote_volume: f64,
+    pub usd_volume: f64,
+    pub revision: i32,
     
     pub created_at: DateTime,
+    pub updated_at: DateTime,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter)]
@@ -34,4 +43,4 @@ impl RelationTrait for Relation {
     }
 }
 
-impl ActiveModelBehavior for ActiveModel {}
\ No newline at end of file
+impl ActiveModelBehavior for ActiveModel {}
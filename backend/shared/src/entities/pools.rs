@@ -0,0 +1,40 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// one row per pool with its last known reserves
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Deserialize, Serialize)]
+#[sea_orm(table_name = "pools")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub pool_id: String,
+
+    pub pool_owner: String,
+    pub program_id: String,
+    pub dex_tag: String,
+    pub token_pair: String,
+    pub token_mint: String,
+    pub token_vault: String,
+    pub sol_vault: String,
+    pub token_decimals: i32,
+    pub sol_decimals: i32,
+    pub token_reserve: f64,
+    pub sol_reserve: f64,
+    pub token_fees: f64,
+    pub sol_fees: f64,
+    pub liquidity_usd: f64,
+    pub active_liquidity_usd: Option<f64>,
+
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No RelationDef")
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,42 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// one row per swap, a replayed stream message hits the same signature and leg and is skipped.
+// the txn worker parses a single swap per transaction and always sets leg 0, so the other swaps of a
+// multi-hop or batched transaction are not stored yet
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Deserialize, Serialize)]
+#[sea_orm(table_name = "swaps")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub signature: String,
+
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub leg: i32,
+
+    pub token_pair: String,
+    pub token_mint: String,
+    pub wallet: String,
+    pub side: String,
+    pub token_quantity: f64,
+    pub quote_quantity: f64,
+    pub token_price: f64,
+    pub usd_value: Option<f64>,
+    pub dex_type: String,
+    pub dex_tag: String,
+    pub slot: i64,
+    pub tx_index: i64,
+    pub block_time: DateTime,
+
+    pub created_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No RelationDef")
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
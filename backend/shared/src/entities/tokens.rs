@@ -0,0 +1,40 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// token metadata and supply, the durable copy of what the metadata resolver caches in redis
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Deserialize, Serialize)]
+#[sea_orm(table_name = "tokens")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    pub mint: String,
+
+    pub symbol: Option<String>,
+    pub name: Option<String>,
+    pub uri: Option<String>,
+    pub image: Option<String>,
+    pub description: Option<String>,
+    pub website: Option<String>,
+    pub twitter: Option<String>,
+    pub telegram: Option<String>,
+    pub discord: Option<String>,
+    pub decimals: Option<i16>,
+    pub total_supply: Option<f64>,
+    pub circulating_supply: Option<f64>,
+    pub update_authority: Option<String>,
+    pub mint_authority: Option<String>,
+    pub freeze_authority: Option<String>,
+
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        panic!("No RelationDef")
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -24,9 +24,8 @@ impl StreamManager {
 
     pub async fn publish(&self, txn : StructeredTransaction) -> RedisResult<String>{
         let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
-        let txn_json = serde_json::to_string(&txn).map_err(|e| {
+        let txn_json = serde_json::to_string(&txn).inspect_err(|_| {
             println!("unable to serialize txn for redis stream");
-            e
         })?;
 
         let id : String = redis::cmd("XADD")
@@ -40,27 +39,38 @@ impl StreamManager {
         Ok(id)
     }
     pub async fn consume(&self, consumer_group : &String, consumer_name : &String) -> RedisResult<Option<(String, StructeredTransaction)>>{
+        let mut messages = self.read_group(consumer_group, consumer_name, 1, ">").await?;
+        Ok(messages.pop())
+    }
+
+    // up to `count` new messages, blocks for a second when the stream is drained
+    pub async fn consume_batch(&self, consumer_group : &String, consumer_name : &String, count : usize) -> RedisResult<Vec<(String, StructeredTransaction)>>{
+        self.read_group(consumer_group, consumer_name, count, ">").await
+    }
+
+    // messages delivered to this consumer but never acknowledged, e.g. before a crash
+    pub async fn consume_pending(&self, consumer_group : &String, consumer_name : &String, count : usize) -> RedisResult<Vec<(String, StructeredTransaction)>>{
+        self.read_group(consumer_group, consumer_name, count, "0").await
+    }
+
+    async fn read_group(&self, consumer_group : &String, consumer_name : &String, count : usize, from_id : &str) -> RedisResult<Vec<(String, StructeredTransaction)>>{
         let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
     
-        let result :Vec<Value> = redis::cmd("XREADGROUP")
+        let result :Value = redis::cmd("XREADGROUP")
                 .arg("GROUP")
                 .arg(consumer_group)
                 .arg(consumer_name)
                 .arg("COUNT")
-                .arg(1)
+                .arg(count)
                 .arg("BLOCK")
                 .arg(1000)
                 .arg("STREAMS")
                 .arg(&self.stream_name)
-                .arg(">")
+                .arg(from_id)
                 .query_async(&mut conn)
                 .await?;
 
-        if let Some(stream_data) = self.parse_stream_response(result){
-            return Ok(Some(stream_data))
-        }
-        Ok(None)
-        
+        Ok(self.parse_stream_response(result))
     }
 
     pub async fn init_stream(&self, consumer_group : &String) -> RedisResult<()>{
@@ -69,7 +79,7 @@ impl StreamManager {
         let res : RedisResult<()> = redis::cmd("XGROUP")
                 .arg("CREATE")
                 .arg(&self.stream_name)
-                .arg(&consumer_group)
+                .arg(consumer_group)
                 .arg("0")
                 .arg("MKSTREAM")
                 .query_async(&mut conn)
@@ -77,84 +87,73 @@ impl StreamManager {
 
         println!("Succesfully created group : {}", consumer_group);
 
-        if let Err(e) = res{
-            if e.to_string().contains("BUSYGROUP"){
-                println!("Group already exist...continue")
-            }
+        if let Err(e) = res
+            && e.to_string().contains("BUSYGROUP"){
+            println!("Group already exist...continue")
         }
         Ok(())
     }
 
-    fn parse_stream_response(&self, result : Vec<Value>) -> Option<(String, StructeredTransaction)>{
-        if result.is_empty(){
-            return None;
-        }
-
-        if let Value::Array(streams) = &result[0]{
-            if streams.len() < 2 {
-                return None;
+    // XREADGROUP replies [[stream, [[id, [field, value, ...]], ...]]], or nil when the block timed out
+    fn parse_stream_response(&self, result : Value) -> Vec<(String, StructeredTransaction)>{
+        let mut parsed = Vec::new();
+        let Value::Array(streams) = result else { return parsed };
+        let Some(Value::Array(stream)) = streams.first() else { return parsed };
+        let Some(Value::Array(messages)) = stream.get(1) else {
+            println!("Could not parse stream structure from response");
+            return parsed;
+        };
+
+        for message in messages{
+            let Value::Array(msg) = message else { continue };
+            if msg.len() < 2 {
+                continue;
             }
 
-            if let Value::Array(messages) = &streams[1]{
-                if messages.is_empty(){
-                    return None;
+            let message_id = match &msg[0]{
+                Value::BulkString(id) => String::from_utf8_lossy(id).to_string(),
+                Value::SimpleString(id) => id.to_owned(),
+                _ => {
+                    println!("Unexpected message id type : {:?}", msg[0]);
+                    continue;
                 }
-
-                if let Value::Array(msg) = &messages[0]{
-                    if msg.is_empty(){
-                        return None;
+            };
+
+            // pending entries that were deleted from the stream come back with nil fields
+            let Value::Array(fields) = &msg[1] else { continue };
+            for i in (0..fields.len()).step_by(2){
+                let field_name = match &fields[i]{
+                    Value::BulkString(name) => name.as_slice(), // converts vec<bytes> (vec<u8>) to slice of bytes [u8]
+                    Value::SimpleString(name) => name.as_bytes(), // converts the string to [u8]
+                    _ => {
+                        println!("unable to identify field name type : {:?}", fields[i]);
+                        continue;
                     }
-
-                    let message_id = match &msg[0]{
-                        Value::BulkString(id) => String::from_utf8_lossy(id).to_string(),
-                        Value::SimpleString(id) => id.to_owned(),
+                };
+                // the i + 1 < field.len check is for safety purpose, to ensure while reading data we dont go out of bonds
+                if field_name == b"data" && i + 1 < fields.len(){ // b before data stands for bytes. it means give the byte representation on text 'data'
+                    let json_str = match &fields[i+1]{
+                        Value::BulkString(json) => String::from_utf8_lossy(json).to_string(),
+                        Value::SimpleString(json) => json.to_string(),
                         _ => {
-                            println!("Unexpected message id type : {:?}", msg[0]);
-                            return None;
+                            println!("unexpected json message type received : {:?}", fields[i+1]);
+                            continue;
                         }
                     };
 
-                    if let Value::Array(fields) = &msg[1]{
-                        for i in (0..fields.len()).step_by(2){
-                            let field_name = match &fields[i]{
-                                Value::BulkString(name) => name.as_slice(), // converts vec<bytes> (vec<u8>) to slice of bytes [u8]
-                                Value::SimpleString(name) => name.as_bytes(), // converts the string to [u8]
-                                _ => {
-                                    println!("unable to identify field name type : {:?}", fields[i]);
-                                    return None;
-                                }
-                            };
-                            // the i + 1 < field.len check is for safety purpose, to ensure while reading data we dont go out of bonds
-                            if field_name == b"data" && i + 1 < fields.len(){ // b before data stands for bytes. it means give the byte representation on text 'data'
-                                let json_str = match &fields[i+1]{
-                                    Value::BulkString(json) => String::from_utf8_lossy(&json).to_string(),
-                                    Value::SimpleString(json) => json.to_string(),
-                                    _ => {
-                                        println!("unexpected json message type received : {:?}", fields[i+1]);
-                                        return None
-                                    }
-                                };
-
-                                match serde_json::from_str::<StructeredTransaction>(&json_str){
-                                    Ok(txn) => {
-                                        println!("parsed succesfully txn from stream");    
-                                        Some((&message_id, txn));
-                                    },
-                                    Err(e) => {
-                                        println!("unable to deserialize the txn from stream : {}",e);
-                                        return None;
-                                    }
-                                }
-
-                            }
-
+                    match serde_json::from_str::<StructeredTransaction>(&json_str){
+                        Ok(txn) => {
+                            println!("parsed succesfully txn from stream");
+                            parsed.push((message_id.clone(), txn));
+                        },
+                        Err(e) => {
+                            println!("unable to deserialize the txn from stream : {}",e);
                         }
                     }
                 }
             }
         }
-        println!("Could not parse stream structure from response");
-        return None
+        parsed
     }
 
     pub async fn ack(&self, consumer_group : &String, message_id : &String) -> RedisResult<()>{
@@ -163,11 +162,50 @@ impl StreamManager {
         let _ : i32 = redis::cmd("XACK")
                     .arg(&self.stream_name)
                     .arg(consumer_group)
-                    .arg(&message_id)
+                    .arg(message_id)
                     .query_async(&mut conn)
                     .await?;
         
         println!("Acknowledged message : {}", message_id);
         Ok(())
     }
-}
\ No newline at end of file
+
+    pub async fn ack_many(&self, consumer_group : &String, message_ids : &[String]) -> RedisResult<()>{
+        if message_ids.is_empty(){
+            return Ok(());
+        }
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+
+        let acked : i32 = redis::cmd("XACK")
+                    .arg(&self.stream_name)
+                    .arg(consumer_group)
+                    .arg(message_ids)
+                    .query_async(&mut conn)
+                    .await?;
+
+        println!("Acknowledged {} messages", acked);
+        Ok(())
+    }
+
+    // parks a message a consumer gave up on in `dead_letter_stream` with the reason, for inspection or a manual replay
+    pub async fn dead_letter(&self, dead_letter_stream : &str, message_id : &str, txn : &StructeredTransaction, error : &str) -> RedisResult<String>{
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let txn_json = serde_json::to_string(txn).inspect_err(|_| {
+            println!("unable to serialize txn for the dead letter stream");
+        })?;
+
+        let id : String = redis::cmd("XADD")
+                .arg(dead_letter_stream)
+                .arg("*")
+                .arg("message_id")
+                .arg(message_id)
+                .arg("data")
+                .arg(txn_json)
+                .arg("error")
+                .arg(error)
+                .query_async(&mut conn)
+                .await?;
+        println!("Dead lettered message {} as {}", message_id, id);
+        Ok(id)
+    }
+}
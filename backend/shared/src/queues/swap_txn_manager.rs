@@ -1,6 +1,7 @@
+use chrono::Utc;
 use redis::{AsyncCommands, Client, RedisError, RedisResult};
 
-use crate::types::grpc::{CustomTokenBalance, TransactionMetadata};
+use crate::types::{grpc::{CustomTokenBalance, DexInstruction, TransactionMetadata}, worker::TradeOrder};
 #[derive(Debug)]
 pub struct SwapTxnQueueManager {
     redis_client: Client,
@@ -14,13 +15,17 @@ impl SwapTxnQueueManager {
             e
         })?;
         Ok(Self {
-            redis_client: redis_client,
+            redis_client,
         })
     }
 
     pub async fn enqueue_message(
         &self,
         txn_meta: yellowstone_grpc_proto::solana::storage::confirmed_block::TransactionStatusMeta,
+        account_keys: Vec<String>,
+        order: TradeOrder,
+        signature: String,
+        dex_instructions: Vec<DexInstruction>,
     ) -> RedisResult<usize> {
         let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
         println!("------METADATA------");
@@ -30,7 +35,7 @@ impl SwapTxnQueueManager {
         println!("Pre Token Balances : {:?}", txn_meta.pre_token_balances);
         println!("Post Token Balances : {:?}", txn_meta.post_token_balances);
 
-        let metadata = self.get_metadata(txn_meta);
+        let metadata = self.get_metadata(txn_meta, account_keys, order, signature, dex_instructions);
         let txn_json = serde_json::to_string(&metadata).expect("Error serializing the txn meta");
 
         let queue_length: usize = conn.lpush("swap_transactions", txn_json).await?;
@@ -54,9 +59,61 @@ impl SwapTxnQueueManager {
         }
     }
 
+    // swaps of a mint whose metadata is still resolving wait here, so the mint's market is keyed only once
+    pub async fn hold_message(&self, mint_address: &str, txn_meta: &TransactionMetadata) -> RedisResult<()> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let txn_json = serde_json::to_string(txn_meta).expect("Error serializing the txn meta");
+        let _: () = redis::pipe()
+            .atomic()
+            .rpush(format!("swap_hold:{}", mint_address), txn_json)
+            .ignore()
+            .cmd("ZADD")
+            .arg("swap_hold")
+            .arg("NX")
+            .arg(Utc::now().timestamp())
+            .arg(mint_address)
+            .ignore()
+            .query_async(&mut conn)
+            .await?;
+        println!("held swap until metadata resolves for mint : {}", mint_address);
+        Ok(())
+    }
+
+    // mints with held swaps and when their first swap was held
+    pub async fn get_held_mints(&self) -> RedisResult<Vec<(String, i64)>> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        conn.zrange_withscores("swap_hold", 0, -1).await
+    }
+
+    pub async fn release_held(&self, mint_address: &str) -> RedisResult<Vec<TransactionMetadata>> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let key = format!("swap_hold:{}", mint_address);
+        let (messages,): (Vec<String>,) = redis::pipe()
+            .atomic()
+            .lrange(&key, 0, -1)
+            .del(&key)
+            .ignore()
+            .zrem("swap_hold", mint_address)
+            .ignore()
+            .query_async(&mut conn)
+            .await?;
+        Ok(messages
+            .iter()
+            .filter_map(|message| {
+                serde_json::from_str::<TransactionMetadata>(message)
+                    .inspect_err(|e| println!("Failed to desearilize held txn message {}", e))
+                    .ok()
+            })
+            .collect())
+    }
+
     fn get_metadata(
         &self,
         txn_meta: yellowstone_grpc_proto::solana::storage::confirmed_block::TransactionStatusMeta,
+        account_keys: Vec<String>,
+        order: TradeOrder,
+        signature: String,
+        dex_instructions: Vec<DexInstruction>,
     ) -> TransactionMetadata {
         let custom_pre_token_balances = txn_meta
             .pre_token_balances
@@ -72,6 +129,10 @@ impl SwapTxnQueueManager {
             log_messages: txn_meta.log_messages,
             pre_token_balances: custom_pre_token_balances,
             post_token_balances: custom_post_token_balances,
+            account_keys,
+            order,
+            signature,
+            dex_instructions,
         }
     }
 }
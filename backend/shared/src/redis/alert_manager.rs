@@ -0,0 +1,152 @@
+use redis::{AsyncCommands, Client, RedisError, RedisResult};
+
+use crate::types::alert::AlertRule;
+
+const ALERT_RULES_KEY: &str = "alerts:rules";
+const LAST_TRIGGERED_KEY: &str = "alerts:last-triggered";
+const DIRTY_ALERTS_KEY: &str = "alerts:dirty"; // rules created, deleted or fired since the last postgres flush
+const EVENT_DEDUP_SECS: u64 = 3600;
+
+// rules are one hash keyed by id with an index per owner, firing state lives in short lived NX keys
+#[derive(Debug, Clone)]
+pub struct AlertManager {
+    redis_client: Client,
+}
+
+impl AlertManager {
+    pub fn new() -> Result<Self, RedisError> {
+        println!("Initializing redis client...for ALERTS");
+        let redis_client = Client::open(std::env::var("REDIS_URL").expect("unable to fetch redis url from env")).map_err(|e| {
+            println!("Couldn't initialize a redis client : {}", e);
+            e
+        })?;
+        Ok(Self {
+            redis_client,
+        })
+    }
+
+    pub async fn save_rule(&self, rule: &AlertRule) -> Result<(), anyhow::Error> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let _: () = redis::pipe()
+            .atomic()
+            .hset(ALERT_RULES_KEY, &rule.id, serde_json::to_string(rule)?).ignore()
+            .sadd(format!("alerts:owner:{}", rule.owner), &rule.id).ignore()
+            .sadd(DIRTY_ALERTS_KEY, &rule.id).ignore()
+            .query_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    // returns false when the rule does not exist or belongs to another owner
+    pub async fn delete_rule(&self, owner: &str, id: &str) -> RedisResult<bool> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let owner_key = format!("alerts:owner:{}", owner);
+        let owned: bool = conn.sismember(&owner_key, id).await?;
+        if !owned {
+            return Ok(false);
+        }
+        let _: () = redis::pipe()
+            .atomic()
+            .hdel(ALERT_RULES_KEY, id).ignore()
+            .srem(&owner_key, id).ignore()
+            .del(format!("alerts:{}:cooldown", id)).ignore()
+            .hdel(LAST_TRIGGERED_KEY, id).ignore()
+            .sadd(DIRTY_ALERTS_KEY, id).ignore()
+            .query_async(&mut conn)
+            .await?;
+        Ok(true)
+    }
+
+    pub async fn get_rules(&self) -> Result<Vec<AlertRule>, anyhow::Error> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let rules: Vec<String> = conn.hvals(ALERT_RULES_KEY).await?;
+        Ok(parse_rules(rules))
+    }
+
+    pub async fn get_rule(&self, id: &str) -> Result<Option<AlertRule>, anyhow::Error> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let rule: Option<String> = conn.hget(ALERT_RULES_KEY, id).await?;
+        Ok(rule.and_then(|rule| parse_rules(vec![rule]).pop()))
+    }
+
+    // unix seconds of the last delivery of a rule
+    pub async fn get_last_triggered(&self, id: &str) -> RedisResult<Option<i64>> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        conn.hget(LAST_TRIGGERED_KEY, id).await
+    }
+
+    pub async fn take_dirty_rules(&self, count: usize) -> RedisResult<Vec<String>> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        redis::cmd("SPOP").arg(DIRTY_ALERTS_KEY).arg(count).query_async(&mut conn).await
+    }
+
+    // puts back rules whose flush failed
+    pub async fn mark_rules_dirty(&self, ids: &[String]) -> RedisResult<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        conn.sadd(DIRTY_ALERTS_KEY, ids).await
+    }
+
+    pub async fn get_owner_rules(&self, owner: &str) -> Result<Vec<AlertRule>, anyhow::Error> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let ids: Vec<String> = conn.smembers(format!("alerts:owner:{}", owner)).await?;
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let rules: Vec<Option<String>> = redis::cmd("HMGET").arg(ALERT_RULES_KEY).arg(&ids).query_async(&mut conn).await?;
+        Ok(parse_rules(rules.into_iter().flatten().collect()))
+    }
+
+    /// Claims the right to deliver an alert.
+    ///
+    /// The event key drops duplicates of the same event (a message seen twice, or by two engines), the
+    /// cooldown key then rate limits the rule. Both are SET NX so concurrent engines agree on one winner.
+    pub async fn try_fire(&self, rule: &AlertRule, event_key: &str) -> RedisResult<bool> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let fresh_event: bool = redis::cmd("SET")
+            .arg(format!("alerts:{}:event:{}", rule.id, event_key))
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(EVENT_DEDUP_SECS)
+            .query_async::<Option<String>>(&mut conn)
+            .await?
+            .is_some();
+        let fired = if !fresh_event || rule.cooldown_secs <= 0 {
+            fresh_event
+        } else {
+            let cooled_down: Option<String> = redis::cmd("SET")
+                .arg(format!("alerts:{}:cooldown", rule.id))
+                .arg(1)
+                .arg("NX")
+                .arg("EX")
+                .arg(rule.cooldown_secs)
+                .query_async(&mut conn)
+                .await?;
+            cooled_down.is_some()
+        };
+        if fired {
+            let _: () = redis::pipe()
+                .hset(LAST_TRIGGERED_KEY, &rule.id, chrono::Utc::now().timestamp()).ignore()
+                .sadd(DIRTY_ALERTS_KEY, &rule.id).ignore()
+                .query_async(&mut conn)
+                .await?;
+        }
+        Ok(fired)
+    }
+}
+
+fn parse_rules(rules: Vec<String>) -> Vec<AlertRule> {
+    rules
+        .into_iter()
+        .filter_map(|rule| match serde_json::from_str(&rule) {
+            Ok(rule) => Some(rule),
+            Err(e) => {
+                println!("Skipping unreadable alert rule : {}", e);
+                None
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,118 @@
+use chrono::Utc;
+use redis::{AsyncCommands, Client, RedisError, RedisResult};
+
+use crate::types::holder::{Holder, HolderStats};
+
+const HOLDERS_KEY_TTL: i64 = 7 * 86400;
+// holder count, top balances, snapshot time and dev balance, read in one pipeline
+type HolderQuery = (usize, Vec<(String, f64)>, Option<i64>, Option<f64>);
+
+// holder balances per mint in a sorted set scored by ui balance, wallets with a zero balance are removed
+#[derive(Debug, Clone)]
+pub struct HolderManager {
+    redis_client: Client,
+}
+
+impl HolderManager {
+    pub fn new() -> Result<Self, RedisError> {
+        println!("Initializing redis client...for HOLDER index");
+        let redis_client = Client::open(std::env::var("REDIS_URL").expect("unable to fetch redis url from env")).map_err(|e| {
+            println!("Couldn't initialize a redis client : {}", e);
+            e
+        })?;
+        Ok(Self {
+            redis_client,
+        })
+    }
+
+    pub async fn apply_balances(&self, mint_address: &str, balances: &[(String, f64)]) -> RedisResult<()> {
+        if balances.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let holders_key = format!("mint:{}:holders", mint_address);
+        let mut pipe = redis::pipe();
+        for (owner, balance) in balances {
+            if *balance > 0.0 {
+                pipe.zadd(&holders_key, owner, *balance).ignore();
+            } else {
+                pipe.zrem(&holders_key, owner).ignore();
+            }
+        }
+        let _: () = pipe.expire(&holders_key, HOLDERS_KEY_TTL).ignore().query_async(&mut conn).await?;
+        Ok(())
+    }
+
+    // swaps in a full scan atomically, trades applied while the scan ran are overwritten by it
+    pub async fn replace_holders(&self, mint_address: &str, balances: &[(String, f64)]) -> RedisResult<()> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let holders_key = format!("mint:{}:holders", mint_address);
+        let building_key = format!("{}:building", holders_key);
+        let members: Vec<(f64, &String)> = balances
+            .iter()
+            .filter(|(_, balance)| *balance > 0.0)
+            .map(|(owner, balance)| (*balance, owner))
+            .collect();
+
+        let mut pipe = redis::pipe();
+        pipe.atomic().del(&building_key).ignore();
+        for chunk in members.chunks(1000) {
+            pipe.zadd_multiple(&building_key, chunk).ignore();
+        }
+        if members.is_empty() {
+            pipe.del(&holders_key).ignore();
+        } else {
+            pipe.rename(&building_key, &holders_key).ignore()
+                .expire(&holders_key, HOLDERS_KEY_TTL).ignore();
+        }
+        let _: () = pipe
+            .set_ex(format!("mint:{}:holders-snapshot", mint_address), Utc::now().timestamp(), HOLDERS_KEY_TTL as u64).ignore()
+            .query_async(&mut conn)
+            .await?;
+        println!("Replaced holder index for {} with {} holders", mint_address, members.len());
+        Ok(())
+    }
+
+    pub async fn get_snapshot_at(&self, mint_address: &str) -> RedisResult<Option<i64>> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        conn.get(format!("mint:{}:holders-snapshot", mint_address)).await
+    }
+
+    // shares are taken against `total_supply`, the dev wallet is usually the metadata update authority
+    pub async fn get_holder_stats(
+        &self,
+        mint_address: &str,
+        total_supply: Option<f64>,
+        dev_wallet: Option<String>,
+        top_n: isize,
+    ) -> RedisResult<HolderStats> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let holders_key = format!("mint:{}:holders", mint_address);
+        let mut pipe = redis::pipe();
+        pipe.zcount(&holders_key, "(0", "+inf")
+            .zrevrange_withscores(&holders_key, 0, top_n.max(10) - 1)
+            .get(format!("mint:{}:holders-snapshot", mint_address))
+            .zscore(&holders_key, dev_wallet.as_deref().unwrap_or_default());
+        let (holder_count, top, snapshot_at, dev_balance): HolderQuery = pipe.query_async(&mut conn).await?;
+
+        let share = |balance: f64| total_supply.filter(|supply| *supply > 0.0).map(|supply| balance / supply);
+        let top10_balance: f64 = top.iter().take(10).map(|(_, balance)| balance).sum();
+        let top_holders = top
+            .into_iter()
+            .take(top_n.max(0) as usize)
+            .map(|(owner, balance)| Holder { owner, balance, share: share(balance) })
+            .collect();
+        let dev_balance = dev_wallet.as_ref().map(|_| dev_balance.unwrap_or(0.0));
+
+        Ok(HolderStats {
+            mint_address: mint_address.to_string(),
+            holder_count,
+            top_holders,
+            top10_share: share(top10_balance),
+            dev_share: dev_balance.and_then(share),
+            dev_wallet,
+            dev_balance,
+            snapshot_at,
+        })
+    }
+}
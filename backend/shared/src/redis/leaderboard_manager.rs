@@ -0,0 +1,60 @@
+use redis::{AsyncCommands, Client, RedisError, RedisResult};
+
+use crate::{redis::metric_and_ohlcv_manager::TimeFrame, types::ranking::{LeaderboardEntry, LeaderboardKind}};
+
+fn leaderboard_key(timeframe: &TimeFrame, kind: LeaderboardKind) -> String {
+    format!("leaderboard:{}:{}", timeframe.label(), kind.label())
+}
+
+#[derive(Debug, Clone)]
+pub struct LeaderboardManager {
+    redis_client: Client,
+}
+
+impl LeaderboardManager {
+    pub fn new() -> Result<Self, RedisError> {
+        println!("Initializing redis client...for LEADERBOARDS");
+        let redis_client = Client::open(std::env::var("REDIS_URL").expect("unable to fetch redis url from env")).map_err(|e| {
+            println!("Couldn't initialize a redis client : {}", e);
+            e
+        })?;
+        Ok(Self {
+            redis_client,
+        })
+    }
+
+    // builds the board under a temp key and renames it over the live one, readers never see a half written board
+    pub async fn replace_leaderboard(&self, timeframe: &TimeFrame, kind: LeaderboardKind, scores: &[(String, f64)]) -> RedisResult<()> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let key = leaderboard_key(timeframe, kind);
+        if scores.is_empty() {
+            let _: () = conn.del(&key).await?;
+            return Ok(());
+        }
+        let building_key = format!("{}:building", key);
+        let members: Vec<(f64, &String)> = scores.iter().map(|(token_pair, score)| (*score, token_pair)).collect();
+        let mut pipe = redis::pipe();
+        pipe.atomic().del(&building_key).ignore();
+        for chunk in members.chunks(500) {
+            pipe.zadd_multiple(&building_key, chunk).ignore();
+        }
+        let _: () = pipe.rename(&building_key, &key).ignore().query_async(&mut conn).await?;
+        Ok(())
+    }
+
+    pub async fn get_leaderboard(&self, timeframe: &TimeFrame, kind: LeaderboardKind, offset: isize, limit: isize) -> RedisResult<Vec<LeaderboardEntry>> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let entries: Vec<(String, f64)> = conn
+            .zrevrange_withscores(leaderboard_key(timeframe, kind), offset, offset + limit - 1)
+            .await?;
+        Ok(entries
+            .into_iter()
+            .enumerate()
+            .map(|(index, (token_pair, score))| LeaderboardEntry {
+                rank: offset as usize + index + 1,
+                token_pair,
+                score,
+            })
+            .collect())
+    }
+}
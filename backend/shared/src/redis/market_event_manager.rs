@@ -0,0 +1,65 @@
+use redis::{Client, RedisError, RedisResult};
+
+use crate::{redis::pubsub_manager::PubSubManager, types::market_event::MarketEvent};
+
+const MARKET_EVENTS_STREAM: &str = "market_events_stream";
+const STREAM_MAX_LEN: u64 = 100_000;
+
+// every event goes to the `market_events` channel for live clients and to a capped stream for consumers that replay
+#[derive(Debug, Clone)]
+pub struct MarketEventManager {
+    redis_client: Client,
+    pubsub_manager: PubSubManager,
+}
+
+impl MarketEventManager {
+    pub fn new(pubsub_manager: PubSubManager) -> Result<Self, RedisError> {
+        println!("Initializing redis client...for MARKET EVENTS");
+        let redis_client = Client::open(std::env::var("REDIS_URL").expect("unable to fetch redis url from env")).map_err(|e| {
+            println!("Couldn't initialize a redis client : {}", e);
+            e
+        })?;
+        Ok(Self {
+            redis_client,
+            pubsub_manager,
+        })
+    }
+
+    /// Publishes an event unless the same kind already fired for the pair within `cooldown_secs`.
+    ///
+    /// The cooldown is a SET NX key so workers that detect the same move publish it once.
+    pub async fn emit(&self, event: &MarketEvent, cooldown_secs: u64) -> RedisResult<bool> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        if cooldown_secs > 0 {
+            let claimed: Option<String> = redis::cmd("SET")
+                .arg(format!("market-event:{}:{}", event.token_pair, event.event.label()))
+                .arg(event.detected_at)
+                .arg("NX")
+                .arg("EX")
+                .arg(cooldown_secs)
+                .query_async(&mut conn)
+                .await?;
+            if claimed.is_none() {
+                return Ok(false);
+            }
+        }
+
+        let event_json = serde_json::to_string(event).inspect_err(|_| {
+            println!("Error serializing the market event");
+        })?;
+        let _: String = redis::cmd("XADD")
+            .arg(MARKET_EVENTS_STREAM)
+            .arg("MAXLEN")
+            .arg("~")
+            .arg(STREAM_MAX_LEN)
+            .arg("*")
+            .arg("kind")
+            .arg(event.event.label())
+            .arg("data")
+            .arg(&event_json)
+            .query_async(&mut conn)
+            .await?;
+        self.pubsub_manager.publish_market_event(event).await?;
+        Ok(true)
+    }
+}
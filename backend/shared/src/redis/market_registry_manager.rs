@@ -0,0 +1,160 @@
+use chrono::Utc;
+use redis::{AsyncCommands, Client, RedisError, RedisResult};
+use serde::{Deserialize, Serialize};
+
+const ACTIVE_MARKETS_KEY: &str = "markets:active";
+const ARCHIVED_MARKETS_KEY: &str = "markets:archived";
+const MARKET_MINTS_KEY: &str = "markets:mint";
+const FIRST_SEEN_KEY: &str = "markets:first-seen";
+
+// last known state of a market whose live redis keys were dropped by the janitor
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ArchivedMarket {
+    pub token_pair: String,
+    pub token_mint: Option<String>,
+    pub last_price: Option<f64>,
+    pub market_cap: Option<f64>,
+    pub fdv: Option<f64>,
+    pub last_trade_at: i64,
+    pub archived_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ActiveMarket {
+    pub token_pair: String,
+    pub last_trade_at: i64,
+}
+
+// registry of markets scored by their last trade time, replaces KEYS scans over price keys
+#[derive(Debug, Clone)]
+pub struct MarketRegistryManager {
+    redis_client: Client,
+}
+
+impl MarketRegistryManager {
+    pub fn new() -> Result<Self, RedisError> {
+        println!("Initializing redis client...for MARKET REGISTRY");
+        let redis_client = Client::open(std::env::var("REDIS_URL").expect("unable to fetch redis url from env")).map_err(|e| {
+            println!("Couldn't initialize a redis client : {}", e);
+            e
+        })?;
+        Ok(Self {
+            redis_client,
+        })
+    }
+
+    // GT keeps the newest trade time when an older trade is processed late, a trade also revives an archived market
+    pub async fn touch_market(&self, token_pair: &str, token_mint: &str, last_trade_at: i64) -> RedisResult<()> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let mut pipe = redis::pipe();
+        pipe.cmd("ZADD").arg(ACTIVE_MARKETS_KEY).arg("GT").arg(last_trade_at).arg(token_pair).ignore()
+            .cmd("ZADD").arg(FIRST_SEEN_KEY).arg("NX").arg(last_trade_at).arg(token_pair).ignore()
+            .hdel(ARCHIVED_MARKETS_KEY, token_pair).ignore();
+        if !token_mint.is_empty() {
+            pipe.hset(MARKET_MINTS_KEY, token_pair, token_mint).ignore();
+        }
+        let _: () = pipe.query_async(&mut conn).await?;
+        Ok(())
+    }
+
+    // most recently traded first, `limit` of None returns every market active since the cutoff
+    pub async fn get_active_markets(&self, active_since: i64, limit: Option<isize>) -> RedisResult<Vec<ActiveMarket>> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let markets: Vec<(String, i64)> = match limit {
+            Some(limit) => conn.zrevrangebyscore_limit_withscores(ACTIVE_MARKETS_KEY, "+inf", active_since, 0, limit).await?,
+            None => conn.zrevrangebyscore_withscores(ACTIVE_MARKETS_KEY, "+inf", active_since).await?,
+        };
+        Ok(markets
+            .into_iter()
+            .map(|(token_pair, last_trade_at)| ActiveMarket { token_pair, last_trade_at })
+            .collect())
+    }
+
+    pub async fn get_active_pairs(&self, active_since: i64) -> RedisResult<Vec<String>> {
+        let markets = self.get_active_markets(active_since, None).await?;
+        Ok(markets.into_iter().map(|market| market.token_pair).collect())
+    }
+
+    // markets first seen after `listed_since`, newest listing first, scored by their first trade
+    pub async fn get_newest_markets(&self, listed_since: i64) -> RedisResult<Vec<(String, i64)>> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        conn.zrevrangebyscore_withscores(FIRST_SEEN_KEY, "+inf", listed_since).await
+    }
+
+    pub async fn get_inactive_markets(&self, inactive_before: i64) -> RedisResult<Vec<ActiveMarket>> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let markets: Vec<(String, i64)> = conn.zrangebyscore_withscores(ACTIVE_MARKETS_KEY, "-inf", inactive_before).await?;
+        Ok(markets
+            .into_iter()
+            .map(|(token_pair, last_trade_at)| ActiveMarket { token_pair, last_trade_at })
+            .collect())
+    }
+
+    pub async fn get_archived_market(&self, token_pair: &str) -> RedisResult<Option<ArchivedMarket>> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let archived: Option<String> = conn.hget(ARCHIVED_MARKETS_KEY, token_pair).await?;
+        Ok(archived.and_then(|market| serde_json::from_str(&market).ok()))
+    }
+
+    // snapshots the last price and market data, then drops every live key of the market
+    pub async fn archive_market(&self, market: &ActiveMarket) -> Result<(), anyhow::Error> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let token_pair = &market.token_pair;
+        let (last_price, market_cap, fdv, token_mint): (Option<f64>, Option<f64>, Option<f64>, Option<String>) = redis::pipe()
+            .get(format!("token:{}:current-price", token_pair))
+            .get(format!("token:{}:market-cap", token_pair))
+            .get(format!("token:{}:fdv", token_pair))
+            .hget(MARKET_MINTS_KEY, token_pair)
+            .query_async(&mut conn)
+            .await?;
+        let archived = ArchivedMarket {
+            token_pair: token_pair.clone(),
+            token_mint,
+            last_price,
+            market_cap,
+            fdv,
+            last_trade_at: market.last_trade_at,
+            archived_at: Utc::now().timestamp(),
+        };
+
+        // SCAN instead of KEYS so the janitor never blocks the server
+        let mut keys: Vec<String> = Vec::new();
+        let patterns = [
+            format!("token:{}:*", token_pair),
+            format!("candles:{}:*", token_pair),
+            format!("candle_idx:{}:*", token_pair),
+            format!("candle_seen:{}:*", token_pair),
+        ];
+        for pattern in patterns {
+            let mut cursor: u64 = 0;
+            loop {
+                let (next_cursor, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+                    .arg(cursor)
+                    .arg("MATCH")
+                    .arg(&pattern)
+                    .arg("COUNT")
+                    .arg(500)
+                    .query_async(&mut conn)
+                    .await?;
+                keys.extend(batch);
+                if next_cursor == 0 {
+                    break;
+                }
+                cursor = next_cursor;
+            }
+        }
+
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .hset(ARCHIVED_MARKETS_KEY, token_pair, serde_json::to_string(&archived)?).ignore()
+            .zrem(ACTIVE_MARKETS_KEY, token_pair).ignore()
+            .zrem(FIRST_SEEN_KEY, token_pair).ignore()
+            .hdel(MARKET_MINTS_KEY, token_pair).ignore();
+        for chunk in keys.chunks(500) {
+            pipe.del(chunk).ignore();
+        }
+        let _: () = pipe.query_async(&mut conn).await?;
+        println!("Archived inactive market {} and removed {} keys", token_pair, keys.len());
+        Ok(())
+    }
+}
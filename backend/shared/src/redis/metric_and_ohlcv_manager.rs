@@ -1,9 +1,9 @@
 use std::collections::HashMap;
 
 use chrono::{Utc};
-use redis::{AsyncCommands, Client, RedisError, RedisResult};
+use redis::{AsyncCommands, Client, RedisError, RedisResult, Script};
 use serde::{Deserialize, Serialize};
-use crate::types::{ohlcv::OHLCVcandle, worker::{StructeredTransaction, Type}};
+use crate::types::{ohlcv::{fill_gaps, CandleTimeFrame, OHLCVcandle}, worker::{StructeredTransaction, TradeOrder, Type}};
 
 #[derive(Debug,Serialize,Deserialize, Clone)]
 pub struct PeriodStats{
@@ -18,12 +18,405 @@ pub struct PeriodStats{
     pub sellers : usize
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub enum TimeFrame {
+    OneMin,
     FiveMins,
+    FifteenMins,
     OneHour,
+    FourHours,
     SixHours,
-    TwentyFourHours
+    TwentyFourHours,
+    SevenDays
+}
+
+impl TimeFrame {
+    pub fn all() -> Vec<TimeFrame> {
+        vec![
+            TimeFrame::OneMin,
+            TimeFrame::FiveMins,
+            TimeFrame::FifteenMins,
+            TimeFrame::OneHour,
+            TimeFrame::FourHours,
+            TimeFrame::SixHours,
+            TimeFrame::TwentyFourHours,
+            TimeFrame::SevenDays
+        ]
+    }
+
+    pub fn to_seconds(&self) -> i64 {
+        match self {
+            TimeFrame::OneMin => 60,
+            TimeFrame::FiveMins => 5 * 60,
+            TimeFrame::FifteenMins => 15 * 60,
+            TimeFrame::OneHour => 60 * 60,
+            TimeFrame::FourHours => 4 * 60 * 60,
+            TimeFrame::SixHours => 6 * 60 * 60,
+            TimeFrame::TwentyFourHours => 24 * 60 * 60,
+            TimeFrame::SevenDays => 7 * 24 * 60 * 60,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TimeFrame::OneMin => "1m",
+            TimeFrame::FiveMins => "5m",
+            TimeFrame::FifteenMins => "15m",
+            TimeFrame::OneHour => "1h",
+            TimeFrame::FourHours => "4h",
+            TimeFrame::SixHours => "6h",
+            TimeFrame::TwentyFourHours => "24h",
+            TimeFrame::SevenDays => "7d",
+        }
+    }
+
+    pub fn from_label(label : &str) -> Option<TimeFrame> {
+        TimeFrame::all().into_iter().find(|timeframe| timeframe.label() == label)
+    }
+
+    // windows up to a day are summed from minute buckets (whole hours from hour buckets, see `window_buckets`),
+    // longer ones from hour buckets only
+    pub fn bucket_seconds(&self) -> i64 {
+        if self.to_seconds() <= 86400 { 60 } else { 3600 }
+    }
+
+    // how often the scheduler republishes the window when not configured explicitly
+    pub fn default_refresh_seconds(&self) -> u64 {
+        match self {
+            TimeFrame::OneMin => 10,
+            TimeFrame::FiveMins => 30,
+            TimeFrame::FifteenMins | TimeFrame::OneHour => 60,
+            TimeFrame::FourHours | TimeFrame::SixHours => 120,
+            TimeFrame::TwentyFourHours => 300,
+            TimeFrame::SevenDays => 900,
+        }
+    }
+}
+
+const MINUTE_BUCKET_RETENTION_SECS: i64 = 86400 + 3600; // longest minute-bucket window plus slack
+// two of the longest window, the previous window is compared against for growth
+const HOUR_BUCKET_RETENTION_SECS: i64 = 2 * 7 * 86400 + 3600;
+
+const PRICE_HISTORY_RETENTION_SECS: i64 = 86400 + 3600;
+
+fn parse_price_entry(entry : &str) -> Option<f64> {
+    entry.split_once(':').and_then(|(_, price)| price.parse().ok())
+}
+
+const CANDLES_PENDING_KEY : &str = "candles:pending";
+// how long merged trade ids are remembered, a trade published again within it is not counted twice.
+// redelivered stream messages never need it since merging and acknowledging happen in one script run
+const CANDLE_SEEN_RETENTION_SECS : i64 = 3600;
+const CANDLES_PROCESSING_KEY : &str = "candles:processing";
+// a flusher that died mid-write leaves its members in processing, they are handed out again after this long
+const CANDLE_FLUSH_LEASE_SECS : i64 = 300;
+
+lazy_static::lazy_static! {
+    // KEYS : trade stream, pending set, then hash and index of every delta, then the seen set of every trade id.
+    // ARGV : consumer group, delta count, trade id count, seen ttl, the trade ids, the 26 fields of every delta
+    // (see `delta_args`), then the stream messages to acknowledge.
+    // nothing is written when a trade id was seen before, the script returns those ids and the caller drops them
+    // and retries. otherwise every delta is merged into its bucket : high, low and volumes always, open and close
+    // only when the delta holds the first or last trade on chain. a bucket that already closed bumps its revision.
+    // the messages are acknowledged in the same run, so a trade is never counted without its message acked
+    static ref CANDLE_MERGE_SCRIPT : Script = Script::new(r"
+        local delta_count = tonumber(ARGV[2])
+        local trade_count = tonumber(ARGV[3])
+        local seen_base = 2 + 2 * delta_count
+        local duplicates = {}
+        for i = 1, trade_count do
+            if redis.call('SISMEMBER', KEYS[seen_base + i], ARGV[4 + i]) == 1 then
+                table.insert(duplicates, ARGV[4 + i])
+            end
+        end
+        if #duplicates > 0 then
+            return {duplicates, {}}
+        end
+        for i = 1, trade_count do
+            redis.call('SADD', KEYS[seen_base + i], ARGV[4 + i])
+            redis.call('EXPIRE', KEYS[seen_base + i], ARGV[4])
+        end
+
+        local function known(o)
+            return o[1] > 0 or o[2] > 0
+        end
+        local function before(a, b)
+            if not known(a) or not known(b) then
+                return false
+            end
+            if a[2] > 0 and b[2] > 0 then
+                return a[2] < b[2] or (a[2] == b[2] and a[3] < b[3])
+            end
+            return a[1] < b[1]
+        end
+        local function number(key, field)
+            return tonumber(redis.call('HGET', key, field) or '0') or 0
+        end
+
+        local candles = {}
+        for d = 0, delta_count - 1 do
+            local key = KEYS[3 + 2 * d]
+            local index = KEYS[4 + 2 * d]
+            local base = 4 + trade_count + d * 26
+            local function arg(n)
+                return ARGV[base + n]
+            end
+            local first = {tonumber(arg(20)), tonumber(arg(21)), tonumber(arg(22))}
+            local last = {tonumber(arg(23)), tonumber(arg(24)), tonumber(arg(25))}
+            local function stored_order(prefix)
+                return {number(key, prefix .. '_time'), number(key, prefix .. '_slot'), number(key, prefix .. '_index')}
+            end
+            local function set_order(prefix, o)
+                redis.call('HSET', key, prefix .. '_time', o[1], prefix .. '_slot', o[2], prefix .. '_index', o[3])
+            end
+            -- a quote price of 0 is unknown, it never replaces a known one
+            local function set_quote(field, n)
+                if tonumber(arg(n)) > 0 then
+                    redis.call('HSET', key, field, arg(n))
+                end
+            end
+
+            if redis.call('HSETNX', key, 'open', arg(6)) == 1 then
+                redis.call('HSET', key, 'high', arg(7), 'low', arg(8), 'close', arg(9))
+                set_order('first_trade', first)
+                set_order('last_trade', last)
+                set_quote('quote_open', 14)
+                set_quote('quote_high', 15)
+                set_quote('quote_low', 16)
+                set_quote('quote_close', 17)
+            else
+                if tonumber(arg(7)) > number(key, 'high') then
+                    redis.call('HSET', key, 'high', arg(7))
+                end
+                if tonumber(arg(8)) < number(key, 'low') then
+                    redis.call('HSET', key, 'low', arg(8))
+                end
+                if before(first, stored_order('first_trade')) then
+                    redis.call('HSET', key, 'open', arg(6))
+                    set_quote('quote_open', 14)
+                    set_order('first_trade', first)
+                end
+                if not before(last, stored_order('last_trade')) then
+                    redis.call('HSET', key, 'close', arg(9))
+                    set_quote('quote_close', 17)
+                    set_order('last_trade', last)
+                end
+                if number(key, 'quote_open') <= 0 then
+                    set_quote('quote_open', 14)
+                end
+                if number(key, 'quote_close') <= 0 then
+                    set_quote('quote_close', 17)
+                end
+                if tonumber(arg(15)) > number(key, 'quote_high') then
+                    set_quote('quote_high', 15)
+                end
+                local quote_low = number(key, 'quote_low')
+                if quote_low <= 0 or tonumber(arg(16)) < quote_low then
+                    set_quote('quote_low', 16)
+                end
+            end
+            redis.call('HINCRBYFLOAT', key, 'volume', arg(10))
+            redis.call('HINCRBYFLOAT', key, 'buy_volume', arg(11))
+            redis.call('HINCRBYFLOAT', key, 'sell_volume', arg(12))
+            redis.call('HINCRBY', key, 'trade_count', arg(13))
+            redis.call('HINCRBYFLOAT', key, 'quote_volume', arg(18))
+            redis.call('HINCRBYFLOAT', key, 'usd_volume', arg(19))
+            if arg(26) == '1' then
+                redis.call('HINCRBY', key, 'revision', 1)
+            end
+            redis.call('EXPIRE', key, arg(2))
+            redis.call('ZADD', index, arg(1), arg(1))
+            redis.call('ZREMRANGEBYSCORE', index, '-inf', arg(3))
+            redis.call('EXPIRE', index, arg(2))
+            if arg(4) ~= '' then
+                redis.call('ZADD', KEYS[2], arg(5), arg(4))
+            end
+            table.insert(candles, redis.call('HGETALL', key))
+        end
+
+        local acks = 4 + trade_count + delta_count * 26
+        for i = acks + 1, #ARGV, 500 do
+            redis.call('XACK', KEYS[1], ARGV[1], unpack(ARGV, i, math.min(i + 499, #ARGV)))
+        end
+        return {duplicates, candles}
+    ");
+
+    // KEYS : pending set, processing set. ARGV : closed before, limit, now, lease deadline.
+    // expired leases go back to pending first, then the closed members move to processing scored by their lease
+    static ref TAKE_CLOSED_CANDLES_SCRIPT : Script = Script::new(r"
+        local expired = redis.call('ZRANGEBYSCORE', KEYS[2], '-inf', ARGV[3])
+        for _, member in ipairs(expired) do
+            redis.call('ZADD', KEYS[1], 'NX', ARGV[3], member)
+            redis.call('ZREM', KEYS[2], member)
+        end
+        local members = redis.call('ZRANGEBYSCORE', KEYS[1], '-inf', ARGV[1], 'LIMIT', 0, ARGV[2])
+        for _, member in ipairs(members) do
+            redis.call('ZREM', KEYS[1], member)
+            redis.call('ZADD', KEYS[2], ARGV[4], member)
+        end
+        return members
+    ");
+
+    // KEYS : pending set, processing set. ARGV : (member, close time) pairs.
+    // a member a late trade already put back in pending keeps that entry
+    static ref REQUEUE_CLOSED_CANDLES_SCRIPT : Script = Script::new(r"
+        for i = 1, #ARGV, 2 do
+            redis.call('ZADD', KEYS[1], 'NX', ARGV[i + 1], ARGV[i])
+            redis.call('ZREM', KEYS[2], ARGV[i])
+        end
+        return #ARGV / 2
+    ");
+}
+
+// one hash per bucket holds the candle, a zset of bucket timestamps indexes them for range reads
+fn candle_key(token_pair : &str, timeframe : &str, bucket : i64) -> String {
+    format!("candles:{}:{}:{}", token_pair, timeframe, bucket)
+}
+
+// `candles:{pair}:{tf}` held json members before buckets moved to hashes, the index gets its own prefix
+fn candle_index_key(token_pair : &str, timeframe : &str) -> String {
+    format!("candle_idx:{}:{}", token_pair, timeframe)
+}
+
+// trade ids merged into the pair's candles, one set per minute of block time
+fn candle_seen_key(token_pair : &str, timestamp : i64) -> String {
+    format!("candle_seen:{}:{}", token_pair, bucket_start(timestamp, 60))
+}
+
+// the fields CANDLE_MERGE_SCRIPT reads for one delta, in order
+fn delta_args(candle : &OHLCVcandle, timeframe : CandleTimeFrame, now : i64) -> Vec<String> {
+    let retention = timeframe.redis_retention_secs();
+    let close_time = candle.timestamp + timeframe.to_seconds();
+    let pending = if timeframe.is_persisted() { pending_member(&candle.token_pair, &candle.timeframe, candle.timestamp) } else { String::new() };
+    let order = |order : &TradeOrder| [order.block_time.to_string(), order.slot.to_string(), order.index.to_string()];
+    let mut args = vec![
+        candle.timestamp.to_string(),
+        retention.to_string(),
+        (now - retention).to_string(),
+        pending,
+        close_time.to_string(),
+        candle.open.to_string(),
+        candle.high.to_string(),
+        candle.low.to_string(),
+        candle.close.to_string(),
+        candle.volume.to_string(),
+        candle.buy_volume.to_string(),
+        candle.sell_volume.to_string(),
+        candle.trade_count.to_string(),
+        candle.quote_open.to_string(),
+        candle.quote_high.to_string(),
+        candle.quote_low.to_string(),
+        candle.quote_close.to_string(),
+        candle.quote_volume.to_string(),
+        candle.usd_volume.to_string(),
+    ];
+    args.extend(order(&candle.first_trade));
+    args.extend(order(&candle.last_trade));
+    args.push(if close_time <= now { "1" } else { "0" }.to_string());
+    args
+}
+
+// pending members are `{timeframe}:{bucket}:{pair}`, the pair goes last since symbols may contain ':'
+fn pending_member(token_pair : &str, timeframe : &str, bucket : i64) -> String {
+    format!("{}:{}:{}", timeframe, bucket, token_pair)
+}
+
+fn parse_pending_member(member : &str) -> Option<(CandleTimeFrame, i64, String)> {
+    let mut parts = member.splitn(3, ':');
+    let timeframe = CandleTimeFrame::from_label(parts.next()?)?;
+    let bucket = parts.next()?.parse().ok()?;
+    Some((timeframe, bucket, parts.next()?.to_string()))
+}
+
+fn order_from_fields(fields : &HashMap<String, String>, prefix : &str) -> TradeOrder {
+    let field = |name : &str| fields.get(&format!("{}_{}", prefix, name)).and_then(|value| value.parse::<i64>().ok()).unwrap_or(0);
+    TradeOrder{
+        block_time : field("time"),
+        slot : field("slot").max(0) as u64,
+        index : field("index").max(0) as u64
+    }
+}
+
+fn candle_from_fields(token_pair : &str, timeframe : &str, bucket : i64, fields : &HashMap<String, String>) -> Option<OHLCVcandle> {
+    let field = |name : &str| fields.get(name).and_then(|value| value.parse::<f64>().ok());
+    Some(OHLCVcandle{
+        token_pair : token_pair.to_string(),
+        timeframe : timeframe.to_string(),
+        timestamp : bucket,
+        open : field("open")?,
+        high : field("high")?,
+        low : field("low")?,
+        close : field("close")?,
+        volume : field("volume").unwrap_or(0.0),
+        buy_volume : field("buy_volume").unwrap_or(0.0),
+        sell_volume : field("sell_volume").unwrap_or(0.0),
+        trade_count : field("trade_count").unwrap_or(0.0) as u32,
+        quote_open : field("quote_open").unwrap_or(0.0),
+        quote_high : field("quote_high").unwrap_or(0.0),
+        quote_low : field("quote_low").unwrap_or(0.0),
+        quote_close : field("quote_close").unwrap_or(0.0),
+        quote_volume : field("quote_volume").unwrap_or(0.0),
+        usd_volume : field("usd_volume").unwrap_or(0.0),
+        first_trade : order_from_fields(fields, "first_trade"),
+        last_trade : order_from_fields(fields, "last_trade"),
+        revision : field("revision").unwrap_or(0.0) as u32
+    })
+}
+
+pub enum CandleMerge {
+    Written(Vec<OHLCVcandle>), // the merged buckets, in the order of the deltas
+    Duplicates(Vec<String>)    // trade ids counted before, nothing was written
+}
+
+fn bucket_start(timestamp : i64, bucket_seconds : i64) -> i64 {
+    (timestamp / bucket_seconds) * bucket_seconds
+}
+
+/// The `(bucket_seconds, bucket)` pairs a window ending at `window_end` is summed from.
+///
+/// Minute windows take hour buckets for every whole hour they cover and minute buckets only for the
+/// partial hours at either edge, so a 24h window reads about 140 buckets instead of 1440. An edge older
+/// than `oldest_minute` has lost its minute buckets and is read from its whole hour bucket instead.
+fn window_buckets(window_end : i64, window_seconds : i64, bucket_seconds : i64, oldest_minute : i64) -> Vec<(i64, i64)> {
+    let last = bucket_start(window_end, bucket_seconds);
+    let first = last - window_seconds + bucket_seconds;
+    if bucket_seconds == 3600 {
+        return (0..window_seconds / 3600).map(|i| (3600, first + i * 3600)).collect();
+    }
+    let mut buckets = Vec::new();
+    let mut bucket = first;
+    while bucket <= last {
+        if bucket % 3600 == 0 && bucket + 3600 - 60 <= last {
+            buckets.push((3600, bucket));
+            bucket += 3600;
+        } else if bucket < oldest_minute {
+            buckets.push((3600, bucket_start(bucket, 3600)));
+            bucket = bucket_start(bucket, 3600) + 3600;
+        } else {
+            buckets.push((60, bucket));
+            bucket += 60;
+        }
+    }
+    buckets
+}
+
+struct BucketKeys {
+    stats : String,
+    buyers : String,
+    sellers : String,
+    makers : String
+}
+
+// minute buckets keep their original key names, hour buckets get a -1h suffix.
+// unique wallets are HyperLogLogs so any window is a PFCOUNT over its buckets
+fn bucket_keys(token_pair : &str, bucket_seconds : i64, bucket : i64) -> BucketKeys {
+    let suffix = if bucket_seconds == 60 { "" } else { "-1h" };
+    BucketKeys {
+        stats : format!("token:{}:stats{}:{}", token_pair, suffix, bucket),
+        buyers : format!("token:{}:buyers-hll{}:{}", token_pair, suffix, bucket),
+        sellers : format!("token:{}:sellers-hll{}:{}", token_pair, suffix, bucket),
+        makers : format!("token:{}:makers-hll{}:{}", token_pair, suffix, bucket),
+    }
 }
 
 #[derive(Debug)]
@@ -39,25 +432,30 @@ impl MetricOHLCVManager {
             e
         })?;
         Ok(Self {
-            redis_client: redis_client,
+            redis_client,
         })
     }
 
-    pub async fn update_current_price(&self, token_pair: String, price_usd : f64) -> RedisResult<()>{
+    pub async fn update_current_price(&self, token_pair: String, price_usd : f64, timestamp : i64) -> RedisResult<()>{
         let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
         let price_key = format!("token:{}:current-price" , token_pair);
-        let _: () = conn.set(price_key, price_usd).await?;
+        let history_key = format!("token:{}:price-history", token_pair);
+        // members carry their own timestamp so equal prices at different times stay distinct
+        let history_price_entry = format!("{}:{}", timestamp, price_usd);
+        let cutoff = Utc::now().timestamp() - PRICE_HISTORY_RETENTION_SECS;
 
-        let history_key = format!("token:{}:history-price", token_pair);
-        let history_price_entry = format!("{}:{}", Utc::now().timestamp(), price_usd);
-
-        let _: () = conn.lpush(&history_key, history_price_entry).await?;
-        let _: () = conn.ltrim(history_key, 0, 3000).await?;
+        let _: () = redis::pipe()
+            .set(price_key, price_usd).ignore()
+            .zadd(&history_key, history_price_entry, timestamp).ignore()
+            .zrembyscore(&history_key, "-inf", cutoff).ignore()
+            .expire(&history_key, PRICE_HISTORY_RETENTION_SECS).ignore()
+            .query_async(&mut conn)
+            .await?;
         println!("updated current price and pushed to historical price for : {}", token_pair);
         Ok(())
     }
 
-    pub async fn update_market_data(&self, token_pair: String, market_cap : i32, fdv : i32) -> RedisResult<()>{
+    pub async fn update_market_data(&self, token_pair: String, market_cap : f64, fdv : f64) -> RedisResult<()>{
         let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
         let mkc_key = format!("token:{}:market-cap" , token_pair);
         let fdv_key = format!("token:{}:fdv", token_pair);
@@ -68,73 +466,84 @@ impl MetricOHLCVManager {
         Ok(())
     }
 
+    // every trade lands in the minute and hour bucket of its block time, windows are summed from buckets on read
     pub async fn update_period_stats(&self, txn : StructeredTransaction) -> RedisResult<()>{
         let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
-        let stats_key = format!("token:{}:stats", txn.token_pair);
         let is_buy = matches!(txn.purchase_type, Type::Buy);
-        let buyers_key = format!("token:{}:buyers", txn.token_pair);
-        let sellers_key = format!("token:{}:sellers", txn.token_pair);
+        let (count_field, volume_field) = if is_buy { ("buys", "buy_volume") } else { ("sells", "sell_volume") };
 
-        // calculate volume ( buy vol + sell vol), txns (buys + sells) and makers (buyers + sellers) from the following.
-        if is_buy{
-            let _: () = conn.hincr(&stats_key, "buys", 1).await?;
-            if let Some(usd_value) = txn.usd_value {
-                let _: () = conn.hincr(&stats_key, "buy_volume", usd_value).await?;
-            }            
-            let _: () = conn.sadd(&buyers_key, txn.owner).await?;
+        let mut pipe = redis::pipe();
+        for (bucket_seconds, retention) in [(60, MINUTE_BUCKET_RETENTION_SECS), (3600, HOUR_BUCKET_RETENTION_SECS)] {
+            let bucket = bucket_start(txn.date.timestamp(), bucket_seconds);
+            let keys = bucket_keys(&txn.token_pair, bucket_seconds, bucket);
+            let side_key = if is_buy { &keys.buyers } else { &keys.sellers };
 
-        }
-        else {
-            let _: () = conn.hincr(&stats_key, "sells", 1).await?;
+            pipe.hincr(&keys.stats, count_field, 1).ignore();
             if let Some(usd_value) = txn.usd_value {
-                let _: () = conn.hincr(&stats_key, "sell_volume", usd_value).await?;
-            }            
-            let _: () = conn.sadd(&sellers_key, txn.owner).await?;
-        }
-        let _: () = conn.expire(stats_key, 86400).await?;
-        let _: () = conn.expire(buyers_key, 86400).await?;
-        let _: () = conn.expire(sellers_key, 86400).await?;
-        println!("Updated period stats for token pair : {}", txn.token_pair);
+                pipe.hincr(&keys.stats, volume_field, usd_value).ignore();
+            }
+            pipe.pfadd(side_key, &txn.owner).ignore()
+                .pfadd(&keys.makers, &txn.owner).ignore()
+                .expire(&keys.stats, retention).ignore()
+                .expire(side_key, retention).ignore()
+                .expire(&keys.makers, retention).ignore();
+            if bucket_seconds == 3600 {
+                // hour buckets double as a downsampled price history for windows past the raw history
+                pipe.hset_nx(&keys.stats, "open_price", txn.token_price).ignore()
+                    .hset(&keys.stats, "close_price", txn.token_price).ignore();
+            }
+        }
+        let _: () = pipe.query_async(&mut conn).await?;
+        println!("Updated period stats buckets for token pair : {}", txn.token_pair);
         Ok(())
     }
 
+    // price at the start of the window, or the first recorded price when the pair is younger than the window
     pub async fn get_historical_price(&self, token_pair: &str, timeframe : TimeFrame) -> RedisResult<Option<f64>>{
+        let search_timestamp = Utc::now().timestamp() - timeframe.to_seconds();
+        if timeframe.bucket_seconds() == 3600 {
+            return self.get_hourly_price_at(token_pair, search_timestamp).await;
+        }
+        if let Some(price) = self.get_price_at(token_pair, search_timestamp).await? {
+            return Ok(Some(price));
+        }
         let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
-        let history_key = format!("token:{}:history-price", token_pair);
-        let timerange = match timeframe {
-            TimeFrame::FiveMins => {   
-                5 * 60
-            },
-            TimeFrame::OneHour => {
-                60 * 60
-            },
-            TimeFrame::SixHours => {
-                6 * 60 * 60
-            },
-            TimeFrame::TwentyFourHours => {
-                24 * 60 * 60
-            }
-        };
-        let price_history : Vec<String> = conn.lrange(history_key, 0, -1).await?;
-        let search_timeframe = Utc::now().timestamp() - timerange; // seconds 5 mins ago
-        let mut timeframe_price : Option<f64> = None;
-        let mut closest_diff = i64::MAX; // initially the value is max.
-
-        for entry in price_history{
-            let parts : Vec<&str> = entry.split(":").collect();
-            let timestamp = parts[0].parse().unwrap_or(0); // the time in secs present in the price_history list
-            let price = parts[1].parse().unwrap_or(0.0);
-
-            if timestamp <= search_timeframe{
-                let diff = search_timeframe - timestamp;
-                if diff < closest_diff{
-                    closest_diff = diff; // as timestamp gets closer to 5 mins ago the diff reduces.
-                    timeframe_price = Some(price);
-                }
-            }
-            break;
-        };
-        Ok(timeframe_price)
+        let history_key = format!("token:{}:price-history", token_pair);
+        let oldest : Vec<String> = conn.zrange(history_key, 0, 0).await?;
+        Ok(oldest.first().and_then(|entry| parse_price_entry(entry)))
+    }
+
+    // close of the last hour bucket before `timestamp`, falling back to the open of the first bucket after it
+    async fn get_hourly_price_at(&self, token_pair: &str, timestamp : i64) -> RedisResult<Option<f64>>{
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let target_bucket = bucket_start(timestamp, 3600);
+        // one bucket before the target is enough for the close, the retention reaches far past it
+        let oldest_bucket = target_bucket - 3600;
+        let current_bucket = bucket_start(Utc::now().timestamp(), 3600);
+
+        let mut pipe = redis::pipe();
+        let buckets : Vec<i64> = (0..).map(|i| oldest_bucket + i * 3600).take_while(|bucket| *bucket <= current_bucket).collect();
+        for bucket in &buckets {
+            pipe.cmd("HMGET").arg(bucket_keys(token_pair, 3600, *bucket).stats).arg("open_price").arg("close_price");
+        }
+        let prices : Vec<(Option<f64>, Option<f64>)> = pipe.query_async(&mut conn).await?;
+
+        let before = buckets.iter().zip(&prices)
+            .filter(|(bucket, _)| **bucket < target_bucket)
+            .filter_map(|(_, (_, close))| *close)
+            .next_back();
+        let after = buckets.iter().zip(&prices)
+            .filter(|(bucket, _)| **bucket >= target_bucket)
+            .find_map(|(_, (open, _))| *open);
+        Ok(before.or(after))
+    }
+
+    // latest price recorded at or before `timestamp`, a single ZREVRANGEBYSCORE so O(log n)
+    pub async fn get_price_at(&self, token_pair: &str, timestamp : i64) -> RedisResult<Option<f64>>{
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let history_key = format!("token:{}:price-history", token_pair);
+        let entries : Vec<String> = conn.zrevrangebyscore_limit(history_key, timestamp, "-inf", 0, 1).await?;
+        Ok(entries.first().and_then(|entry| parse_price_entry(entry)))
     }
 
     pub async fn get_current_price(&self, token_pair: &str) -> RedisResult<f64>{
@@ -144,27 +553,58 @@ impl MetricOHLCVManager {
         Ok(current_price)
     }
 
-    pub async fn get_metrics(&self, token_pair: &str) -> RedisResult<PeriodStats>{
-        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
-        let stats_key = format!("token:{}:stats", token_pair);
-        let token_stats : HashMap<String, String> = conn.hgetall(&stats_key).await?;
-        
-        let buys = token_stats.get("buys").unwrap_or(&"0".to_string()).parse().unwrap_or(0);
-        let sells = token_stats.get("sells").unwrap_or(&"0".to_string()).parse().unwrap_or(0);
-        let buy_volume = token_stats.get("buy vol").unwrap_or(&"0.0".to_string()).parse().unwrap_or(0.0);
-        let sell_volume = token_stats.get("sell vol").unwrap_or(&"0.0".to_string()).parse().unwrap_or(0.0);
-        let txns = buys + sells;
-        let volume = buy_volume + sell_volume;
-        let buyers_key = format!("token:{}:buyers", token_pair);
-        let sellers_key = format!("token:{}:sellers", token_pair);
-        let buyers_list : Vec<String> = conn.smembers(buyers_key).await?;
-        let sellers_list : Vec<String> = conn.smembers(sellers_key).await?;
-        let buyers = buyers_list.len();
-        let sellers = sellers_list.len();
-        let makers = buyers + sellers;
+    pub async fn get_metrics(&self, token_pair: &str, timeframe : &TimeFrame) -> RedisResult<PeriodStats>{
+        self.get_window_metrics(token_pair, timeframe, Utc::now().timestamp()).await
+    }
+
+    // the window of the same length right before the current one. its older edge is past the minute bucket
+    // retention for 24h, so that edge is read from whole hour buckets
+    pub async fn get_previous_metrics(&self, token_pair: &str, timeframe : &TimeFrame) -> RedisResult<PeriodStats>{
+        self.get_window_metrics(token_pair, timeframe, Utc::now().timestamp() - timeframe.to_seconds()).await
+    }
+
+    async fn get_window_metrics(&self, token_pair: &str, timeframe : &TimeFrame, window_end : i64) -> RedisResult<PeriodStats>{
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let oldest_minute = Utc::now().timestamp() - MINUTE_BUCKET_RETENTION_SECS;
+        let window_keys : Vec<BucketKeys> = window_buckets(window_end, timeframe.to_seconds(), timeframe.bucket_seconds(), oldest_minute)
+            .into_iter()
+            .map(|(bucket_seconds, bucket)| bucket_keys(token_pair, bucket_seconds, bucket))
+            .collect();
+        let buyer_keys : Vec<&String> = window_keys.iter().map(|keys| &keys.buyers).collect();
+        let seller_keys : Vec<&String> = window_keys.iter().map(|keys| &keys.sellers).collect();
+        let maker_keys : Vec<&String> = window_keys.iter().map(|keys| &keys.makers).collect();
+
+        let mut pipe = redis::pipe();
+        for keys in &window_keys {
+            pipe.hgetall(&keys.stats);
+        }
+        let bucket_stats : Vec<HashMap<String, String>> = pipe.query_async(&mut conn).await?;
+
+        // PFCOUNT over several keys counts their union, so a wallet trading in many buckets or on both sides counts once
+        let (buyers, sellers, makers) : (usize, usize, usize) = redis::pipe()
+            .pfcount(&buyer_keys)
+            .pfcount(&seller_keys)
+            .pfcount(&maker_keys)
+            .query_async(&mut conn)
+            .await?;
+
+        let field = |stats: &HashMap<String, String>, name: &str| -> f64 {
+            stats.get(name).and_then(|value| value.parse().ok()).unwrap_or(0.0)
+        };
+        let mut buys = 0;
+        let mut sells = 0;
+        let mut buy_volume = 0.0;
+        let mut sell_volume = 0.0;
+        for stats in &bucket_stats {
+            buys += field(stats, "buys") as u64;
+            sells += field(stats, "sells") as u64;
+            buy_volume += field(stats, "buy_volume");
+            sell_volume += field(stats, "sell_volume");
+        }
+
         Ok(PeriodStats{
-            txns,
-            volume,
+            txns : buys + sells,
+            volume : buy_volume + sell_volume,
             makers,
             buys,
             sells,
@@ -175,39 +615,223 @@ impl MetricOHLCVManager {
         })
     }
 
-    pub async fn save_candle(&self, candle : &OHLCVcandle) -> RedisResult<()>{
+    /// Merges the delta candles of one pair's batch into redis and acknowledges the batch messages, all in
+    /// one script run.
+    ///
+    /// Returns the merged candles, or the trade ids redis has already counted, in which case nothing was
+    /// written and the caller retries without them.
+    pub async fn merge_candles(&self, token_pair : &str, deltas : &[OHLCVcandle], trade_ids : &[(i64, &str)], stream : &str, consumer_group : &str, message_ids : &[String]) -> RedisResult<CandleMerge>{
         let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
-        let key = format!("candles:{}:{}", candle.token_pair, candle.timeframe);
-        let candle_json = serde_json::to_string(&candle).expect("unable to serialize the candle");
-        let _: () = conn.zadd(&key, candle_json, candle.timestamp).await?;
-        let cutoff = Utc::now().timestamp() - 172800;
-        let _: () = conn.zrembyscore(&key, "-inf", cutoff).await?;
+        let now = Utc::now().timestamp();
+        let mut timeframes = Vec::with_capacity(deltas.len());
+        for candle in deltas {
+            let timeframe = CandleTimeFrame::from_label(&candle.timeframe).ok_or_else(|| {
+                RedisError::from((redis::ErrorKind::ClientError, "delta candle with an unknown timeframe"))
+            })?;
+            timeframes.push(timeframe);
+        }
 
-        let _: () = conn.expire(&key, 172800).await?;
-        println!("Saved candle for token-pair : {} : {} @ {}", candle.token_pair, candle.timeframe, candle.timestamp);
-        Ok(())
+        let mut script = CANDLE_MERGE_SCRIPT.prepare_invoke();
+        script.key(stream).key(CANDLES_PENDING_KEY);
+        for candle in deltas {
+            script.key(candle_key(token_pair, &candle.timeframe, candle.timestamp))
+                .key(candle_index_key(token_pair, &candle.timeframe));
+        }
+        for (timestamp, _) in trade_ids {
+            script.key(candle_seen_key(token_pair, *timestamp));
+        }
+        script.arg(consumer_group).arg(deltas.len()).arg(trade_ids.len()).arg(CANDLE_SEEN_RETENTION_SECS);
+        for (_, id) in trade_ids {
+            script.arg(*id);
+        }
+        for (candle, timeframe) in deltas.iter().zip(&timeframes) {
+            script.arg(delta_args(candle, *timeframe, now));
+        }
+        script.arg(message_ids);
+
+        let (duplicates, rows) : (Vec<String>, Vec<HashMap<String, String>>) = script.invoke_async(&mut conn).await?;
+        if !duplicates.is_empty() {
+            return Ok(CandleMerge::Duplicates(duplicates));
+        }
+        let candles = deltas
+            .iter()
+            .zip(rows)
+            .map(|(delta, fields)| candle_from_fields(token_pair, &delta.timeframe, delta.timestamp, &fields))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| RedisError::from((redis::ErrorKind::TypeError, "candle script returned an incomplete candle")))?;
+        Ok(CandleMerge::Written(candles))
     }
 
     pub async fn get_candle(&self, token_pair: &str, timeframe : &str, timestamp : i64) -> RedisResult<Option<OHLCVcandle>>{
         let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
-        let key = format!("candles:{}:{}", token_pair, timeframe);
-        let results : Vec<(String, i64)> = conn.zrangebyscore_withscores(&key, timestamp, timestamp).await?;
-
-        if let Some((candle_json, _)) = results.first(){
-            match serde_json::from_str::<OHLCVcandle>(&candle_json) {
-                Ok(candle) => {
-                    println!("Found existing candle at {}", candle.timestamp);
-                    Ok(Some(candle))
-                }
-                Err(e) => {
-                    println!("Unable to Serialize existing candle for pair : {} due to error : {}", token_pair, e);
-                    Ok(None)
-                }
+        let fields : HashMap<String, String> = conn.hgetall(candle_key(token_pair, timeframe, timestamp)).await?;
+        Ok(candle_from_fields(token_pair, timeframe, timestamp, &fields))
+    }
+
+    // candles with `from <= timestamp <= to`, optionally gap filled with flat candles at the previous close
+    pub async fn get_candles(&self, token_pair: &str, timeframe : CandleTimeFrame, from : i64, to : i64, gap_fill : bool, max_candles : usize) -> RedisResult<Vec<OHLCVcandle>>{
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let label = timeframe.to_string();
+        let mut buckets : Vec<i64> = conn.zrangebyscore(candle_index_key(token_pair, &label), from, to).await?;
+        if !gap_fill && buckets.len() > max_candles {
+            buckets.drain(..buckets.len() - max_candles);
+        }
+        let candles = self.get_buckets(token_pair, &label, &buckets).await?;
+        if !gap_fill {
+            return Ok(candles);
+        }
+        let previous = self.get_last_candle_before(token_pair, &label, from).await?;
+        Ok(fill_gaps(candles, timeframe, token_pair, from, to, previous, max_candles))
+    }
+
+    pub async fn get_last_candle_before(&self, token_pair: &str, timeframe : &str, timestamp : i64) -> RedisResult<Option<OHLCVcandle>>{
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let buckets : Vec<i64> = conn.zrevrangebyscore_limit(candle_index_key(token_pair, timeframe), format!("({}", timestamp), "-inf", 0, 1).await?;
+        Ok(self.get_buckets(token_pair, timeframe, &buckets).await?.pop())
+    }
+
+    // buckets whose hash already expired are skipped
+    async fn get_buckets(&self, token_pair: &str, timeframe : &str, buckets : &[i64]) -> RedisResult<Vec<OHLCVcandle>>{
+        if buckets.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let mut pipe = redis::pipe();
+        for bucket in buckets {
+            pipe.hgetall(candle_key(token_pair, timeframe, *bucket));
+        }
+        let rows : Vec<HashMap<String, String>> = pipe.query_async(&mut conn).await?;
+        Ok(buckets
+            .iter()
+            .zip(rows)
+            .filter_map(|(bucket, fields)| candle_from_fields(token_pair, timeframe, *bucket, &fields))
+            .collect())
+    }
+
+    /// Takes up to `limit` persisted candles whose bucket closed before `closed_before`.
+    ///
+    /// Members move from the pending set to the processing set in one script run, callers remove them with
+    /// `ack_closed_candles` once written or put them back with `requeue_closed_candles`. A trade landing in the
+    /// bucket meanwhile adds it to pending again so the corrected candle is flushed again.
+    pub async fn take_closed_candles(&self, closed_before : i64, limit : isize) -> RedisResult<Vec<OHLCVcandle>>{
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let now = Utc::now().timestamp();
+        let members : Vec<String> = TAKE_CLOSED_CANDLES_SCRIPT
+            .key(CANDLES_PENDING_KEY)
+            .key(CANDLES_PROCESSING_KEY)
+            .arg(closed_before)
+            .arg(limit)
+            .arg(now)
+            .arg(now + CANDLE_FLUSH_LEASE_SECS)
+            .invoke_async(&mut conn)
+            .await?;
+
+        let mut candles = Vec::with_capacity(members.len());
+        let mut dropped = Vec::new();
+        for member in &members {
+            let Some((timeframe, bucket, token_pair)) = parse_pending_member(member) else {
+                println!("Dropping unreadable pending candle : {}", member);
+                dropped.push(member);
+                continue;
+            };
+            // the bucket hash may have expired when the flusher was down longer than the redis retention
+            match self.get_candle(&token_pair, &timeframe.to_string(), bucket).await? {
+                Some(candle) => candles.push(candle),
+                None => dropped.push(member)
             }
         }
-        else {
-            println!("No existing candle found for token : {}", token_pair);
-            Ok(None)
+        if !dropped.is_empty() {
+            let _: () = conn.zrem(CANDLES_PROCESSING_KEY, &dropped).await?;
+        }
+        Ok(candles)
+    }
+
+    // the candles were written, their processing entries go away
+    pub async fn ack_closed_candles(&self, candles : &[OHLCVcandle]) -> RedisResult<()>{
+        if candles.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let members : Vec<String> = candles.iter().map(|candle| pending_member(&candle.token_pair, &candle.timeframe, candle.timestamp)).collect();
+        let _: () = conn.zrem(CANDLES_PROCESSING_KEY, &members).await?;
+        Ok(())
+    }
+
+    // moves candles that failed to write from processing back to pending
+    pub async fn requeue_closed_candles(&self, candles : &[OHLCVcandle]) -> RedisResult<()>{
+        if candles.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let mut script = REQUEUE_CLOSED_CANDLES_SCRIPT.prepare_invoke();
+        script.key(CANDLES_PENDING_KEY).key(CANDLES_PROCESSING_KEY);
+        for candle in candles {
+            let close_time = CandleTimeFrame::from_label(&candle.timeframe).map(|timeframe| candle.timestamp + timeframe.to_seconds()).unwrap_or(candle.timestamp);
+            script.arg(pending_member(&candle.token_pair, &candle.timeframe, candle.timestamp)).arg(close_time);
         }
+        let _: i64 = script.invoke_async(&mut conn).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2025-10-20 00:00:00 utc
+    const T0: i64 = 1_760_918_400;
+
+    fn covered(buckets: &[(i64, i64)]) -> Vec<i64> {
+        buckets
+            .iter()
+            .flat_map(|(bucket_seconds, bucket)| (0..bucket_seconds / 60).map(move |i| bucket + i * 60))
+            .collect()
+    }
+
+    #[test]
+    fn whole_hours_come_from_hour_buckets() {
+        let window_end = T0 + 5 * 3600 + 17 * 60 + 30;
+        let buckets = window_buckets(window_end, 24 * 3600, 60, i64::MIN);
+
+        let minutes: Vec<i64> = (0..1440).map(|i| bucket_start(window_end, 60) - (1439 - i) * 60).collect();
+        assert_eq!(covered(&buckets), minutes);
+        assert_eq!(buckets.iter().filter(|(bucket_seconds, _)| *bucket_seconds == 3600).count(), 23);
+        assert_eq!(buckets.len(), 23 + 42 + 18);
+    }
+
+    #[test]
+    fn short_windows_stay_on_minute_buckets() {
+        let buckets = window_buckets(T0 + 30 * 60, 3600, 60, i64::MIN);
+        assert!(buckets.iter().all(|(bucket_seconds, _)| *bucket_seconds == 60));
+        assert_eq!(buckets.len(), 60);
+        assert_eq!(buckets.last(), Some(&(60, T0 + 30 * 60)));
+    }
+
+    #[test]
+    fn an_hour_aligned_window_ends_on_the_current_minutes() {
+        let buckets = window_buckets(T0 + 6 * 3600, 6 * 3600, 60, i64::MIN);
+        assert_eq!(buckets.first(), Some(&(60, T0 + 60)));
+        assert_eq!(buckets.last(), Some(&(60, T0 + 6 * 3600)));
+        assert_eq!(covered(&buckets).len(), 360);
+    }
+
+    #[test]
+    fn long_windows_use_hour_buckets_only() {
+        let buckets = window_buckets(T0 + 90, 7 * 86400, 3600, i64::MIN);
+        assert_eq!(buckets.len(), 168);
+        assert_eq!(buckets.last(), Some(&(3600, T0)));
+        assert_eq!(buckets.first(), Some(&(3600, T0 - 167 * 3600)));
+    }
+
+    #[test]
+    fn expired_minute_edges_fall_back_to_their_hour() {
+        // the previous 24h window, its older edge is past the minute retention
+        let now = T0 + 5 * 3600 + 17 * 60 + 30;
+        let buckets = window_buckets(now - 86400, 86400, 60, now - MINUTE_BUCKET_RETENTION_SECS);
+
+        assert_eq!(buckets.first(), Some(&(3600, T0 - 2 * 86400 + 5 * 3600)));
+        assert_eq!(buckets.iter().filter(|(bucket_seconds, _)| *bucket_seconds == 3600).count(), 24);
+        assert_eq!(buckets.iter().filter(|(bucket_seconds, _)| *bucket_seconds == 60).count(), 18);
+        assert_eq!(buckets.last(), Some(&(60, T0 - 86400 + 5 * 3600 + 17 * 60)));
     }
 }
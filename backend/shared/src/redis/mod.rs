@@ -1,3 +1,10 @@
 pub mod token_symbol_manager;
 pub mod metric_and_ohlcv_manager;
-pub mod pubsub_manager;
\ No newline at end of file
+pub mod pubsub_manager;
+pub mod market_registry_manager;
+pub mod pool_manager;
+pub mod leaderboard_manager;
+pub mod holder_manager;
+pub mod wallet_manager;
+pub mod alert_manager;
+pub mod market_event_manager;
\ No newline at end of file
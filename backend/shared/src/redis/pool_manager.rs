@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use redis::{AsyncCommands, Client, RedisError, RedisResult};
+
+use crate::types::pool::{PoolInfo, PoolReserves};
+
+const ACTIVE_POOLS_KEY: &str = "pools:active";
+const VAULT_INDEX_KEY: &str = "pools:vault-index";
+const POOL_OWNERS_KEY: &str = "pools:owners";
+const DIRTY_POOLS_KEY: &str = "pools:dirty"; // pools whose info or reserves changed since the last postgres flush
+const POOL_KEY_TTL: i64 = 7 * 86400;
+
+#[derive(Debug, Clone)]
+pub struct PoolManager {
+    redis_client: Client,
+}
+
+impl PoolManager {
+    pub fn new() -> Result<Self, RedisError> {
+        println!("Initializing redis client...for POOL cache");
+        let redis_client = Client::open(std::env::var("REDIS_URL").expect("unable to fetch redis url from env")).map_err(|e| {
+            println!("Couldn't initialize a redis client : {}", e);
+            e
+        })?;
+        Ok(Self {
+            redis_client,
+        })
+    }
+
+    // called on every swap, refreshes the pair name once metadata resolves and keeps the pool in the active set
+    pub async fn register_pool(&self, pool: &PoolInfo) -> Result<(), anyhow::Error> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let info_key = format!("pool:{}:info", pool.pool_id);
+        let _: () = redis::pipe()
+            .set_ex(&info_key, serde_json::to_string(pool)?, POOL_KEY_TTL as u64).ignore()
+            .hset(VAULT_INDEX_KEY, &pool.token_vault, &pool.pool_id).ignore()
+            .hset(VAULT_INDEX_KEY, &pool.sol_vault, &pool.pool_id).ignore()
+            .sadd(POOL_OWNERS_KEY, &pool.pool_owner).ignore()
+            .zadd(ACTIVE_POOLS_KEY, &pool.pool_id, Utc::now().timestamp()).ignore()
+            .sadd(DIRTY_POOLS_KEY, &pool.pool_id).ignore()
+            .query_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    // wallets that own pool vaults, their balances are liquidity rather than holdings
+    pub async fn are_pool_owners(&self, owners: &[String]) -> RedisResult<Vec<bool>> {
+        if owners.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        conn.smismember(POOL_OWNERS_KEY, owners).await
+    }
+
+    pub async fn get_pool(&self, pool_id: &str) -> RedisResult<Option<PoolInfo>> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let pool: Option<String> = conn.get(format!("pool:{}:info", pool_id)).await?;
+        Ok(pool.and_then(|pool| serde_json::from_str(&pool).ok()))
+    }
+
+    pub async fn get_pool_by_vault(&self, vault: &str) -> RedisResult<Option<PoolInfo>> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let pool_id: Option<String> = conn.hget(VAULT_INDEX_KEY, vault).await?;
+        match pool_id {
+            Some(pool_id) => self.get_pool(&pool_id).await,
+            None => Ok(None),
+        }
+    }
+
+    // vaults of the most recently traded pools, used to build the account subscription.
+    // pools that stopped trading are pruned from the active set and the vault index
+    pub async fn get_active_vaults(&self, active_since: i64, limit: isize) -> RedisResult<Vec<String>> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let stale_ids: Vec<String> = conn.zrangebyscore(ACTIVE_POOLS_KEY, "-inf", active_since).await?;
+        if !stale_ids.is_empty() {
+            let stale_pools = self.get_pools(&stale_ids).await?;
+            let stale_vaults: Vec<&String> = stale_pools.iter().flat_map(|pool| [&pool.token_vault, &pool.sol_vault]).collect();
+            let mut pipe = redis::pipe();
+            pipe.zrem(ACTIVE_POOLS_KEY, &stale_ids).ignore();
+            if !stale_vaults.is_empty() {
+                pipe.hdel(VAULT_INDEX_KEY, stale_vaults).ignore();
+            }
+            let _: () = pipe.query_async(&mut conn).await?;
+        }
+
+        let pool_ids: Vec<String> = conn.zrevrangebyscore_limit(ACTIVE_POOLS_KEY, "+inf", active_since, 0, limit).await?;
+        let pools = self.get_pools(&pool_ids).await?;
+        Ok(pools.into_iter().flat_map(|pool| [pool.token_vault, pool.sol_vault]).collect())
+    }
+
+    pub async fn get_active_pools(&self, active_since: i64, limit: isize) -> RedisResult<Vec<PoolInfo>> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let pool_ids: Vec<String> = conn.zrevrangebyscore_limit(ACTIVE_POOLS_KEY, "+inf", active_since, 0, limit).await?;
+        self.get_pools(&pool_ids).await
+    }
+
+    // token mints of the most recently traded pools, their account updates carry supply changes
+    pub async fn get_active_mints(&self, active_since: i64, limit: isize) -> RedisResult<Vec<String>> {
+        let pools = self.get_active_pools(active_since, limit).await?;
+        let mut mints: Vec<String> = pools.into_iter().map(|pool| pool.token_mint).filter(|mint| !mint.is_empty()).collect();
+        mints.sort();
+        mints.dedup();
+        Ok(mints)
+    }
+
+    async fn get_pools(&self, pool_ids: &[String]) -> RedisResult<Vec<PoolInfo>> {
+        if pool_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let info_keys: Vec<String> = pool_ids.iter().map(|pool_id| format!("pool:{}:info", pool_id)).collect();
+        let pools: Vec<Option<String>> = redis::cmd("MGET").arg(&info_keys).query_async(&mut conn).await?;
+        Ok(pools
+            .into_iter()
+            .flatten()
+            .filter_map(|pool| serde_json::from_str(&pool).ok())
+            .collect())
+    }
+
+    pub async fn get_reserves(&self, pool_id: &str) -> RedisResult<Option<PoolReserves>> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let reserves: Option<String> = conn.get(format!("pool:{}:reserves", pool_id)).await?;
+        Ok(reserves.and_then(|reserves| serde_json::from_str(&reserves).ok()))
+    }
+
+    // stores the pool reserves and returns the liquidity of the whole pair across its pools
+    pub async fn save_reserves(&self, pool: &PoolInfo, reserves: &PoolReserves) -> Result<f64, anyhow::Error> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let pool_liquidity_key = format!("token:{}:pool-liquidity", pool.token_pair);
+        let (pool_liquidity,): (HashMap<String, f64>,) = redis::pipe()
+            .set_ex(format!("pool:{}:reserves", pool.pool_id), serde_json::to_string(reserves)?, POOL_KEY_TTL as u64).ignore()
+            .hset(&pool_liquidity_key, &pool.pool_id, reserves.liquidity_usd).ignore()
+            .expire(&pool_liquidity_key, POOL_KEY_TTL).ignore()
+            .sadd(DIRTY_POOLS_KEY, &pool.pool_id).ignore()
+            .hgetall(&pool_liquidity_key)
+            .query_async(&mut conn)
+            .await?;
+        let pair_liquidity: f64 = pool_liquidity.values().sum();
+        let _: () = conn.set_ex(format!("token:{}:liquidity", pool.token_pair), pair_liquidity, POOL_KEY_TTL as u64).await?;
+        Ok(pair_liquidity)
+    }
+
+    pub async fn take_dirty_pools(&self, count: usize) -> RedisResult<Vec<String>> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        redis::cmd("SPOP").arg(DIRTY_POOLS_KEY).arg(count).query_async(&mut conn).await
+    }
+
+    // puts back pools whose flush failed
+    pub async fn mark_pools_dirty(&self, pool_ids: &[String]) -> RedisResult<()> {
+        if pool_ids.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        conn.sadd(DIRTY_POOLS_KEY, pool_ids).await
+    }
+
+    pub async fn get_pair_liquidity(&self, token_pair: &str) -> RedisResult<Option<f64>> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        conn.get(format!("token:{}:liquidity", token_pair)).await
+    }
+}
@@ -1,7 +1,7 @@
 use futures::StreamExt;
 use redis::{AsyncCommands, Client, RedisResult, RedisError};
 use tokio::sync::mpsc;
-use crate::{services::metrics_service::PeriodStatsUpdate, types::{ohlcv::OHLCVcandle, worker::StructeredTransaction}};
+use crate::{services::metrics_service::PeriodStatsUpdate, types::{alert::AlertNotification, market_event::MarketEvent, ohlcv::OHLCVcandle, pool::LiquidityUpdate, ranking::LeaderboardUpdate, price::{TokenInfo, TokenMetadataUpdate}, worker::StructeredTransaction}};
 use serde::{Deserialize,Serialize};
 
 #[derive(Debug)]
@@ -9,13 +9,20 @@ pub enum PubSubMessage{
     Transaction(StructeredTransaction),
     PriceMetrics(PeriodStatsUpdate),
     CurrentPrice(PriceInfo),
-    CandleUpdate(OHLCVcandle)
+    CandleUpdate(OHLCVcandle),
+    TokenMetadata(TokenMetadataUpdate),
+    Liquidity(LiquidityUpdate),
+    Leaderboard(LeaderboardUpdate),
+    Alert(AlertNotification),
+    MarketEvent(MarketEvent)
 }
 
 #[derive(Debug,Serialize,Deserialize)]
 pub struct PriceInfo{
     pub usd_current_price : f64,
-    pub sol_relative_price : f64
+    pub sol_relative_price : f64,
+    #[serde(default)]
+    pub token_pair : String
 }
 
 #[derive(Debug, Clone)]
@@ -37,9 +44,8 @@ impl PubSubManager {
         let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
         
         let message_json = serde_json::to_string(&transaction)
-            .map_err(|e| {
+            .inspect_err(|_| {
                 println!("Error serializing the transaction message");
-                e
             })?;
     
         let _: () = conn.publish("transactions", message_json).await?;
@@ -55,9 +61,8 @@ impl PubSubManager {
         let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
         
         let message_json = serde_json::to_string(&updated_data)
-            .map_err(|e| {
+            .inspect_err(|_| {
                 println!("Error serializing the transaction message");
-                e
             })?;
         
         let _: () = conn.publish("price_metrics", message_json).await?;
@@ -76,12 +81,12 @@ impl PubSubManager {
         
          let price_info = PriceInfo{
             usd_current_price : price,
-            sol_relative_price : price / sol_price
+            sol_relative_price : price / sol_price,
+            token_pair : token_pair.clone()
         };
         let message_json = serde_json::to_string(&price_info)
-            .map_err(|e| {
+            .inspect_err(|_| {
                 println!("Error serializing the current price message");
-                e
             })?;
     
         let _: () = conn.publish("current_price", message_json).await?;
@@ -93,15 +98,92 @@ impl PubSubManager {
     pub async fn publish_candle_update(&self, candle : OHLCVcandle) -> RedisResult<()>{
         let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
         let candle_json = serde_json::to_string(&candle)
-            .map_err(|e| {
+            .inspect_err(|_| {
                 println!("Error serializing the candle data");
-                e
             })?;
         let _: () = conn.publish("candle_price", candle_json).await?;
         println!("Published candle update for token : {}", candle.token_pair);
         Ok(())
     }
 
+    // one round trip for a batch of candle updates
+    pub async fn publish_candle_updates(&self, candles : &[OHLCVcandle]) -> RedisResult<()>{
+        if candles.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let mut pipe = redis::pipe();
+        for candle in candles {
+            let candle_json = serde_json::to_string(candle)
+                .inspect_err(|_| {
+                    println!("Error serializing the candle data");
+                })?;
+            pipe.publish("candle_price", candle_json).ignore();
+        }
+        let _: () = pipe.query_async(&mut conn).await?;
+        Ok(())
+    }
+
+    pub async fn publish_token_metadata(&self, mint_address : String, token_info : TokenInfo) -> RedisResult<()>{
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let metadata_update = TokenMetadataUpdate{
+            mint_address,
+            token_symbol : token_info.token_symbol,
+            token_name : token_info.token_name
+        };
+        let metadata_json = serde_json::to_string(&metadata_update)
+            .inspect_err(|_| {
+                println!("Error serializing the token metadata");
+            })?;
+        let _: () = conn.publish("token_metadata", metadata_json).await?;
+        println!("Published metadata for mint : {}", metadata_update.mint_address);
+        Ok(())
+    }
+
+    pub async fn publish_liquidity_update(&self, liquidity_update : LiquidityUpdate) -> RedisResult<()>{
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let liquidity_json = serde_json::to_string(&liquidity_update)
+            .inspect_err(|_| {
+                println!("Error serializing the liquidity update");
+            })?;
+        let _: () = conn.publish("liquidity", liquidity_json).await?;
+        println!("Published liquidity update for pool : {}", liquidity_update.pool_id);
+        Ok(())
+    }
+
+    pub async fn publish_leaderboard_update(&self, leaderboard_update : LeaderboardUpdate) -> RedisResult<()>{
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let leaderboard_json = serde_json::to_string(&leaderboard_update)
+            .inspect_err(|_| {
+                println!("Error serializing the leaderboard update");
+            })?;
+        let _: () = conn.publish("leaderboards", leaderboard_json).await?;
+        println!("Published {:?} leaderboard for {}", leaderboard_update.kind, leaderboard_update.timeframe.label());
+        Ok(())
+    }
+
+    pub async fn publish_alert(&self, notification : &AlertNotification) -> RedisResult<()>{
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let alert_json = serde_json::to_string(notification)
+            .inspect_err(|_| {
+                println!("Error serializing the alert notification");
+            })?;
+        let _: () = conn.publish("alerts", alert_json).await?;
+        println!("Published alert {} for {}", notification.alert_id, notification.token_pair);
+        Ok(())
+    }
+
+    pub async fn publish_market_event(&self, event : &MarketEvent) -> RedisResult<()>{
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let event_json = serde_json::to_string(event)
+            .inspect_err(|_| {
+                println!("Error serializing the market event");
+            })?;
+        let _: () = conn.publish("market_events", event_json).await?;
+        println!("Published {} event for {}", event.event.label(), event.token_pair);
+        Ok(())
+    }
+
     // the websocket calls this fn.
     pub async fn subscribe_to_channels(&self) -> RedisResult<mpsc::UnboundedReceiver<PubSubMessage>> {
         let (tx, rx) = mpsc::unbounded_channel(); // we create unbounded mpsc channel to send messages to it through redis subscription
@@ -121,6 +203,11 @@ impl PubSubManager {
         let _: () = pubsub.subscribe("price_metrics").await?;
         let _: () = pubsub.subscribe("current_price").await?;
         let _: () = pubsub.subscribe("candle_price").await?;
+        let _: () = pubsub.subscribe("token_metadata").await?;
+        let _: () = pubsub.subscribe("liquidity").await?;
+        let _: () = pubsub.subscribe("leaderboards").await?;
+        let _: () = pubsub.subscribe("alerts").await?;
+        let _: () = pubsub.subscribe("market_events").await?;
 
         println!("Subs to redis channel");
         let mut pubsub_stream = pubsub.into_on_message();
@@ -137,7 +224,7 @@ impl PubSubManager {
                 "transactions" => {
                     match serde_json::from_str::<StructeredTransaction>(&payload) {
                         Ok(txn) => {
-                            if let Err(_) = tx.send(PubSubMessage::Transaction(txn)){ // wrap the txn into an enum so it would be easy for websocket to identify the message                                println!("failed to send transaction to mpsc channel");
+                            if tx.send(PubSubMessage::Transaction(txn)).is_err(){ // wrap the txn into an enum so it would be easy for websocket to identify the message                                println!("failed to send transaction to mpsc channel");
                                 break;
                             }
                         },
@@ -149,7 +236,7 @@ impl PubSubManager {
                 "price_metrics" => {
                     match serde_json::from_str::<PeriodStatsUpdate>(&payload) {
                         Ok(stats) => {
-                            if let Err(_) = tx.send(PubSubMessage::PriceMetrics(stats)){
+                            if tx.send(PubSubMessage::PriceMetrics(stats)).is_err(){
                                 println!("Failed to send update stats to mpsc channel");
                                 break;
                             }
@@ -162,7 +249,7 @@ impl PubSubManager {
                 "current_price" => {
                     match serde_json::from_str::<PriceInfo>(&payload) {
                         Ok(price_info) => {
-                            if let Err(_) = tx.send(PubSubMessage::CurrentPrice(price_info)){
+                            if tx.send(PubSubMessage::CurrentPrice(price_info)).is_err(){
                                 println!("Failed to send current price to mpsc channel");
                                 break;
                             }
@@ -175,7 +262,7 @@ impl PubSubManager {
                 "candle_price" => {
                     match serde_json::from_str::<OHLCVcandle>(&payload) {
                         Ok(candle_info) => {
-                            if let Err(_) = tx.send(PubSubMessage::CandleUpdate(candle_info)){
+                            if tx.send(PubSubMessage::CandleUpdate(candle_info)).is_err(){
                                 println!("Failed to send candle data to mpsc channel");
                                 break;
                             }
@@ -185,6 +272,71 @@ impl PubSubManager {
                         }
                     }
                 },
+                "token_metadata" => {
+                    match serde_json::from_str::<TokenMetadataUpdate>(&payload) {
+                        Ok(metadata) => {
+                            if tx.send(PubSubMessage::TokenMetadata(metadata)).is_err(){
+                                println!("Failed to send token metadata to mpsc channel");
+                                break;
+                            }
+                        }
+                        Err(e) => {
+                            println!("Failed to desearialize token metadata : {}",e)
+                        }
+                    }
+                },
+                "liquidity" => {
+                    match serde_json::from_str::<LiquidityUpdate>(&payload) {
+                        Ok(liquidity) => {
+                            if tx.send(PubSubMessage::Liquidity(liquidity)).is_err(){
+                                println!("Failed to send liquidity update to mpsc channel");
+                                break;
+                            }
+                        }
+                        Err(e) => {
+                            println!("Failed to desearialize liquidity update : {}",e)
+                        }
+                    }
+                },
+                "leaderboards" => {
+                    match serde_json::from_str::<LeaderboardUpdate>(&payload) {
+                        Ok(leaderboard) => {
+                            if tx.send(PubSubMessage::Leaderboard(leaderboard)).is_err(){
+                                println!("Failed to send leaderboard update to mpsc channel");
+                                break;
+                            }
+                        }
+                        Err(e) => {
+                            println!("Failed to desearialize leaderboard update : {}",e)
+                        }
+                    }
+                },
+                "alerts" => {
+                    match serde_json::from_str::<AlertNotification>(&payload) {
+                        Ok(alert) => {
+                            if tx.send(PubSubMessage::Alert(alert)).is_err(){
+                                println!("Failed to send alert to mpsc channel");
+                                break;
+                            }
+                        }
+                        Err(e) => {
+                            println!("Failed to desearialize alert : {}",e)
+                        }
+                    }
+                },
+                "market_events" => {
+                    match serde_json::from_str::<MarketEvent>(&payload) {
+                        Ok(event) => {
+                            if tx.send(PubSubMessage::MarketEvent(event)).is_err(){
+                                println!("Failed to send market event to mpsc channel");
+                                break;
+                            }
+                        }
+                        Err(e) => {
+                            println!("Failed to desearialize market event : {}",e)
+                        }
+                    }
+                },
                 _ => {
                     println!("⚠️ Received message from unknown channel: {}", channel);
                 }
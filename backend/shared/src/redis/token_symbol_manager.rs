@@ -1,7 +1,13 @@
+use std::collections::HashMap;
+
 use chrono::{DateTime,Utc};
 use redis::{AsyncCommands, Client, RedisError};
 use serde::{Deserialize, Serialize};
-use crate::types::price::TokenInfo;
+use crate::types::price::{MetadataSection, MintAccountInfo, OracleQuote, TokenInfo, TokenMetadata, TokenSupply};
+
+const METADATA_KEY_TTL: i64 = 604800; // whole metadata hash is dropped after a week without writes
+const NEGATIVE_METADATA_TTL: u64 = 3600;
+const DIRTY_MINTS_KEY: &str = "mints:dirty"; // mints whose metadata or supply changed since the last postgres flush
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SolInfo {
@@ -9,7 +15,7 @@ pub struct SolInfo {
     pub last_updated: DateTime<Utc>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TokenSymbolManager {
     redis_client: Client,
 }
@@ -22,25 +28,213 @@ impl TokenSymbolManager {
             e
         })?;
         Ok(Self {
-            redis_client: redis_client,
+            redis_client,
         })
     }
 
-    pub async fn create_mint_info(
+    pub async fn save_onchain_metadata(
         &self,
-        mint_address: String,
+        mint_address: &str,
         token_symbol: String,
         token_name: String,
-    ) -> Result<String, anyhow::Error> {
+        uri: String,
+        update_authority: String,
+    ) -> Result<(), anyhow::Error> {
+        let fields = vec![
+            ("symbol", token_symbol),
+            ("name", token_name),
+            ("uri", uri),
+            ("update_authority", update_authority),
+        ];
+        self.save_metadata_fields(mint_address, MetadataSection::OnChain, fields).await?;
+        // a mint that now has metadata should not stay negatively cached
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let _: () = conn.del(format!("mint:{}:no-metadata", mint_address)).await?;
+        println!("saved on-chain metadata for mint : {}", mint_address);
+        Ok(())
+    }
+
+    pub async fn save_mint_account(&self, mint_address: &str, mint_account: &MintAccountInfo) -> Result<(), anyhow::Error> {
+        // revoked authorities are stored as empty strings and read back as None
+        let fields = vec![
+            ("decimals", mint_account.decimals.to_string()),
+            ("supply", mint_account.supply.to_string()),
+            ("mint_authority", mint_account.mint_authority.clone().unwrap_or_default()),
+            ("freeze_authority", mint_account.freeze_authority.clone().unwrap_or_default()),
+        ];
+        self.save_metadata_fields(mint_address, MetadataSection::MintAccount, fields).await
+    }
+
+    pub async fn save_offchain_metadata(&self, mint_address: &str, offchain: &TokenMetadata) -> Result<(), anyhow::Error> {
+        let fields = vec![
+            ("image", offchain.image.clone().unwrap_or_default()),
+            ("description", offchain.description.clone().unwrap_or_default()),
+            ("website", offchain.website.clone().unwrap_or_default()),
+            ("twitter", offchain.twitter.clone().unwrap_or_default()),
+            ("telegram", offchain.telegram.clone().unwrap_or_default()),
+            ("discord", offchain.discord.clone().unwrap_or_default()),
+        ];
+        self.save_metadata_fields(mint_address, MetadataSection::OffChain, fields).await
+    }
+
+    async fn save_metadata_fields(
+        &self,
+        mint_address: &str,
+        section: MetadataSection,
+        mut fields: Vec<(&str, String)>,
+    ) -> Result<(), anyhow::Error> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let metadata_key = format!("mint:{}:metadata", mint_address);
+        fields.push((section.updated_at_field(), Utc::now().timestamp().to_string()));
+        let _: () = conn.hset_multiple(&metadata_key, &fields).await?;
+        let _: () = conn.expire(&metadata_key, METADATA_KEY_TTL).await?;
+        let _: () = conn.sadd(DIRTY_MINTS_KEY, mint_address).await?;
+        Ok(())
+    }
+
+    pub async fn get_token_metadata(&self, mint_address: &str) -> Option<TokenMetadata> {
+        let mut conn = match self.redis_client.get_multiplexed_async_connection().await {
+            Ok(conn) => conn,
+            Err(_) => {
+                println!("Error connecting to redis server");
+                return None;
+            }
+        };
+        let fields: HashMap<String, String> = match conn.hgetall(format!("mint:{}:metadata", mint_address)).await {
+            Ok(fields) => fields,
+            Err(e) => {
+                println!("Error getting token metadata from the redis server : {}", e);
+                return None;
+            }
+        };
+        if fields.is_empty() {
+            return None;
+        }
+        let text = |name: &str| fields.get(name).filter(|value| !value.is_empty()).cloned();
+        Some(TokenMetadata {
+            mint_address: mint_address.to_string(),
+            name: text("name"),
+            symbol: text("symbol"),
+            uri: text("uri"),
+            update_authority: text("update_authority"),
+            decimals: text("decimals").and_then(|value| value.parse().ok()),
+            supply: text("supply").and_then(|value| value.parse().ok()),
+            mint_authority: text("mint_authority"),
+            freeze_authority: text("freeze_authority"),
+            image: text("image"),
+            description: text("description"),
+            website: text("website"),
+            twitter: text("twitter"),
+            telegram: text("telegram"),
+            discord: text("discord"),
+            onchain_updated_at: text("onchain_updated_at").and_then(|value| value.parse().ok()),
+            mint_account_updated_at: text("mint_account_updated_at").and_then(|value| value.parse().ok()),
+            offchain_updated_at: text("offchain_updated_at").and_then(|value| value.parse().ok()),
+        })
+    }
+
+    pub async fn mark_metadata_missing(&self, mint_address: &str) -> Result<(), anyhow::Error> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let _: () = conn.set_ex(format!("mint:{}:no-metadata", mint_address), 1, NEGATIVE_METADATA_TTL).await?;
+        println!("negatively cached mint without metadata : {}", mint_address);
+        Ok(())
+    }
+
+    pub async fn is_metadata_missing(&self, mint_address: &str) -> bool {
+        let mut conn = match self.redis_client.get_multiplexed_async_connection().await {
+            Ok(conn) => conn,
+            Err(_) => return false,
+        };
+        conn.exists(format!("mint:{}:no-metadata", mint_address)).await.unwrap_or(false)
+    }
+
+    // the first pair a mint trades under is kept for good, later metadata never moves its market
+    pub async fn pin_token_pair(&self, mint_address: &str, token_pair: &str) -> Result<String, anyhow::Error> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let key = format!("mint:{}:pair", mint_address);
+        let (pinned,): (String,) = redis::pipe()
+            .atomic()
+            .set_nx(&key, token_pair)
+            .ignore()
+            .get(&key)
+            .query_async(&mut conn)
+            .await?;
+        if pinned == token_pair {
+            println!("pinned market {} for mint : {}", pinned, mint_address);
+        }
+        Ok(pinned)
+    }
+
+    pub async fn get_token_pair(&self, mint_address: &str) -> Option<String> {
+        let mut conn = match self.redis_client.get_multiplexed_async_connection().await {
+            Ok(conn) => conn,
+            Err(_) => {
+                println!("Error connecting to redis server");
+                return None;
+            }
+        };
+        match conn.get(format!("mint:{}:pair", mint_address)).await {
+            Ok(token_pair) => token_pair,
+            Err(e) => {
+                println!("Error getting the pinned pair from the redis server : {}", e);
+                None
+            }
+        }
+    }
+
+    pub async fn save_token_supply(&self, mint_address: &str, supply: &TokenSupply) -> Result<(), anyhow::Error> {
         let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
-        let token_info = TokenInfo {
-            token_name,
-            token_symbol,
+        let supply_string = serde_json::to_string(supply)?;
+        let _: () = conn.set_ex(format!("mint:{}:supply", mint_address), supply_string, METADATA_KEY_TTL as u64).await?;
+        let _: () = conn.sadd(DIRTY_MINTS_KEY, mint_address).await?;
+        Ok(())
+    }
+
+    pub async fn take_dirty_mints(&self, count: usize) -> Result<Vec<String>, anyhow::Error> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        Ok(redis::cmd("SPOP").arg(DIRTY_MINTS_KEY).arg(count).query_async(&mut conn).await?)
+    }
+
+    // puts back mints whose flush failed
+    pub async fn mark_mints_dirty(&self, mint_addresses: &[String]) -> Result<(), anyhow::Error> {
+        if mint_addresses.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let _: () = conn.sadd(DIRTY_MINTS_KEY, mint_addresses).await?;
+        Ok(())
+    }
+
+    pub async fn get_token_supply(&self, mint_address: &str) -> Option<TokenSupply> {
+        let mut conn = match self.redis_client.get_multiplexed_async_connection().await {
+            Ok(conn) => conn,
+            Err(_) => {
+                println!("Error connecting to redis server");
+                return None;
+            }
+        };
+        let supply_string: Option<String> = match conn.get(format!("mint:{}:supply", mint_address)).await {
+            Ok(supply) => supply,
+            Err(e) => {
+                println!("Error getting token supply from the redis server : {}", e);
+                return None;
+            }
         };
-        let token_info_string = serde_json::to_string(&token_info)?;
-        let _: () = conn.set(mint_address.clone(), token_info_string).await?;
-        println!("created new mint info");
-        Ok(mint_address)
+        supply_string.and_then(|supply| serde_json::from_str(&supply).ok())
+    }
+
+    // remembers when a mint last traded so the refresh job only revisits active tokens
+    pub async fn track_mint(&self, mint_address: &str) -> Result<(), anyhow::Error> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let _: () = conn.zadd("mints:tracked", mint_address, Utc::now().timestamp()).await?;
+        Ok(())
+    }
+
+    pub async fn get_tracked_mints(&self, active_since: i64) -> Result<Vec<String>, anyhow::Error> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let _: () = conn.zrembyscore("mints:tracked", "-inf", active_since).await?;
+        let mints: Vec<String> = conn.zrangebyscore("mints:tracked", active_since, "+inf").await?;
+        Ok(mints)
     }
 
     pub async fn store_sol_value(&self, sol_price: f64) -> Result<(), anyhow::Error> {
@@ -55,6 +249,33 @@ impl TokenSymbolManager {
         Ok(())
     }
 
+    pub async fn store_oracle_quote(&self, quote: &OracleQuote) -> Result<(), anyhow::Error> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let quote_key = format!("oracle:{}:sol_price", quote.source);
+        let quote_string = serde_json::to_string(quote)?;
+        let _: () = conn.set_ex(quote_key, quote_string, 3600).await?;
+        println!("updated {} SOL quote : {}", quote.source, quote.price);
+        Ok(())
+    }
+
+    pub async fn get_oracle_quote(&self, source: &str) -> Option<OracleQuote> {
+        let mut conn = match self.redis_client.get_multiplexed_async_connection().await {
+            Ok(conn) => conn,
+            Err(_) => {
+                println!("Error connecting to redis server");
+                return None;
+            }
+        };
+        let quote_string: Option<String> = match conn.get(format!("oracle:{}:sol_price", source)).await {
+            Ok(quote) => quote,
+            Err(e) => {
+                println!("Error getting {} quote from the redis server : {}", source, e);
+                return None;
+            }
+        };
+        quote_string.and_then(|quote| serde_json::from_str(&quote).ok())
+    }
+
     pub async fn get_mint_info(&self, mint_address: &String) -> Option<TokenInfo> {
         let mut conn = match self.redis_client.get_multiplexed_async_connection().await {
             Ok(conn) => conn,
@@ -63,7 +284,10 @@ impl TokenSymbolManager {
                 return None;
             }
         };
-        let mint_info_string: Option<String> = match conn.get(mint_address).await {
+        let (token_symbol, token_name): (Option<String>, Option<String>) = match conn
+            .hget(format!("mint:{}:metadata", mint_address), &["symbol", "name"])
+            .await
+        {
             Ok(info) => info,
             Err(e) => {
                 println!("Error getting mint info from the redis server : {}", e);
@@ -71,19 +295,15 @@ impl TokenSymbolManager {
             }
         };
 
-        if let Some(mint_string) = mint_info_string {
-            println!("Found mint info in the redis server");
-            let mint_info: TokenInfo = match serde_json::from_str(&mint_string) {
-                Ok(data) => data,
-                Err(_) => {
-                    println!("Error getting session data from the server");
-                    return None;
-                }
-            };
-            Some(mint_info)
-        } else {
-            println!("Did not find any mint info in the redis server");
-            None
+        match (token_symbol, token_name) {
+            (Some(token_symbol), Some(token_name)) => {
+                println!("Found mint info in the redis server");
+                Some(TokenInfo { token_symbol, token_name })
+            }
+            _ => {
+                println!("Did not find any mint info in the redis server");
+                None
+            }
         }
     }
 
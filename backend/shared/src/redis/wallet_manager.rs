@@ -0,0 +1,89 @@
+use redis::{AsyncCommands, Client, RedisError};
+
+use crate::types::wallet::{TraderSort, WalletPosition};
+
+const WALLET_KEY_TTL: i64 = 30 * 86400;
+
+fn traders_key(token_pair: &str, sort: TraderSort) -> String {
+    format!("token:{}:traders-{}", token_pair, sort.label())
+}
+
+// positions live in one hash per wallet keyed by pair, each market keeps its traders ranked by realized pnl and volume
+#[derive(Debug, Clone)]
+pub struct WalletManager {
+    redis_client: Client,
+}
+
+impl WalletManager {
+    pub fn new() -> Result<Self, RedisError> {
+        println!("Initializing redis client...for WALLET positions");
+        let redis_client = Client::open(std::env::var("REDIS_URL").expect("unable to fetch redis url from env")).map_err(|e| {
+            println!("Couldn't initialize a redis client : {}", e);
+            e
+        })?;
+        Ok(Self {
+            redis_client,
+        })
+    }
+
+    pub async fn get_position(&self, wallet: &str, token_pair: &str) -> Result<Option<WalletPosition>, anyhow::Error> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let position: Option<String> = conn.hget(format!("wallet:{}:positions", wallet), token_pair).await?;
+        Ok(position.map(|position| serde_json::from_str(&position)).transpose()?)
+    }
+
+    pub async fn save_position(&self, position: &WalletPosition, trade_usd: f64) -> Result<(), anyhow::Error> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let positions_key = format!("wallet:{}:positions", position.wallet);
+        let _: () = redis::pipe()
+            .hset(&positions_key, &position.token_pair, serde_json::to_string(position)?).ignore()
+            .expire(&positions_key, WALLET_KEY_TTL).ignore()
+            .zadd(traders_key(&position.token_pair, TraderSort::Pnl), &position.wallet, position.realized_pnl_usd).ignore()
+            .zincr(traders_key(&position.token_pair, TraderSort::Volume), &position.wallet, trade_usd).ignore()
+            .query_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_positions(&self, wallet: &str) -> Result<Vec<WalletPosition>, anyhow::Error> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let positions: Vec<String> = conn.hvals(format!("wallet:{}:positions", wallet)).await?;
+        let mut parsed = Vec::with_capacity(positions.len());
+        for position in positions {
+            match serde_json::from_str(&position) {
+                Ok(position) => parsed.push(position),
+                Err(e) => println!("Skipping unreadable position of {} : {}", wallet, e),
+            }
+        }
+        Ok(parsed)
+    }
+
+    // highest scores first, with each trader's position in the market and their traded volume
+    pub async fn get_top_traders(
+        &self,
+        token_pair: &str,
+        sort: TraderSort,
+        limit: isize,
+    ) -> Result<Vec<(WalletPosition, f64)>, anyhow::Error> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let wallets: Vec<String> = conn.zrevrange(traders_key(token_pair, sort), 0, limit - 1).await?;
+        if wallets.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut pipe = redis::pipe();
+        for wallet in &wallets {
+            pipe.hget(format!("wallet:{}:positions", wallet), token_pair);
+        }
+        let positions: Vec<Option<String>> = pipe.query_async(&mut conn).await?;
+        let volumes: Vec<Option<f64>> = conn.zscore_multiple(traders_key(token_pair, TraderSort::Volume), &wallets).await?;
+        // a wallet whose positions expired is left out rather than shown without a position
+        Ok(positions
+            .into_iter()
+            .zip(volumes)
+            .filter_map(|(position, volume)| {
+                let position = serde_json::from_str(&position?).ok()?;
+                Some((position, volume.unwrap_or(0.0)))
+            })
+            .collect())
+    }
+}
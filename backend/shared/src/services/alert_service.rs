@@ -0,0 +1,362 @@
+use std::{collections::HashMap, net::{IpAddr, SocketAddr}, sync::Arc, time::Duration};
+
+use chrono::Utc;
+use tokio::{sync::RwLock, time::sleep};
+
+use crate::{
+    redis::{
+        alert_manager::AlertManager,
+        metric_and_ohlcv_manager::MetricOHLCVManager,
+        pubsub_manager::{PriceInfo, PubSubManager, PubSubMessage},
+    },
+    services::{
+        metrics_service::PeriodStatsUpdate,
+        outbound::OUTBOUND,
+    },
+    types::{
+        alert::{AlertCondition, AlertNotification, AlertRule},
+        worker::StructeredTransaction,
+    },
+};
+
+const RULES_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+const CROSS_DEDUP_BUCKET_SECS: i64 = 10; // engines seeing the same crossing agree on the event key within this bucket
+const WEBHOOK_BUCKET: &str = "webhooks"; // webhook hosts are user chosen, they all share one outbound bucket
+
+// false for loopback, private, link local, unspecified and other addresses that are not reachable on the internet
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || first == 0
+                || first >= 240
+                || (first == 100 && (64..128).contains(&second)) // carrier grade nat
+                || (first == 198 && (second == 18 || second == 19))) // benchmarking
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ipv4) = ip.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(ipv4));
+            }
+            let segments = ip.segments();
+            // nat64 embeds an ipv4 address in the last 32 bits
+            if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+                let [.., high, low] = segments;
+                return is_public_ip(IpAddr::V4(((high as u32) << 16 | low as u32).into()));
+            }
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || (segments[0] & 0xfe00) == 0xfc00 // unique local
+                || (segments[0] & 0xffc0) == 0xfe80 // link local
+                || (segments[0] == 0x2001 && segments[1] == 0x0db8)) // documentation
+        }
+    }
+}
+
+/// Resolves a webhook url to the host and address it is delivered to.
+///
+/// Every address the host resolves to has to be public, so a rule can't make the server call
+/// localhost, a private network or the cloud metadata endpoint. Deliveries resolve again and
+/// connect to the checked address, so a DNS answer changed after the rule was saved can't either.
+pub async fn resolve_webhook(webhook_url: &str) -> Result<(String, SocketAddr), String> {
+    let url = reqwest::Url::parse(webhook_url).map_err(|_| format!("invalid webhook url {}", webhook_url))?;
+    if url.scheme() != "https" && url.scheme() != "http" {
+        return Err(format!("invalid webhook url {}", webhook_url));
+    }
+    let port = url.port_or_known_default().ok_or_else(|| format!("invalid webhook url {}", webhook_url))?;
+    // ipv6 literals come back in brackets
+    let host = url
+        .host_str()
+        .map(|host| host.trim_start_matches('[').trim_end_matches(']').to_string())
+        .ok_or_else(|| format!("invalid webhook url {}", webhook_url))?;
+    let addresses: Vec<SocketAddr> = match host.parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => tokio::net::lookup_host((host.as_str(), port))
+            .await
+            .map_err(|e| format!("unable to resolve webhook host {} : {}", host, e))?
+            .collect(),
+    };
+    match addresses.first() {
+        Some(address) if addresses.iter().all(|address| is_public_ip(address.ip())) => Ok((host, *address)),
+        Some(_) => Err(format!("webhook host {} is not a public address", host)),
+        None => Err(format!("webhook host {} has no address", host)),
+    }
+}
+
+// one client per delivery, pinned to the checked address and refusing redirects to anywhere else
+async fn deliver_webhook(webhook_url: &str, notification: &AlertNotification) -> Result<(), anyhow::Error> {
+    let (host, address) = resolve_webhook(webhook_url).await.map_err(anyhow::Error::msg)?;
+    let http_client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .resolve(&host, address)
+        .build()?;
+    http_client.post(webhook_url).json(notification).send().await?.error_for_status()?;
+    Ok(())
+}
+
+// evaluates user rules against the price, metrics and transaction channels and delivers over websocket and webhooks
+#[derive(Debug)]
+pub struct AlertService {
+    alert_manager: AlertManager,
+    metric_manager: MetricOHLCVManager,
+    pubsub_manager: PubSubManager,
+    rules: RwLock<HashMap<String, Vec<AlertRule>>>, // by token pair
+    last_prices: RwLock<HashMap<String, f64>>,
+}
+
+impl AlertService {
+    pub fn new(pubsub_manager: PubSubManager) -> Self {
+        Self {
+            alert_manager: AlertManager::new().expect("Error creating alert manager"),
+            metric_manager: MetricOHLCVManager::new().expect("Error creating metric manager for alerts"),
+            pubsub_manager,
+            rules: RwLock::new(HashMap::new()),
+            last_prices: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub async fn start(self: Arc<Self>) {
+        let refresher = self.clone();
+        tokio::spawn(async move {
+            loop {
+                refresher.reload_rules().await;
+                sleep(RULES_REFRESH_INTERVAL).await;
+            }
+        });
+
+        loop {
+            let mut receiver = match self.pubsub_manager.subscribe_to_channels().await {
+                Ok(receiver) => receiver,
+                Err(e) => {
+                    println!("Alert engine could not subscribe to channels : {}", e);
+                    sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+            println!("Alert engine listening for market updates");
+            while let Some(message) = receiver.recv().await {
+                match message {
+                    PubSubMessage::CurrentPrice(price_info) => self.on_price(&price_info).await,
+                    PubSubMessage::PriceMetrics(update) => self.on_metrics(&update).await,
+                    PubSubMessage::Transaction(txn) => self.on_transaction(&txn).await,
+                    _ => {}
+                }
+            }
+            println!("Alert engine subscription ended, resubscribing");
+            sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    async fn reload_rules(&self) {
+        match self.alert_manager.get_rules().await {
+            Ok(rules) => {
+                let mut by_pair: HashMap<String, Vec<AlertRule>> = HashMap::new();
+                for rule in rules {
+                    by_pair.entry(rule.token_pair.clone()).or_default().push(rule);
+                }
+                *self.rules.write().await = by_pair;
+            }
+            Err(e) => println!("Error reloading alert rules : {}", e),
+        }
+    }
+
+    async fn rules_for(&self, token_pair: &str) -> Vec<AlertRule> {
+        self.rules.read().await.get(token_pair).cloned().unwrap_or_default()
+    }
+
+    // price rules are edge triggered, they fire when the price crosses the threshold rather than while it stays past it
+    async fn on_price(&self, price_info: &PriceInfo) {
+        if price_info.token_pair.is_empty() {
+            return;
+        }
+        let price = price_info.usd_current_price;
+        let previous = self.last_prices.write().await.insert(price_info.token_pair.clone(), price);
+        let Some(previous) = previous else { return };
+
+        let event_key = format!("cross:{}", Utc::now().timestamp() / CROSS_DEDUP_BUCKET_SECS);
+        for rule in self.rules_for(&price_info.token_pair).await {
+            let threshold = match rule.condition {
+                AlertCondition::PriceAbove { price: threshold } if previous < threshold && price >= threshold => threshold,
+                AlertCondition::PriceBelow { price: threshold } if previous > threshold && price <= threshold => threshold,
+                _ => continue,
+            };
+            let message = format!("{} crossed ${} (now ${})", rule.token_pair, threshold, price);
+            self.fire(&rule, &event_key, price, message).await;
+        }
+    }
+
+    async fn on_metrics(&self, update: &PeriodStatsUpdate) {
+        let rules = self.rules_for(&update.token_pair).await;
+        let minute = Utc::now().timestamp() / 60;
+        for rule in rules {
+            match rule.condition {
+                AlertCondition::PriceChange { timeframe, percent } if timeframe == update.timeframe => {
+                    let change_percent = update.price_change * 100.0;
+                    if change_percent.abs() >= percent {
+                        let message = format!("{} moved {:+.2}% in {}", rule.token_pair, change_percent, timeframe.label());
+                        self.fire(&rule, &format!("change:{}:{}", timeframe.label(), minute), change_percent, message).await;
+                    }
+                }
+                AlertCondition::VolumeSpike { timeframe, multiplier } if timeframe == update.timeframe => {
+                    let Some(current) = &update.period_stats else { continue };
+                    let previous = match self.metric_manager.get_previous_metrics(&update.token_pair, &timeframe).await {
+                        Ok(previous) => previous,
+                        Err(e) => {
+                            println!("Error reading previous volume of {} : {}", update.token_pair, e);
+                            continue;
+                        }
+                    };
+                    // without a previous window there is no baseline to spike against
+                    if previous.volume <= 0.0 {
+                        continue;
+                    }
+                    let ratio = current.volume / previous.volume;
+                    if ratio >= multiplier {
+                        let message = format!("{} volume is {:.1}x the previous {}", rule.token_pair, ratio, timeframe.label());
+                        self.fire(&rule, &format!("volume:{}:{}", timeframe.label(), minute), ratio, message).await;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    async fn on_transaction(&self, txn: &StructeredTransaction) {
+        let Some(usd_value) = txn.usd_value else { return };
+        for rule in self.rules_for(&txn.token_pair).await {
+            if let AlertCondition::LargeTrade { min_usd } = rule.condition
+                && usd_value >= min_usd {
+                let message = format!("{:?} of ${:.0} on {} by {}", txn.purchase_type, usd_value, rule.token_pair, txn.owner);
+                let event_key = format!("trade:{}:{}", txn.owner, txn.date.timestamp_millis());
+                self.fire(&rule, &event_key, usd_value, message).await;
+            }
+        }
+    }
+
+    async fn fire(&self, rule: &AlertRule, event_key: &str, value: f64, message: String) {
+        match self.alert_manager.try_fire(rule, event_key).await {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(e) => {
+                println!("Error claiming alert {} : {}", rule.id, e);
+                return;
+            }
+        }
+        let notification = AlertNotification {
+            alert_id: rule.id.clone(),
+            owner: rule.owner.clone(),
+            token_pair: rule.token_pair.clone(),
+            condition: rule.condition.clone(),
+            value,
+            message,
+            triggered_at: Utc::now().timestamp(),
+        };
+        println!("Alert {} triggered : {}", rule.id, notification.message);
+
+        if let Err(e) = self.pubsub_manager.publish_alert(&notification).await {
+            println!("Error publishing alert {} : {}", rule.id, e);
+        }
+        if let Some(webhook_url) = rule.webhook_url.clone() {
+            // webhooks go out on their own task so a slow receiver never holds up evaluation
+            tokio::spawn(async move {
+                let result = OUTBOUND.call(WEBHOOK_BUCKET, || deliver_webhook(&webhook_url, &notification)).await;
+                if let Err(e) = result {
+                    println!("Error delivering alert {} to webhook : {}", notification.alert_id, e);
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    fn is_public(ip: &str) -> bool {
+        is_public_ip(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn rejects_loopback_and_unspecified() {
+        assert!(!is_public("127.0.0.1"));
+        assert!(!is_public("127.8.9.10"));
+        assert!(!is_public("0.0.0.0"));
+        assert!(!is_public("::1"));
+        assert!(!is_public("::"));
+    }
+
+    #[test]
+    fn rejects_private_networks() {
+        assert!(!is_public("10.0.0.1"));
+        assert!(!is_public("172.16.0.1"));
+        assert!(!is_public("172.31.255.255"));
+        assert!(!is_public("192.168.1.1"));
+        assert!(!is_public("100.64.0.1"));
+        // just outside the rfc1918 and carrier grade nat ranges
+        assert!(is_public("172.32.0.1"));
+        assert!(is_public("100.128.0.1"));
+    }
+
+    #[test]
+    fn rejects_link_local_and_the_metadata_endpoint() {
+        assert!(!is_public("169.254.169.254"));
+        assert!(!is_public("169.254.0.1"));
+        assert!(!is_public("fe80::1"));
+    }
+
+    #[test]
+    fn rejects_ipv4_embedded_in_ipv6() {
+        assert!(!is_public("::ffff:127.0.0.1"));
+        assert!(!is_public("::ffff:169.254.169.254"));
+        assert!(!is_public("::ffff:10.0.0.1"));
+        assert!(!is_public("64:ff9b::a9fe:a9fe")); // nat64 of 169.254.169.254
+        assert!(is_public("::ffff:8.8.8.8"));
+        assert!(is_public("64:ff9b::808:808"));
+    }
+
+    #[test]
+    fn rejects_unique_local_ipv6() {
+        assert!(!is_public("fc00::1"));
+        assert!(!is_public("fd12:3456:789a::1"));
+        assert!(is_public("2606:4700:4700::1111"));
+    }
+
+    #[test]
+    fn accepts_public_addresses() {
+        assert!(is_public("8.8.8.8"));
+        assert!(is_public("1.1.1.1"));
+        assert!(!is_public("224.0.0.1"));
+        assert!(!is_public("255.255.255.255"));
+    }
+
+    #[test]
+    fn resolve_webhook_checks_literal_addresses() {
+        assert_eq!(
+            block_on(resolve_webhook("https://8.8.8.8/hook")),
+            Ok(("8.8.8.8".to_string(), "8.8.8.8:443".parse().unwrap()))
+        );
+        assert_eq!(
+            block_on(resolve_webhook("http://[2606:4700:4700::1111]:8080/hook")),
+            Ok(("2606:4700:4700::1111".to_string(), "[2606:4700:4700::1111]:8080".parse().unwrap()))
+        );
+        assert!(block_on(resolve_webhook("http://127.0.0.1:8080/hook")).is_err());
+        assert!(block_on(resolve_webhook("http://169.254.169.254/latest/meta-data")).is_err());
+        assert!(block_on(resolve_webhook("http://[::ffff:10.0.0.1]/hook")).is_err());
+        assert!(block_on(resolve_webhook("http://[fd00::1]/hook")).is_err());
+    }
+
+    #[test]
+    fn resolve_webhook_rejects_other_schemes() {
+        assert!(block_on(resolve_webhook("ftp://8.8.8.8/hook")).is_err());
+        assert!(block_on(resolve_webhook("file:///etc/passwd")).is_err());
+        assert!(block_on(resolve_webhook("not a url")).is_err());
+    }
+}
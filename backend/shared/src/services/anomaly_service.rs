@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use tokio::sync::Mutex;
+
+use crate::{
+    redis::{market_event_manager::MarketEventManager, pubsub_manager::PubSubManager},
+    types::{
+        market_event::{MarketEvent, MarketEventKind},
+        worker::StructeredTransaction,
+    },
+};
+
+const BASELINE_MINUTES: f64 = 60.0; // ewma span of the per minute baselines
+const MIN_BASELINE_SAMPLES: u32 = 15; // minutes folded in before price and volume events are trusted
+const MIN_RETURN_STDDEV: f64 = 0.002; // floor so a flat pair does not flag every tick as several sigma
+
+#[derive(Debug, Clone)]
+pub struct AnomalyConfig {
+    pub price_sigma: f64,
+    pub volume_multiplier: f64,
+    pub whale_trade_usd: f64,
+    pub liquidity_pull_percent: f64,
+    pub min_liquidity_usd: f64,
+    pub cooldown_secs: u64,
+}
+
+impl AnomalyConfig {
+    // ANOMALY_PRICE_SIGMA, ANOMALY_VOLUME_MULTIPLIER, ANOMALY_WHALE_TRADE_USD, ANOMALY_LIQUIDITY_PULL_PERCENT,
+    // ANOMALY_MIN_LIQUIDITY_USD and ANOMALY_COOLDOWN_SECS override the defaults
+    pub fn from_env() -> Self {
+        let env_f64 = |name: &str, default: f64| {
+            std::env::var(name).ok().and_then(|value| value.parse::<f64>().ok()).unwrap_or(default)
+        };
+        Self {
+            price_sigma: env_f64("ANOMALY_PRICE_SIGMA", 4.0),
+            volume_multiplier: env_f64("ANOMALY_VOLUME_MULTIPLIER", 5.0),
+            whale_trade_usd: env_f64("ANOMALY_WHALE_TRADE_USD", 50_000.0),
+            liquidity_pull_percent: env_f64("ANOMALY_LIQUIDITY_PULL_PERCENT", 30.0),
+            min_liquidity_usd: env_f64("ANOMALY_MIN_LIQUIDITY_USD", 5_000.0),
+            cooldown_secs: env_f64("ANOMALY_COOLDOWN_SECS", 300.0) as u64,
+        }
+    }
+}
+
+// per pair state of the minute being traded and the ewma of finished minutes
+#[derive(Debug, Clone, Default)]
+struct PairBaseline {
+    minute: i64,
+    minute_open: f64,
+    minute_volume: f64,
+    last_price: f64,
+    return_mean: f64,
+    return_var: f64,
+    volume_mean: f64,
+    samples: u32,
+}
+
+impl PairBaseline {
+    fn fold_minute(&mut self, minute_return: f64, minute_volume: f64) {
+        let alpha = 2.0 / (BASELINE_MINUTES + 1.0);
+        if self.samples == 0 {
+            self.return_mean = minute_return;
+            self.volume_mean = minute_volume;
+        } else {
+            let deviation = minute_return - self.return_mean;
+            self.return_mean += alpha * deviation;
+            self.return_var = (1.0 - alpha) * (self.return_var + alpha * deviation * deviation);
+            self.volume_mean += alpha * (minute_volume - self.volume_mean);
+        }
+        self.samples = self.samples.saturating_add(1);
+    }
+
+    // closes the finished minute and any empty minutes since, then starts `minute` at `price`
+    fn roll_to(&mut self, minute: i64, price: f64) {
+        if self.minute > 0 && self.minute_open > 0.0 {
+            self.fold_minute((self.last_price / self.minute_open).ln(), self.minute_volume);
+            let empty_minutes = (minute - self.minute - 1).clamp(0, BASELINE_MINUTES as i64);
+            for _ in 0..empty_minutes {
+                self.fold_minute(0.0, 0.0);
+            }
+        }
+        self.minute = minute;
+        self.minute_open = if self.last_price > 0.0 { self.last_price } else { price };
+        self.minute_volume = 0.0;
+    }
+}
+
+/// Detects pumps, dumps, volume spikes and whale trades from the trades the metrics worker consumes.
+///
+/// Baselines are kept in memory per worker and warm up over `MIN_BASELINE_SAMPLES` minutes after a restart.
+#[derive(Debug)]
+pub struct AnomalyService {
+    config: AnomalyConfig,
+    event_manager: MarketEventManager,
+    baselines: Mutex<HashMap<String, PairBaseline>>,
+}
+
+impl AnomalyService {
+    pub fn new(pubsub_manager: PubSubManager) -> Self {
+        Self {
+            config: AnomalyConfig::from_env(),
+            event_manager: MarketEventManager::new(pubsub_manager).expect("Error creating market event manager"),
+            baselines: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn on_trade(&self, txn: &StructeredTransaction) -> Result<(), anyhow::Error> {
+        if txn.token_price <= 0.0 {
+            return Ok(());
+        }
+        let detected_at = Utc::now().timestamp();
+        let mut events = Vec::new();
+
+        if let Some(usd_value) = txn.usd_value.filter(|usd_value| *usd_value >= self.config.whale_trade_usd) {
+            events.push(MarketEventKind::WhaleTrade {
+                side: txn.purchase_type.clone(),
+                usd_value,
+                wallet: txn.owner.clone(),
+            });
+        }
+
+        {
+            let mut baselines = self.baselines.lock().await;
+            let baseline = baselines.entry(txn.token_pair.clone()).or_default();
+            let minute = txn.date.timestamp() / 60;
+            // a late trade from an earlier minute still counts toward volume but does not roll the window back
+            if minute > baseline.minute {
+                baseline.roll_to(minute, txn.token_price);
+            }
+            baseline.minute_volume += txn.usd_value.unwrap_or(0.0);
+            baseline.last_price = txn.token_price;
+
+            if baseline.samples >= MIN_BASELINE_SAMPLES {
+                let change = txn.token_price / baseline.minute_open - 1.0;
+                let stddev = baseline.return_var.sqrt().max(MIN_RETURN_STDDEV);
+                let sigma = ((txn.token_price / baseline.minute_open).ln() - baseline.return_mean) / stddev;
+                if sigma >= self.config.price_sigma {
+                    events.push(MarketEventKind::Pump { sigma, change });
+                } else if sigma <= -self.config.price_sigma {
+                    events.push(MarketEventKind::Dump { sigma, change });
+                }
+
+                if baseline.volume_mean > 0.0 {
+                    let ratio = baseline.minute_volume / baseline.volume_mean;
+                    if ratio >= self.config.volume_multiplier {
+                        events.push(MarketEventKind::VolumeSpike { ratio, minute_volume: baseline.minute_volume });
+                    }
+                }
+            }
+        }
+
+        for event in events {
+            // whale trades are individual trades, the other kinds describe a state that persists for a while
+            let cooldown_secs = match event {
+                MarketEventKind::WhaleTrade { .. } => 0,
+                _ => self.config.cooldown_secs,
+            };
+            let market_event = MarketEvent {
+                token_pair: txn.token_pair.clone(),
+                event,
+                price: Some(txn.token_price),
+                detected_at,
+            };
+            self.event_manager.emit(&market_event, cooldown_secs).await?;
+        }
+        Ok(())
+    }
+}
+
+// a drop of pair liquidity beyond the configured share, checked whenever reserves are saved
+pub fn detect_liquidity_pull(config: &AnomalyConfig, previous_usd: f64, current_usd: f64) -> Option<MarketEventKind> {
+    if previous_usd < config.min_liquidity_usd || current_usd >= previous_usd {
+        return None;
+    }
+    let drop = (previous_usd - current_usd) / previous_usd;
+    (drop * 100.0 >= config.liquidity_pull_percent).then_some(MarketEventKind::LiquidityPull {
+        previous_usd,
+        current_usd,
+        drop,
+    })
+}
@@ -0,0 +1,325 @@
+use std::collections::{BTreeMap, HashSet};
+
+use crate::types::ohlcv::{fill_gaps, CandleTimeFrame, CandleTrade, OHLCVcandle};
+
+const MAX_GAP_CANDLES: usize = 120;
+
+#[derive(Debug, Clone)]
+struct BufferedTrade {
+    timestamp: i64,
+    trade: CandleTrade,
+}
+
+/// Trades of one pair buffered for a single write, with the stream messages that carried them.
+///
+/// The batch turns into delta candles: what its trades add to every bucket they touch. Deltas are
+/// merged into redis rather than replacing the bucket, so several workers can write the same pair.
+#[derive(Debug, Default)]
+pub struct RollupBatch {
+    pub token_pair: String,
+    trades: Vec<BufferedTrade>,
+    trade_ids: HashSet<String>,
+    pub message_ids: Vec<String>,
+}
+
+impl RollupBatch {
+    fn new(token_pair: &str) -> Self {
+        Self {
+            token_pair: token_pair.to_string(),
+            ..Default::default()
+        }
+    }
+
+    // false when the batch already holds a trade with the same id, the message is still acknowledged with it
+    fn push(&mut self, message_id: String, timestamp: i64, trade: CandleTrade) -> bool {
+        self.message_ids.push(message_id);
+        if !trade.id.is_empty() && !self.trade_ids.insert(trade.id.clone()) {
+            return false;
+        }
+        self.trades.push(BufferedTrade { timestamp, trade });
+        true
+    }
+
+    fn append(&mut self, other: RollupBatch) {
+        self.message_ids.extend(other.message_ids);
+        for buffered in other.trades {
+            if buffered.trade.id.is_empty() || self.trade_ids.insert(buffered.trade.id.clone()) {
+                self.trades.push(buffered);
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.trades.is_empty() && self.message_ids.is_empty()
+    }
+
+    pub fn trade_count(&self) -> usize {
+        self.trades.len()
+    }
+
+    /// Block time and id of every trade that can be deduplicated.
+    pub fn trade_ids(&self) -> Vec<(i64, &str)> {
+        self.trades
+            .iter()
+            .filter(|buffered| !buffered.trade.id.is_empty())
+            .map(|buffered| (buffered.timestamp, buffered.trade.id.as_str()))
+            .collect()
+    }
+
+    /// Drops trades that were already counted, returns how many went.
+    pub fn drop_trades(&mut self, ids: &HashSet<String>) -> usize {
+        let before = self.trades.len();
+        self.trades.retain(|buffered| !ids.contains(&buffered.trade.id));
+        self.trade_ids.retain(|id| !ids.contains(id));
+        before - self.trades.len()
+    }
+
+    /// Delta candles of every timeframe, rolled up from the 1s buckets of the batch trades.
+    ///
+    /// Buckets that ended before `oldest_end` are past the lateness window and left out, their trades
+    /// still count in the longer buckets containing them.
+    pub fn deltas(&self, oldest_end: i64) -> Vec<OHLCVcandle> {
+        let mut deltas = Vec::new();
+        let mut below: BTreeMap<i64, OHLCVcandle> = BTreeMap::new();
+        for (index, timeframe) in CandleTimeFrame::all().into_iter().enumerate() {
+            let label = timeframe.to_string();
+            let mut level: BTreeMap<i64, OHLCVcandle> = BTreeMap::new();
+            if index == 0 {
+                for buffered in &self.trades {
+                    let bucket = timeframe.round_timestamp(buffered.timestamp);
+                    match level.get_mut(&bucket) {
+                        Some(candle) => candle.absorb_trade(&buffered.trade),
+                        None => {
+                            level.insert(bucket, OHLCVcandle::from_trade(&self.token_pair, &label, bucket, &buffered.trade));
+                        }
+                    }
+                }
+            } else {
+                for child in below.values() {
+                    let bucket = timeframe.round_timestamp(child.timestamp);
+                    match level.get_mut(&bucket) {
+                        Some(candle) => candle.merge(child),
+                        None => {
+                            level.insert(bucket, child.relabel(&label, bucket));
+                        }
+                    }
+                }
+            }
+            let step = timeframe.to_seconds();
+            deltas.extend(level.values().filter(|candle| candle.timestamp + step >= oldest_end).cloned());
+            below = level;
+        }
+        deltas
+    }
+}
+
+/// Trades of one pair waiting for the next flush, plus the last candle written per timeframe.
+///
+/// Only the trades since the last flush live here, redis holds the candles. Each flush rolls the
+/// buffered trades up from 1s into every timeframe (see `RollupBatch::deltas`) and merges the deltas
+/// into the redis buckets. The last written candles are kept to publish flat candles over gaps.
+#[derive(Debug)]
+pub struct PairRollup {
+    token_pair: String,
+    batch: RollupBatch,
+    last_written: Vec<Option<OHLCVcandle>>,
+    last_trade_at: i64,
+}
+
+impl PairRollup {
+    pub fn new(token_pair: &str) -> Self {
+        Self {
+            token_pair: token_pair.to_string(),
+            batch: RollupBatch::new(token_pair),
+            last_written: vec![None; CandleTimeFrame::all().len()],
+            last_trade_at: 0,
+        }
+    }
+
+    /// Buffers a trade read at wall clock `now`, false when the buffer already holds it.
+    pub fn apply_trade(&mut self, message_id: String, timestamp: i64, trade: CandleTrade, now: i64) -> bool {
+        self.last_trade_at = now;
+        self.batch.push(message_id, timestamp, trade)
+    }
+
+    /// Hands the buffered trades over for a write, `None` when there is nothing to write.
+    pub fn take_batch(&mut self) -> Option<RollupBatch> {
+        if self.batch.is_empty() {
+            return None;
+        }
+        Some(std::mem::replace(&mut self.batch, RollupBatch::new(&self.token_pair)))
+    }
+
+    // a batch that failed to write goes back in front of the trades buffered meanwhile
+    pub fn restore(&mut self, mut batch: RollupBatch) {
+        let buffered = std::mem::replace(&mut self.batch, RollupBatch::new(&self.token_pair));
+        batch.append(buffered);
+        self.batch = batch;
+    }
+
+    /// Remembers the candles redis returned for a write and returns the flat candles for the empty
+    /// buckets between them and the candles written before.
+    pub fn record_written(&mut self, candles: &[OHLCVcandle]) -> Vec<OHLCVcandle> {
+        let mut gaps = Vec::new();
+        for candle in candles {
+            let Some(index) = CandleTimeFrame::all().iter().position(|timeframe| timeframe.to_string() == candle.timeframe) else {
+                continue;
+            };
+            let timeframe = CandleTimeFrame::all()[index];
+            let last = &mut self.last_written[index];
+            if let Some(previous) = last.as_ref() {
+                if candle.timestamp < previous.timestamp {
+                    continue;
+                }
+                let step = timeframe.to_seconds();
+                gaps.extend(fill_gaps(
+                    Vec::new(),
+                    timeframe,
+                    &self.token_pair,
+                    previous.timestamp + step,
+                    candle.timestamp - step,
+                    Some(previous.clone()),
+                    MAX_GAP_CANDLES,
+                ));
+            }
+            *last = Some(candle.clone());
+        }
+        gaps
+    }
+
+    // nothing buffered and no trade for `idle_secs`, the pair can be dropped from memory
+    pub fn is_idle(&self, now: i64, idle_secs: i64) -> bool {
+        self.batch.is_empty() && now - self.last_trade_at >= idle_secs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::worker::{TradeOrder, Type};
+
+    const PAIR: &str = "BONK/SOL";
+    // a monday, so every timeframe up to 1w starts a bucket here
+    const T0: i64 = 1_760_918_400;
+
+    fn trade(id: &str, price: f64, quantity: f64, side: Type, slot: u64) -> CandleTrade {
+        CandleTrade {
+            price,
+            quote_price: price / 200.0,
+            quantity,
+            quote_quantity: quantity * price / 200.0,
+            usd_value: quantity * price,
+            side,
+            order: TradeOrder { block_time: 0, slot, index: 0 },
+            id: id.to_string(),
+        }
+    }
+
+    fn delta<'a>(deltas: &'a [OHLCVcandle], timeframe: &str, bucket: i64) -> &'a OHLCVcandle {
+        deltas
+            .iter()
+            .find(|candle| candle.timeframe == timeframe && candle.timestamp == bucket)
+            .unwrap_or_else(|| panic!("no {} delta at {}", timeframe, bucket))
+    }
+
+    #[test]
+    fn deltas_roll_one_second_buckets_up() {
+        let mut rollup = PairRollup::new(PAIR);
+        rollup.apply_trade("1-0".into(), T0 + 1, trade("a:0", 10.0, 1.0, Type::Buy, 100), T0);
+        rollup.apply_trade("1-1".into(), T0 + 1, trade("b:0", 12.0, 2.0, Type::Sell, 101), T0);
+        rollup.apply_trade("1-2".into(), T0 + 61, trade("c:0", 8.0, 3.0, Type::Buy, 150), T0);
+
+        let batch = rollup.take_batch().expect("trades buffered");
+        let deltas = batch.deltas(T0);
+
+        let second = delta(&deltas, "1s", T0 + 1);
+        assert_eq!((second.open, second.high, second.low, second.close), (10.0, 12.0, 10.0, 12.0));
+        assert_eq!(second.trade_count, 2);
+
+        let first_minute = delta(&deltas, "1m", T0);
+        assert_eq!(first_minute.volume, 3.0);
+        let second_minute = delta(&deltas, "1m", T0 + 60);
+        assert_eq!(second_minute.trade_count, 1);
+
+        for timeframe in ["5m", "15m", "1h", "4h", "1d", "1w"] {
+            let candle = delta(&deltas, timeframe, T0);
+            assert_eq!((candle.open, candle.high, candle.low, candle.close), (10.0, 12.0, 8.0, 8.0), "{}", timeframe);
+            assert_eq!((candle.buy_volume, candle.sell_volume, candle.trade_count), (4.0, 2.0, 3), "{}", timeframe);
+        }
+    }
+
+    #[test]
+    fn open_and_close_follow_chain_order() {
+        let mut rollup = PairRollup::new(PAIR);
+        // the later slot arrives first
+        rollup.apply_trade("1-0".into(), T0 + 30, trade("late:0", 20.0, 1.0, Type::Buy, 200), T0);
+        rollup.apply_trade("1-1".into(), T0 + 10, trade("early:0", 5.0, 1.0, Type::Buy, 100), T0);
+
+        let deltas = rollup.take_batch().expect("trades buffered").deltas(T0);
+        let minute = delta(&deltas, "1m", T0);
+        assert_eq!((minute.open, minute.close), (5.0, 20.0));
+        assert_eq!((minute.first_trade.slot, minute.last_trade.slot), (100, 200));
+    }
+
+    #[test]
+    fn buckets_past_lateness_only_reach_longer_timeframes() {
+        let mut rollup = PairRollup::new(PAIR);
+        rollup.apply_trade("1-0".into(), T0 + 5, trade("a:0", 1.0, 1.0, Type::Buy, 1), T0 + 3600);
+
+        let deltas = rollup.take_batch().expect("trades buffered").deltas(T0 + 600);
+        let timeframes: Vec<&str> = deltas.iter().map(|candle| candle.timeframe.as_str()).collect();
+        assert_eq!(timeframes, ["15m", "1h", "4h", "1d", "1w"]);
+    }
+
+    #[test]
+    fn duplicate_trades_are_counted_once() {
+        let mut rollup = PairRollup::new(PAIR);
+        assert!(rollup.apply_trade("1-0".into(), T0, trade("a:0", 1.0, 1.0, Type::Buy, 1), T0));
+        assert!(!rollup.apply_trade("1-1".into(), T0, trade("a:0", 1.0, 1.0, Type::Buy, 1), T0));
+        // legs of the same transaction are different trades
+        assert!(rollup.apply_trade("1-2".into(), T0, trade("a:1", 1.0, 1.0, Type::Buy, 1), T0));
+
+        let mut batch = rollup.take_batch().expect("trades buffered");
+        assert_eq!(batch.trade_count(), 2);
+        assert_eq!(batch.message_ids, ["1-0", "1-1", "1-2"]);
+
+        assert_eq!(batch.drop_trades(&HashSet::from(["a:1".to_string()])), 1);
+        assert_eq!(delta(&batch.deltas(T0), "1s", T0).trade_count, 1);
+    }
+
+    #[test]
+    fn restored_batch_goes_before_newer_trades() {
+        let mut rollup = PairRollup::new(PAIR);
+        rollup.apply_trade("1-0".into(), T0, trade("a:0", 1.0, 1.0, Type::Buy, 1), T0);
+        let batch = rollup.take_batch().expect("trades buffered");
+        assert!(rollup.take_batch().is_none());
+
+        rollup.apply_trade("1-1".into(), T0 + 1, trade("b:0", 2.0, 1.0, Type::Buy, 2), T0);
+        rollup.apply_trade("1-2".into(), T0, trade("a:0", 1.0, 1.0, Type::Buy, 1), T0);
+        rollup.restore(batch);
+
+        let batch = rollup.take_batch().expect("trades buffered");
+        assert_eq!(batch.message_ids, ["1-0", "1-1", "1-2"]);
+        assert_eq!(batch.trade_count(), 2);
+    }
+
+    #[test]
+    fn written_candles_fill_gaps_and_idle_pairs_go() {
+        let mut rollup = PairRollup::new(PAIR);
+        rollup.apply_trade("1-0".into(), T0, trade("a:0", 1.0, 1.0, Type::Buy, 1), T0);
+        let first = rollup.take_batch().expect("trades buffered").deltas(T0);
+        assert!(rollup.record_written(&first).is_empty());
+
+        rollup.apply_trade("1-1".into(), T0 + 180, trade("b:0", 2.0, 1.0, Type::Buy, 2), T0 + 180);
+        let second = rollup.take_batch().expect("trades buffered").deltas(T0 + 180);
+        let gaps = rollup.record_written(&second);
+        let minute_gaps: Vec<i64> = gaps.iter().filter(|candle| candle.timeframe == "1m").map(|candle| candle.timestamp).collect();
+        assert_eq!(minute_gaps, [T0 + 60, T0 + 120]);
+        assert!(gaps.iter().all(|candle| candle.close == 1.0 && candle.volume == 0.0));
+
+        assert!(!rollup.is_idle(T0 + 200, 300));
+        assert!(rollup.is_idle(T0 + 480, 300));
+        rollup.apply_trade("1-2".into(), T0 + 500, trade("c:0", 2.0, 1.0, Type::Buy, 3), T0 + 500);
+        assert!(!rollup.is_idle(T0 + 900, 300));
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+
+use chrono::Utc;
+use sea_orm::{
+    sea_query::OnConflict, ActiveValue::Set, ColumnTrait, Database, DatabaseConnection, EntityTrait, QueryFilter, QueryOrder,
+    QuerySelect,
+};
+
+use crate::{
+    entities::ohlcv_candles::{self, Column},
+    redis::metric_and_ohlcv_manager::MetricOHLCVManager,
+    types::ohlcv::{fill_gaps, CandleTimeFrame, OHLCVcandle},
+};
+
+const FLUSH_BATCH_SIZE: isize = 500;
+
+fn to_datetime(timestamp: i64) -> chrono::NaiveDateTime {
+    chrono::DateTime::from_timestamp(timestamp, 0).unwrap_or_default().naive_utc()
+}
+
+fn to_active_model(candle: &OHLCVcandle, now: chrono::NaiveDateTime) -> ohlcv_candles::ActiveModel {
+    ohlcv_candles::ActiveModel {
+        token_pair: Set(candle.token_pair.clone()),
+        timeframe: Set(candle.timeframe.clone()),
+        timestamp: Set(to_datetime(candle.timestamp)),
+        open_price: Set(candle.open),
+        high_price: Set(candle.high),
+        low_price: Set(candle.low),
+        close_price: Set(candle.close),
+        volume: Set(candle.volume),
+        buy_volume: Set(candle.buy_volume),
+        sell_volume: Set(candle.sell_volume),
+        trade_count: Set(candle.trade_count as i64),
+        quote_open_price: Set(candle.quote_open),
+        quote_high_price: Set(candle.quote_high),
+        quote_low_price: Set(candle.quote_low),
+        quote_close_price: Set(candle.quote_close),
+        quote_volume: Set(candle.quote_volume),
+        usd_volume: Set(candle.usd_volume),
+        revision: Set(candle.revision as i32),
+        created_at: Set(now),
+        updated_at: Set(now),
+    }
+}
+
+fn from_model(model: ohlcv_candles::Model) -> OHLCVcandle {
+    OHLCVcandle {
+        token_pair: model.token_pair,
+        timeframe: model.timeframe,
+        timestamp: model.timestamp.and_utc().timestamp(),
+        open: model.open_price,
+        high: model.high_price,
+        low: model.low_price,
+        close: model.close_price,
+        volume: model.volume,
+        buy_volume: model.buy_volume,
+        sell_volume: model.sell_volume,
+        trade_count: model.trade_count.clamp(0, u32::MAX as i64) as u32,
+        quote_open: model.quote_open_price,
+        quote_high: model.quote_high_price,
+        quote_low: model.quote_low_price,
+        quote_close: model.quote_close_price,
+        quote_volume: model.quote_volume,
+        usd_volume: model.usd_volume,
+        first_trade: Default::default(),
+        last_trade: Default::default(),
+        revision: model.revision.max(0) as u32,
+    }
+}
+
+/// Long term candle storage. Redis keeps the live buckets for `redis_retention_secs`, closed candles
+/// are upserted into Postgres and kept there for `postgres_retention_secs` (forever from 1h up).
+///
+/// Without DATABASE_URL the store only serves redis and drops closed candles.
+pub struct CandleStore {
+    ohlcv_manager: MetricOHLCVManager,
+    db: Option<DatabaseConnection>,
+}
+
+impl CandleStore {
+    pub async fn connect() -> Result<Self, anyhow::Error> {
+        let db = match std::env::var("DATABASE_URL") {
+            Ok(database_url) => {
+                println!("Connecting candle store to PostgreSQL...");
+                Some(Database::connect(&database_url).await?)
+            }
+            Err(_) => {
+                println!("DATABASE_URL not set, candles are only kept in redis");
+                None
+            }
+        };
+        Ok(Self {
+            ohlcv_manager: MetricOHLCVManager::new()?,
+            db,
+        })
+    }
+
+    /// Upserts every candle whose bucket has closed, returns how many were written.
+    ///
+    /// Taken candles stay in the processing set until the upsert went through, a batch that fails to write
+    /// goes back to the pending set and is retried on the next flush.
+    pub async fn flush_closed_candles(&self) -> Result<usize, anyhow::Error> {
+        let mut flushed = 0;
+        loop {
+            let candles = self.ohlcv_manager.take_closed_candles(Utc::now().timestamp(), FLUSH_BATCH_SIZE).await?;
+            if candles.is_empty() {
+                return Ok(flushed);
+            }
+            // without a database the pending set is still drained so it does not grow
+            let Some(db) = &self.db else {
+                self.ohlcv_manager.ack_closed_candles(&candles).await?;
+                continue;
+            };
+
+            let now = Utc::now().naive_utc();
+            let result = ohlcv_candles::Entity::insert_many(candles.iter().map(|candle| to_active_model(candle, now)))
+                .on_conflict(
+                    OnConflict::columns([Column::TokenPair, Column::Timeframe, Column::Timestamp])
+                        .update_columns([
+                            Column::OpenPrice,
+                            Column::HighPrice,
+                            Column::LowPrice,
+                            Column::ClosePrice,
+                            Column::Volume,
+                            Column::BuyVolume,
+                            Column::SellVolume,
+                            Column::TradeCount,
+                            Column::QuoteOpenPrice,
+                            Column::QuoteHighPrice,
+                            Column::QuoteLowPrice,
+                            Column::QuoteClosePrice,
+                            Column::QuoteVolume,
+                            Column::UsdVolume,
+                            Column::Revision,
+                            Column::UpdatedAt,
+                        ])
+                        .to_owned(),
+                )
+                .exec(db)
+                .await;
+            if let Err(e) = result {
+                self.ohlcv_manager.requeue_closed_candles(&candles).await?;
+                return Err(e.into());
+            }
+            self.ohlcv_manager.ack_closed_candles(&candles).await?;
+            flushed += candles.len();
+        }
+    }
+
+    // deletes rows past the postgres retention of their timeframe
+    pub async fn prune(&self) -> Result<u64, anyhow::Error> {
+        let Some(db) = &self.db else { return Ok(0) };
+        let now = Utc::now().timestamp();
+        let mut deleted = 0;
+        for timeframe in CandleTimeFrame::all() {
+            let Some(retention) = timeframe.postgres_retention_secs() else { continue };
+            let result = ohlcv_candles::Entity::delete_many()
+                .filter(Column::Timeframe.eq(timeframe.to_string()))
+                .filter(Column::Timestamp.lt(to_datetime(now - retention)))
+                .exec(db)
+                .await?;
+            deleted += result.rows_affected;
+        }
+        Ok(deleted)
+    }
+
+    /// Candles of `[from, to]`, read from redis and from Postgres for the part older than the redis window.
+    ///
+    /// Redis wins where both have a bucket since its candle may include trades not flushed yet.
+    pub async fn get_candles(
+        &self,
+        token_pair: &str,
+        timeframe: CandleTimeFrame,
+        from: i64,
+        to: i64,
+        gap_fill: bool,
+        max_candles: usize,
+    ) -> Result<Vec<OHLCVcandle>, anyhow::Error> {
+        let label = timeframe.to_string();
+        let redis_from = Utc::now().timestamp() - timeframe.redis_retention_secs();
+        let mut merged = BTreeMap::new();
+
+        if let Some(db) = self.db.as_ref().filter(|_| timeframe.is_persisted() && from < redis_from) {
+            let rows = ohlcv_candles::Entity::find()
+                .filter(Column::TokenPair.eq(token_pair))
+                .filter(Column::Timeframe.eq(label.as_str()))
+                .filter(Column::Timestamp.between(to_datetime(from), to_datetime(to.min(redis_from))))
+                .order_by_desc(Column::Timestamp)
+                .limit(max_candles as u64)
+                .all(db)
+                .await?;
+            merged.extend(rows.into_iter().map(from_model).map(|candle| (candle.timestamp, candle)));
+        }
+        let live = self.ohlcv_manager.get_candles(token_pair, timeframe, from.max(redis_from), to, false, max_candles).await?;
+        merged.extend(live.into_iter().map(|candle| (candle.timestamp, candle)));
+
+        let mut candles: Vec<OHLCVcandle> = merged.into_values().collect();
+        if !gap_fill {
+            if candles.len() > max_candles {
+                candles.drain(..candles.len() - max_candles);
+            }
+            return Ok(candles);
+        }
+        let previous = self.get_last_candle_before(token_pair, timeframe, from).await?;
+        Ok(fill_gaps(candles, timeframe, token_pair, from, to, previous, max_candles))
+    }
+
+    // the latest candle strictly before `timestamp`, from redis or else from postgres
+    pub async fn get_last_candle_before(&self, token_pair: &str, timeframe: CandleTimeFrame, timestamp: i64) -> Result<Option<OHLCVcandle>, anyhow::Error> {
+        let label = timeframe.to_string();
+        match self.ohlcv_manager.get_last_candle_before(token_pair, &label, timestamp).await? {
+            Some(candle) => Ok(Some(candle)),
+            None => self.get_last_stored_before(token_pair, &label, timestamp).await,
+        }
+    }
+
+    async fn get_last_stored_before(&self, token_pair: &str, timeframe: &str, timestamp: i64) -> Result<Option<OHLCVcandle>, anyhow::Error> {
+        let Some(db) = &self.db else { return Ok(None) };
+        let row = ohlcv_candles::Entity::find()
+            .filter(Column::TokenPair.eq(token_pair))
+            .filter(Column::Timeframe.eq(timeframe))
+            .filter(Column::Timestamp.lt(to_datetime(timestamp)))
+            .order_by_desc(Column::Timestamp)
+            .one(db)
+            .await?;
+        Ok(row.map(from_model))
+    }
+}
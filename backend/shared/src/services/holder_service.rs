@@ -0,0 +1,166 @@
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+    sync::Arc,
+};
+
+use chrono::Utc;
+use solana_client::{
+    nonblocking::rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig, UiAccountEncoding, UiDataSliceConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_program::pubkey::Pubkey;
+use tokio::sync::{Mutex, Semaphore};
+
+use crate::{
+    redis::{holder_manager::HolderManager, pool_manager::PoolManager},
+    services::{
+        liquidity_service::{TOKEN_2022_PROGRAM, TOKEN_PROGRAM},
+        outbound::{host_of, OUTBOUND},
+    },
+    types::grpc::CustomTokenBalance,
+};
+
+const INCINERATOR: &str = "1nc1nerator11111111111111111111111111111111";
+const SPL_TOKEN_ACCOUNT_SIZE: u64 = 165;
+const SNAPSHOT_MAX_AGE_SECS: i64 = 6 * 3600;
+const MAX_CONCURRENT_SNAPSHOTS: usize = 2;
+
+// keeps the holder index of every traded mint, seeded by a token account scan and then moved by swap balances
+#[derive(Clone)]
+pub struct HolderService {
+    holder_manager: HolderManager,
+    pool_manager: PoolManager,
+    rpc_client: Arc<RpcClient>,
+    rpc_host: String,
+    snapshot_permits: Arc<Semaphore>,
+    snapshots_in_flight: Arc<Mutex<HashSet<String>>>,
+}
+
+impl std::fmt::Debug for HolderService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HolderService")
+            .field("holder_manager", &self.holder_manager)
+            .field("pool_manager", &self.pool_manager)
+            .field("rpc_client", &"RpcClient")
+            .finish()
+    }
+}
+
+impl HolderService {
+    pub fn new(pool_manager: PoolManager) -> Self {
+        let rpc_url = std::env::var("HELIUS_URL").expect("Helius url not present in env");
+        Self {
+            holder_manager: HolderManager::new().expect("Error creating holder manager"),
+            pool_manager,
+            rpc_host: host_of(&rpc_url),
+            rpc_client: Arc::new(RpcClient::new(rpc_url)),
+            snapshot_permits: Arc::new(Semaphore::new(MAX_CONCURRENT_SNAPSHOTS)),
+            snapshots_in_flight: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    // post balances only list the accounts a swap touched, so an owner with several accounts for the mint is
+    // tracked by the touched ones until the next snapshot
+    pub async fn apply_post_balances(&self, mint_address: &str, post_balances: &[CustomTokenBalance]) -> Result<(), anyhow::Error> {
+        let mut balances: HashMap<String, f64> = HashMap::new();
+        let mut decimals = None;
+        for balance in post_balances.iter().filter(|balance| balance.mint == mint_address && !balance.owner.is_empty()) {
+            let amount = balance.ui_token_amount.as_ref();
+            decimals = decimals.or(amount.map(|amount| amount.decimals));
+            *balances.entry(balance.owner.clone()).or_default() += amount.map(|amount| amount.ui_amount).unwrap_or(0.0);
+        }
+        let balances = self.without_excluded_owners(balances.into_iter().collect()).await?;
+        self.holder_manager.apply_balances(mint_address, &balances).await?;
+
+        if let Some(decimals) = decimals {
+            self.ensure_snapshot(mint_address, decimals).await?;
+        }
+        Ok(())
+    }
+
+    async fn ensure_snapshot(&self, mint_address: &str, decimals: u32) -> Result<(), anyhow::Error> {
+        let snapshot_at = self.holder_manager.get_snapshot_at(mint_address).await?;
+        if snapshot_at.is_some_and(|snapshot_at| Utc::now().timestamp() - snapshot_at < SNAPSHOT_MAX_AGE_SECS) {
+            return Ok(());
+        }
+        if !self.snapshots_in_flight.lock().await.insert(mint_address.to_string()) {
+            return Ok(());
+        }
+
+        let service = self.clone();
+        let mint_address = mint_address.to_string();
+        tokio::spawn(async move {
+            let _permit = service.snapshot_permits.acquire().await;
+            if let Err(e) = service.snapshot_holders(&mint_address, decimals).await {
+                println!("Error taking holder snapshot for {} : {}", mint_address, e);
+            }
+            service.snapshots_in_flight.lock().await.remove(&mint_address);
+        });
+        Ok(())
+    }
+
+    // scans every token account of the mint, only the owner and amount are requested (offset 32, 40 bytes)
+    async fn snapshot_holders(&self, mint_address: &str, decimals: u32) -> Result<(), anyhow::Error> {
+        let mint = Pubkey::from_str(mint_address)?;
+        let mut raw_balances: HashMap<Pubkey, u64> = HashMap::new();
+
+        for program in [TOKEN_PROGRAM, TOKEN_2022_PROGRAM] {
+            let mut filters = vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, &mint.to_bytes()))];
+            // token-2022 accounts carry extensions, so their size varies
+            if program == TOKEN_PROGRAM {
+                filters.push(RpcFilterType::DataSize(SPL_TOKEN_ACCOUNT_SIZE));
+            }
+            let config = RpcProgramAccountsConfig {
+                filters: Some(filters),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    data_slice: Some(UiDataSliceConfig { offset: 32, length: 40 }),
+                    ..RpcAccountInfoConfig::default()
+                },
+                ..RpcProgramAccountsConfig::default()
+            };
+            let program_id = Pubkey::from_str(program)?;
+            let accounts = OUTBOUND
+                .call(&self.rpc_host, || self.rpc_client.get_program_ui_accounts_with_config(&program_id, config.clone()))
+                .await?;
+
+            for (_, account) in accounts {
+                let Some(data) = account.data.decode() else { continue };
+                let owner = data.get(0..32).and_then(|bytes| Pubkey::try_from(bytes).ok());
+                let amount = data.get(32..40).and_then(|bytes| bytes.try_into().ok()).map(u64::from_le_bytes);
+                if let (Some(owner), Some(amount)) = (owner, amount)
+                    && amount > 0 {
+                    *raw_balances.entry(owner).or_default() += amount;
+                }
+            }
+        }
+
+        let scale = 10f64.powi(decimals as i32);
+        let balances = raw_balances
+            .into_iter()
+            .map(|(owner, amount)| (owner.to_string(), amount as f64 / scale))
+            .collect();
+        let balances = self.without_excluded_owners(balances).await?;
+        self.holder_manager.replace_holders(mint_address, &balances).await?;
+        Ok(())
+    }
+
+    // pool vaults and burned tokens are not holders
+    async fn without_excluded_owners(&self, balances: Vec<(String, f64)>) -> Result<Vec<(String, f64)>, anyhow::Error> {
+        let mut holders = Vec::with_capacity(balances.len());
+        for chunk in balances.chunks(1000) {
+            let owners: Vec<String> = chunk.iter().map(|(owner, _)| owner.clone()).collect();
+            let pool_owners = self.pool_manager.are_pool_owners(&owners).await?;
+            holders.extend(
+                chunk
+                    .iter()
+                    .zip(pool_owners)
+                    .filter(|((owner, _), is_pool_owner)| !is_pool_owner && owner != INCINERATOR)
+                    .map(|(balance, _)| balance.clone()),
+            );
+        }
+        Ok(holders)
+    }
+}
@@ -0,0 +1,173 @@
+use chrono::Utc;
+
+use crate::{
+    redis::{market_event_manager::MarketEventManager, metric_and_ohlcv_manager::MetricOHLCVManager, pool_manager::PoolManager, pubsub_manager::PubSubManager, token_symbol_manager::TokenSymbolManager},
+    services::{anomaly_service::{detect_liquidity_pull, AnomalyConfig}, pool_state::{decode_pool_state, StateReserves}},
+    types::{market_event::MarketEvent, pool::{LiquidityUpdate, PoolInfo, PoolReserves}},
+};
+
+pub const TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+pub const TOKEN_2022_PROGRAM: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb";
+
+// reserves are the spl token vault balances of a pool, less the fees its state account says they hold.
+// concentrated liquidity pools also get the liquidity active at their current price from the state
+#[derive(Debug)]
+pub struct LiquidityService {
+    pool_manager: PoolManager,
+    token_manager: TokenSymbolManager,
+    metric_manager: MetricOHLCVManager,
+    pubsub_manager: PubSubManager,
+    event_manager: MarketEventManager,
+    anomaly_config: AnomalyConfig,
+}
+
+impl LiquidityService {
+    pub fn new(pool_manager: PoolManager, token_manager: TokenSymbolManager, pubsub_manager: PubSubManager) -> Self {
+        let metric_manager = MetricOHLCVManager::new().expect("Error creating metric manager for liquidity");
+        let event_manager = MarketEventManager::new(pubsub_manager.clone()).expect("Error creating market event manager for liquidity");
+        Self {
+            pool_manager,
+            token_manager,
+            metric_manager,
+            pubsub_manager,
+            event_manager,
+            anomaly_config: AnomalyConfig::from_env(),
+        }
+    }
+
+    // seeds reserves from the post balances of a swap so liquidity is known before the vault subscription catches up
+    pub async fn record_swap_reserves(
+        &self,
+        pool: &PoolInfo,
+        token_vault_amount: f64,
+        sol_vault_amount: f64,
+        token_price_usd: Option<f64>,
+    ) -> Result<(), anyhow::Error> {
+        self.pool_manager.register_pool(pool).await?;
+        self.update_reserves(pool, Some(token_vault_amount), Some(sol_vault_amount), None, token_price_usd).await
+    }
+
+    // handles an account update for a subscribed vault
+    pub async fn handle_vault_account(&self, vault: &str, data: &[u8]) -> Result<(), anyhow::Error> {
+        let amount = decode_token_account_amount(data)?;
+        let pool = match self.pool_manager.get_pool_by_vault(vault).await? {
+            Some(pool) => pool,
+            None => {
+                println!("Received update for unknown vault : {}", vault);
+                return Ok(());
+            }
+        };
+        if vault == pool.token_vault {
+            let token_vault_amount = amount as f64 / 10f64.powi(pool.token_decimals as i32);
+            self.update_reserves(&pool, Some(token_vault_amount), None, None, None).await
+        } else {
+            let sol_vault_amount = amount as f64 / 10f64.powi(pool.sol_decimals as i32);
+            self.update_reserves(&pool, None, Some(sol_vault_amount), None, None).await
+        }
+    }
+
+    // handles an account update for a subscribed pool state, `program_id` is the account owner
+    pub async fn handle_pool_state(&self, pool_id: &str, program_id: &str, data: &[u8]) -> Result<(), anyhow::Error> {
+        let pool = match self.pool_manager.get_pool(pool_id).await? {
+            Some(pool) => pool,
+            None => {
+                println!("Received update for unknown pool state : {}", pool_id);
+                return Ok(());
+            }
+        };
+        let state = decode_pool_state(program_id, data)?;
+        match state.reserves_for(&pool) {
+            Some(state_reserves) => self.update_reserves(&pool, None, None, Some(state_reserves), None).await,
+            None => {
+                println!("Vaults of pool state {} do not match the pool's vaults", pool_id);
+                Ok(())
+            }
+        }
+    }
+
+    // a vault balance or state left `None` keeps the last one stored for the pool
+    async fn update_reserves(
+        &self,
+        pool: &PoolInfo,
+        token_vault_amount: Option<f64>,
+        sol_vault_amount: Option<f64>,
+        state_reserves: Option<StateReserves>,
+        token_price_usd: Option<f64>,
+    ) -> Result<(), anyhow::Error> {
+        let sol_price = match self.token_manager.get_sol_value().await {
+            Some(sol_info) => sol_info.sol_price,
+            None => {
+                println!("Got no value for SOL from redis, skipping liquidity for {}", pool.pool_id);
+                return Ok(());
+            }
+        };
+        let token_price_usd = match token_price_usd {
+            Some(price) => Some(price),
+            None => self.metric_manager.get_current_price(&pool.token_pair).await.ok(),
+        };
+        let current = self.pool_manager.get_reserves(&pool.pool_id).await?.unwrap_or_default();
+        let state_reserves = state_reserves.unwrap_or(StateReserves {
+            token_fees: current.token_fees,
+            sol_fees: current.sol_fees,
+            active_sol_reserve: current.active_sol_reserve,
+        });
+        let token_vault_amount = token_vault_amount.unwrap_or(current.token_reserve + current.token_fees);
+        let sol_vault_amount = sol_vault_amount.unwrap_or(current.sol_reserve + current.sol_fees);
+        let token_reserve = (token_vault_amount - state_reserves.token_fees).max(0.0);
+        let sol_reserve = (sol_vault_amount - state_reserves.sol_fees).max(0.0);
+
+        let sol_side_usd = sol_reserve * sol_price;
+        // without a traded price the token side is assumed to match the sol side, as in a balanced constant product pool
+        let token_side_usd = match token_price_usd {
+            Some(price) if price > 0.0 => token_reserve * price,
+            _ => sol_side_usd,
+        };
+
+        let reserves = PoolReserves {
+            token_reserve,
+            sol_reserve,
+            token_fees: state_reserves.token_fees,
+            sol_fees: state_reserves.sol_fees,
+            active_sol_reserve: state_reserves.active_sol_reserve,
+            liquidity_usd: sol_side_usd + token_side_usd,
+            // both sides of the virtual reserves are worth the same at the current price
+            active_liquidity_usd: state_reserves.active_sol_reserve.map(|active_sol_reserve| 2.0 * active_sol_reserve * sol_price),
+            updated_at: Utc::now().timestamp(),
+        };
+        let previous_liquidity_usd = self.pool_manager.get_pair_liquidity(&pool.token_pair).await?;
+        let pair_liquidity_usd = self.pool_manager.save_reserves(pool, &reserves).await?;
+        if let Some(event) = previous_liquidity_usd
+            .and_then(|previous_usd| detect_liquidity_pull(&self.anomaly_config, previous_usd, pair_liquidity_usd))
+        {
+            let market_event = MarketEvent {
+                token_pair: pool.token_pair.clone(),
+                event,
+                price: token_price_usd,
+                detected_at: reserves.updated_at,
+            };
+            self.event_manager.emit(&market_event, self.anomaly_config.cooldown_secs).await?;
+        }
+
+        let liquidity_update = LiquidityUpdate {
+            token_pair: pool.token_pair.clone(),
+            pool_id: pool.pool_id.clone(),
+            dex_tag: pool.dex_tag.clone(),
+            token_reserve,
+            sol_reserve,
+            liquidity_usd: reserves.liquidity_usd,
+            active_liquidity_usd: reserves.active_liquidity_usd,
+            pair_liquidity_usd,
+            updated_at: reserves.updated_at,
+        };
+        self.pubsub_manager.publish_liquidity_update(liquidity_update).await?;
+        Ok(())
+    }
+}
+
+// spl token account layout : mint (32) + owner (32) + amount (u64), token-2022 accounts share the prefix
+pub fn decode_token_account_amount(data: &[u8]) -> Result<u64, anyhow::Error> {
+    data.get(64..72)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u64::from_le_bytes)
+        .ok_or_else(|| anyhow::anyhow!("token account data too short"))
+}
@@ -0,0 +1,329 @@
+use std::{collections::HashMap, str::FromStr, sync::Arc, time::Duration};
+
+use anyhow::Context;
+use mpl_token_metadata::{accounts::Metadata, programs::MPL_TOKEN_METADATA_ID};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+use serde_json::Value;
+use tokio::sync::{mpsc, oneshot, Mutex};
+
+use crate::{
+    redis::{pubsub_manager::PubSubManager, token_symbol_manager::TokenSymbolManager},
+    services::outbound::{host_of, OUTBOUND},
+    types::price::{MetadataSection, MintAccountInfo, TokenInfo, TokenMetadata},
+};
+
+const MAX_BATCH_SIZE: usize = 50; // getMultipleAccounts accepts at most 100 keys, two per mint
+const BATCH_WINDOW: Duration = Duration::from_millis(50);
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+const TRACKED_MINT_WINDOW: i64 = 86400; // only mints that traded in the last day are refreshed
+
+type Waiters = Arc<Mutex<HashMap<String, Vec<oneshot::Sender<Option<TokenInfo>>>>>>;
+
+// resolves mint metadata in the background. concurrent lookups of the same mint share one
+// in-flight request and misses are batched into getMultipleAccounts calls.
+#[derive(Debug, Clone)]
+pub struct MetadataResolver {
+    request_tx: mpsc::UnboundedSender<String>,
+    in_flight: Waiters,
+}
+
+impl MetadataResolver {
+    pub fn new(token_manager: TokenSymbolManager, pubsub_manager: PubSubManager) -> Self {
+        let rpc_url = std::env::var("HELIUS_URL").expect("Helius url not present in env");
+        let rpc_host = host_of(&rpc_url);
+        let rpc_client = RpcClient::new(rpc_url);
+        let (request_tx, request_rx) = mpsc::unbounded_channel();
+        let in_flight: Waiters = Arc::new(Mutex::new(HashMap::new()));
+
+        let batch_in_flight = Arc::clone(&in_flight);
+        tokio::spawn(async move {
+            Self::batch_loop(rpc_client, rpc_host, token_manager, pubsub_manager, request_rx, batch_in_flight).await;
+        });
+
+        Self { request_tx, in_flight }
+    }
+
+    // queues the mint for resolution without waiting for the result
+    pub async fn request(&self, mint_address: &str) {
+        self.enqueue(mint_address, None).await;
+    }
+
+    // queues the mint and waits until its batch has been resolved
+    pub async fn resolve(&self, mint_address: &str) -> Option<TokenInfo> {
+        let (tx, rx) = oneshot::channel();
+        self.enqueue(mint_address, Some(tx)).await;
+        rx.await.ok().flatten()
+    }
+
+    async fn enqueue(&self, mint_address: &str, waiter: Option<oneshot::Sender<Option<TokenInfo>>>) {
+        let mut in_flight = self.in_flight.lock().await;
+        if let Some(waiters) = in_flight.get_mut(mint_address) {
+            println!("Metadata lookup already in flight for mint : {}", mint_address);
+            waiters.extend(waiter);
+            return;
+        }
+        in_flight.insert(mint_address.to_string(), waiter.into_iter().collect());
+        drop(in_flight);
+
+        if self.request_tx.send(mint_address.to_string()).is_err() {
+            println!("Metadata resolver stopped, dropping lookup for mint : {}", mint_address);
+            self.in_flight.lock().await.remove(mint_address);
+        }
+    }
+
+    // periodically re-queues tracked mints whose metadata sections have outlived their ttl
+    pub async fn start_refresh_job(&self, token_manager: TokenSymbolManager) {
+        let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            println!("⏰ Running token metadata refresh...");
+            let active_since = chrono::Utc::now().timestamp() - TRACKED_MINT_WINDOW;
+            let mints = match token_manager.get_tracked_mints(active_since).await {
+                Ok(mints) => mints,
+                Err(e) => {
+                    println!("Error getting tracked mints from redis : {}", e);
+                    continue;
+                }
+            };
+            let mut queued = 0;
+            for mint_address in mints {
+                if token_manager.is_metadata_missing(&mint_address).await {
+                    continue;
+                }
+                let needs_refresh = match token_manager.get_token_metadata(&mint_address).await {
+                    Some(metadata) => {
+                        metadata.is_stale(MetadataSection::OnChain)
+                            || metadata.is_stale(MetadataSection::MintAccount)
+                            || (metadata.uri.is_some() && metadata.is_stale(MetadataSection::OffChain))
+                    }
+                    None => true,
+                };
+                if needs_refresh {
+                    self.request(&mint_address).await;
+                    queued += 1;
+                }
+            }
+            println!("Queued {} mint(s) for metadata refresh", queued);
+        }
+    }
+
+    async fn batch_loop(
+        rpc_client: RpcClient,
+        rpc_host: String,
+        token_manager: TokenSymbolManager,
+        pubsub_manager: PubSubManager,
+        mut request_rx: mpsc::UnboundedReceiver<String>,
+        in_flight: Waiters,
+    ) {
+        println!("Metadata resolver started...");
+        let http_client = reqwest::Client::new();
+
+        while let Some(first_mint) = request_rx.recv().await {
+            let mut batch = vec![first_mint];
+            let deadline = tokio::time::Instant::now() + BATCH_WINDOW;
+            while batch.len() < MAX_BATCH_SIZE {
+                match tokio::time::timeout_at(deadline, request_rx.recv()).await {
+                    Ok(Some(mint)) => batch.push(mint),
+                    Ok(None) | Err(_) => break,
+                }
+            }
+
+            println!("Resolving metadata for {} mint(s)", batch.len());
+            let mut resolved = match Self::fetch_batch(&rpc_client, &rpc_host, &batch).await {
+                Ok(resolved) => resolved,
+                Err(e) => {
+                    println!("❌ RPC Error fetching metadata batch: {}", e);
+                    // leave the mints uncached so they are retried on a later trade
+                    for mint_address in batch {
+                        for waiter in in_flight.lock().await.remove(&mint_address).unwrap_or_default() {
+                            let _ = waiter.send(None);
+                        }
+                    }
+                    continue;
+                }
+            };
+
+            for mint_address in batch {
+                let ResolvedMint { onchain, mint_account } = resolved.remove(&mint_address).unwrap_or_default();
+                if let Some(mint_account) = &mint_account
+                    && let Err(e) = token_manager.save_mint_account(&mint_address, mint_account).await {
+                    println!("Error saving mint account to redis cache : {}", e);
+                }
+
+                let token_info = match onchain {
+                    Some(OnChainMetadata { token_info, uri, update_authority }) => {
+                        let previous = token_manager.get_token_metadata(&mint_address).await;
+                        if let Err(e) = token_manager
+                            .save_onchain_metadata(&mint_address, token_info.token_symbol.clone(), token_info.token_name.clone(), uri.clone(), update_authority)
+                            .await
+                        {
+                            println!("Error saving mint info to redis cache : {}", e);
+                        }
+                        if let Err(e) = pubsub_manager.publish_token_metadata(mint_address.clone(), token_info.clone()).await {
+                            println!("Error publishing metadata for mint {} : {}", mint_address, e);
+                        }
+
+                        let offchain_stale = match &previous {
+                            Some(previous) => previous.uri.as_deref() != Some(uri.as_str()) || previous.is_stale(MetadataSection::OffChain),
+                            None => true,
+                        };
+                        if !uri.is_empty() && offchain_stale {
+                            let http_client = http_client.clone();
+                            let token_manager = token_manager.clone();
+                            let mint_address = mint_address.clone();
+                            tokio::spawn(async move {
+                                match fetch_offchain_metadata(&http_client, &uri).await {
+                                    Ok(offchain) => {
+                                        if let Err(e) = token_manager.save_offchain_metadata(&mint_address, &offchain).await {
+                                            println!("Error saving off-chain metadata for {} : {}", mint_address, e);
+                                        }
+                                    }
+                                    Err(e) => println!("Error fetching off-chain metadata for {} : {}", mint_address, e),
+                                }
+                            });
+                        }
+                        Some(token_info)
+                    }
+                    None => {
+                        println!("No metadata found on-chain for mint: {}", mint_address);
+                        if let Err(e) = token_manager.mark_metadata_missing(&mint_address).await {
+                            println!("Error negatively caching mint {} : {}", mint_address, e);
+                        }
+                        None
+                    }
+                };
+
+                let waiters = in_flight.lock().await.remove(&mint_address).unwrap_or_default();
+                for waiter in waiters {
+                    let _ = waiter.send(token_info.clone());
+                }
+            }
+        }
+        println!("Metadata resolver loop ended");
+    }
+
+    // every mint costs two keys in the batch, its metadata pda and the mint account itself
+    async fn fetch_batch(rpc_client: &RpcClient, rpc_host: &str, mints: &[String]) -> Result<HashMap<String, ResolvedMint>, anyhow::Error> {
+        let mut keys = Vec::with_capacity(mints.len() * 2);
+        let mut key_mints = Vec::with_capacity(mints.len());
+        for mint_address in mints {
+            match (get_metadata_pda_address(mint_address), Pubkey::from_str(mint_address)) {
+                (Ok(pda), Ok(mint_pubkey)) => {
+                    keys.push(pda);
+                    keys.push(mint_pubkey);
+                    key_mints.push(mint_address.clone());
+                }
+                _ => println!("Skipping invalid mint {}", mint_address),
+            }
+        }
+
+        let accounts = OUTBOUND.call(rpc_host, || rpc_client.get_multiple_accounts(&keys)).await?;
+        let mpl_program_id = Pubkey::new_from_array(MPL_TOKEN_METADATA_ID.to_bytes());
+        let mut resolved = HashMap::new();
+
+        for (mint_address, pair) in key_mints.into_iter().zip(accounts.chunks(2)) {
+            let mut entry = ResolvedMint::default();
+            if let Some(Some(account)) = pair.get(1) {
+                entry.mint_account = decode_mint_account(&account.data);
+            }
+            match pair.first() {
+                Some(Some(account)) if account.owner == mpl_program_id => {
+                    match Metadata::safe_deserialize(&account.data) {
+                        Ok(metadata) => {
+                            entry.onchain = Some(OnChainMetadata {
+                                token_info: TokenInfo {
+                                    token_symbol: clean_metadata_string(&metadata.symbol),
+                                    token_name: clean_metadata_string(&metadata.name),
+                                },
+                                uri: clean_metadata_string(&metadata.uri),
+                                update_authority: metadata.update_authority.to_string(),
+                            });
+                        }
+                        Err(_) => println!("no metadata found for the mint : {}", mint_address),
+                    }
+                }
+                Some(Some(_)) => println!("Metadata account for {} not owned by metaplex program", mint_address),
+                _ => {}
+            }
+            resolved.insert(mint_address, entry);
+        }
+        Ok(resolved)
+    }
+}
+
+#[derive(Debug)]
+struct OnChainMetadata {
+    token_info: TokenInfo,
+    uri: String, // off-chain json uri
+    update_authority: String,
+}
+
+#[derive(Debug, Default)]
+struct ResolvedMint {
+    onchain: Option<OnChainMetadata>,
+    mint_account: Option<MintAccountInfo>,
+}
+
+// metaplex pads name, symbol and uri with null bytes
+fn clean_metadata_string(value: &str) -> String {
+    value.trim_end_matches('\0').trim().to_string()
+}
+
+/// Decodes the base spl-token mint layout, which token-2022 mints share before their extensions.
+pub fn decode_mint_account(data: &[u8]) -> Option<MintAccountInfo> {
+    if data.len() < 82 {
+        return None;
+    }
+    let read_authority = |offset: usize| -> Option<String> {
+        let tag = u32::from_le_bytes(data[offset..offset + 4].try_into().ok()?);
+        if tag == 1 {
+            let key: [u8; 32] = data[offset + 4..offset + 36].try_into().ok()?;
+            Some(Pubkey::new_from_array(key).to_string())
+        } else {
+            None
+        }
+    };
+    Some(MintAccountInfo {
+        mint_authority: read_authority(0),
+        supply: u64::from_le_bytes(data[36..44].try_into().ok()?),
+        decimals: data[44],
+        freeze_authority: read_authority(46),
+    })
+}
+
+async fn fetch_offchain_metadata(http_client: &reqwest::Client, uri: &str) -> Result<TokenMetadata, anyhow::Error> {
+    let url = match uri.strip_prefix("ipfs://") {
+        Some(cid) => format!("https://ipfs.io/ipfs/{}", cid),
+        None => uri.to_string(),
+    };
+    let json: Value = OUTBOUND
+        .call(&host_of(&url), || async {
+            http_client.get(&url).send().await?.error_for_status()?.json().await
+        })
+        .await?;
+    let text = |value: &Value| value.as_str().map(|value| value.trim().to_string()).filter(|value| !value.is_empty());
+    let extensions = &json["extensions"];
+
+    Ok(TokenMetadata {
+        image: text(&json["image"]),
+        description: text(&json["description"]),
+        website: text(&extensions["website"]).or_else(|| text(&json["website"])).or_else(|| text(&json["external_url"])),
+        twitter: text(&extensions["twitter"]).or_else(|| text(&json["twitter"])),
+        telegram: text(&extensions["telegram"]).or_else(|| text(&json["telegram"])),
+        discord: text(&extensions["discord"]).or_else(|| text(&json["discord"])),
+        ..Default::default()
+    })
+}
+
+pub fn get_metadata_pda_address(mint_address: &str) -> Result<Pubkey, anyhow::Error> {
+    let mint_pubkey = Pubkey::from_str(mint_address).context("Error occured while parsing pubkey")?;
+    let mpl_program_id = Pubkey::new_from_array(MPL_TOKEN_METADATA_ID.to_bytes());
+    let meta_seeds = &[
+        b"metadata",
+        MPL_TOKEN_METADATA_ID.as_ref(),
+        mint_pubkey.as_ref(),
+    ];
+    let (metadata_pda, _) = Pubkey::find_program_address(meta_seeds, &mpl_program_id);
+    Ok(metadata_pda)
+}
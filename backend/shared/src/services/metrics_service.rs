@@ -1,9 +1,13 @@
-use std::{collections::HashSet, sync::Arc, time::Duration};
-use redis::{AsyncCommands, Client, RedisResult};
+use std::{sync::Arc, time::Duration};
+use chrono::Utc;
+use redis::{Client, RedisResult};
 use serde::{Deserialize, Serialize};
-use tokio::time::interval;
+use tokio::time::{interval, MissedTickBehavior};
 
-use crate::redis::{metric_and_ohlcv_manager::{MetricOHLCVManager, PeriodStats, TimeFrame}, pubsub_manager::PubSubManager};
+use crate::{redis::{market_registry_manager::MarketRegistryManager, metric_and_ohlcv_manager::{MetricOHLCVManager, PeriodStats, TimeFrame}, pubsub_manager::PubSubManager}, services::ranking_service::{RankingService, WindowSnapshot}};
+
+const MARKET_INACTIVE_AFTER_SECS: i64 = 3 * 86400;
+const JANITOR_INTERVAL: Duration = Duration::from_secs(600);
 
 #[derive(Debug , Serialize, Deserialize, Clone)]
 pub struct PeriodStatsUpdate{
@@ -13,17 +17,65 @@ pub struct PeriodStatsUpdate{
     pub period_stats : Option<PeriodStats>
 }
 
+// a period window the scheduler republishes every `refresh_every`
+#[derive(Debug, Clone)]
+pub struct MetricsWindow {
+    pub timeframe : TimeFrame,
+    pub refresh_every : Duration
+}
+
+impl MetricsWindow {
+    // METRICS_WINDOWS="1m,5m,15m=60,1h,4h,6h,24h,7d", a `=secs` suffix overrides the refresh interval
+    pub fn from_env() -> Vec<MetricsWindow> {
+        let windows = match std::env::var("METRICS_WINDOWS") {
+            Ok(config) => config
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .filter_map(|entry| {
+                    let (label, refresh) = match entry.split_once('=') {
+                        Some((label, refresh)) => (label.trim(), Some(refresh.trim())),
+                        None => (entry, None),
+                    };
+                    let Some(timeframe) = TimeFrame::from_label(label) else {
+                        println!("⚠️ Ignoring unknown metrics window : {}", label);
+                        return None;
+                    };
+                    let refresh_secs = refresh
+                        .and_then(|secs| secs.parse::<u64>().ok())
+                        .filter(|secs| *secs > 0)
+                        .unwrap_or_else(|| timeframe.default_refresh_seconds());
+                    Some(MetricsWindow { timeframe, refresh_every : Duration::from_secs(refresh_secs) })
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        if windows.is_empty() {
+            return TimeFrame::all()
+                .into_iter()
+                .map(|timeframe| MetricsWindow { timeframe, refresh_every : Duration::from_secs(timeframe.default_refresh_seconds()) })
+                .collect();
+        }
+        windows
+    }
+}
+
 #[derive(Debug)]
 pub struct MetricsService {
     pub metrics_manager: MetricOHLCVManager,
     pub redis_client: Client,
-    pub pubsub_manager : PubSubManager
+    pub pubsub_manager : PubSubManager,
+    pub market_registry : MarketRegistryManager,
+    pub ranking_service : RankingService,
+    pub windows : Vec<MetricsWindow>
 }
 
 impl MetricsService {
     pub fn new() -> Result<Self, anyhow::Error> {
         let metrics_manager = MetricOHLCVManager::new().expect("Error creating metric manager");
         let pubsub_manager = PubSubManager::new().expect("Error creating pubsub manager");
+        let market_registry = MarketRegistryManager::new().expect("Error creating market registry");
+        let ranking_service = RankingService::new(market_registry.clone(), pubsub_manager.clone());
 
         let redis_client = Client::open(std::env::var("REDIS_URL").expect("unable to fetch redis url from env")).map_err(|e| {
             println!("Couldn't initialize a redis client : {}", e);
@@ -32,227 +84,125 @@ impl MetricsService {
         Ok(Self {
             metrics_manager,
             redis_client,
-            pubsub_manager
+            pubsub_manager,
+            market_registry,
+            ranking_service,
+            windows : MetricsWindow::from_env()
         })
     }
 
     pub async fn start_all_schedulers(self: Arc<Self>) {
         println!("🕐 Starting metrics schedulers...");
+        for window in self.windows.clone() {
+            let scheduler = Arc::clone(&self);
+            tokio::spawn(async move {
+                println!("Started {} scheduler, refreshing every {:?}", window.timeframe.label(), window.refresh_every);
+                scheduler.run_window_scheduler(window).await;
+            });
+        }
 
-        let scheduler_5m = Arc::clone(&self);
-        tokio::spawn(async move {
-            println!("Started 5 min scheduler");
-            scheduler_5m.start_5m_scheduler().await;
-        });
-
-        let scheduler_1h = Arc::clone(&self);
-        tokio::spawn(async move {
-            println!("Started 1 hour scheduler");
-            scheduler_1h.start_1h_scheduler().await;
-        });
-
-        let scheduler_6h = Arc::clone(&self);
-        tokio::spawn(async move {
-            println!("Started 6 hours scheduler");
-            scheduler_6h.start_6h_scheduler().await;
-        });
-
-        let scheduler_24h = Arc::clone(&self);
+        let janitor = Arc::clone(&self);
         tokio::spawn(async move {
-            println!("Started 24 hours scheduler");
-            scheduler_24h.start_24h_scheduler().await;
+            println!("Started market janitor");
+            janitor.run_market_janitor().await;
         });
     }
 
-    async fn start_5m_scheduler(&self) {
-        let mut interval = interval(Duration::from_secs(300));
+    async fn run_window_scheduler(&self, window : MetricsWindow) {
+        let label = window.timeframe.label();
+        let refresh_ms = window.refresh_every.as_millis() as u64;
+        // spread the first run so every window does not hit redis in the same instant
+        tokio::time::sleep(Duration::from_millis(fastrand::u64(0..=refresh_ms.min(10_000)))).await;
+
+        let mut interval = interval(window.refresh_every);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
         loop {
             interval.tick().await;
-            println!("⏰ Running 5m metrics update...");
-            if let Ok(token_pair_list) = self.get_active_token_pairs().await {
-                for token in token_pair_list {
-                    let current_price  = self.metrics_manager.get_current_price(&token).await.expect("Error getting current price from redis");
-                    if let Ok(token_metrics) = self.metrics_manager.get_metrics(&token).await{
-                        match self.metrics_manager.get_historical_price(&token, TimeFrame::FiveMins).await{
-                            Ok(Some(price)) => {
-                                println!("got historical price for : {}", token);
-                                let percentage_change = (current_price - price) / price;
-                                let period_stats_update = PeriodStatsUpdate{
-                                    token_pair : token,
-                                    timeframe : TimeFrame::FiveMins,
-                                    price_change : percentage_change,
-                                    period_stats : Some(PeriodStats{
-                                        txns : token_metrics.txns,
-                                        volume : token_metrics.volume,
-                                        makers : token_metrics.makers,
-                                        buyers : token_metrics.buyers,
-                                        buy_volume : token_metrics.buy_volume,
-                                        buys : token_metrics.buys,
-                                        sells : token_metrics.sells,
-                                        sell_volume : token_metrics.sell_volume,
-                                        sellers : token_metrics.sellers
-                                    })
-                                };
-                                let _ = self.pubsub_manager.publish_price_and_metrics_update(period_stats_update).await;
-                            }
-                            Ok(None) => {
-                                println!("Received no historical price for : {}", token);
-                            }
-                            Err(e) => {
-                                println!("Redis error occured while getting historical price : {}", e);
-                            }
-                        };
-                    };
+            tokio::time::sleep(Duration::from_millis(fastrand::u64(0..=(refresh_ms / 10).min(2_000)))).await;
+            println!("⏰ Running {} metrics update...", label);
+            let token_pair_list = match self.get_active_token_pairs(&window).await {
+                Ok(token_pair_list) => token_pair_list,
+                Err(e) => {
+                    println!("Error getting active token pairs for {} metrics : {}", label, e);
+                    continue;
                 }
             };
-        }
-    }
-    async fn start_1h_scheduler(&self) {
-        let mut interval = interval(Duration::from_secs(3600));
-        loop {
-            interval.tick().await;
-            println!("⏰ Running 1hr metrics update...");
-            if let Ok(token_pair_list) = self.get_active_token_pairs().await {
-                for token in token_pair_list {
-                    let current_price  = self.metrics_manager.get_current_price(&token).await.expect("Error getting current price from redis");
-                    if let Ok(token_metrics) = self.metrics_manager.get_metrics(&token).await{
-                        match self.metrics_manager.get_historical_price(&token, TimeFrame::OneHour).await{
-                            Ok(Some(price)) => {
-                                println!("got historical price for : {}", token);
-                                let percentage_change = (current_price - price) / price;
-                                let period_stats_update = PeriodStatsUpdate{
-                                    token_pair : token,
-                                    timeframe : TimeFrame::OneHour,
-                                    price_change : percentage_change,
-                                    period_stats : Some(PeriodStats{
-                                        txns : token_metrics.txns,
-                                        volume : token_metrics.volume,
-                                        makers : token_metrics.makers,
-                                        buyers : token_metrics.buyers,
-                                        buy_volume : token_metrics.buy_volume,
-                                        buys : token_metrics.buys,
-                                        sells : token_metrics.sells,
-                                        sell_volume : token_metrics.sell_volume,
-                                        sellers : token_metrics.sellers
-                                    })
-                                };
-                                let _ = self.pubsub_manager.publish_price_and_metrics_update(period_stats_update).await;
-                            }
-                            Ok(None) => {
-                                println!("Received no historical price for : {}", token);
-                            }
-                            Err(e) => {
-                                println!("Redis error occured while getting historical price : {}", e);
-                            }
-                        };
-                    };
+            let mut snapshots = Vec::with_capacity(token_pair_list.len());
+            for token in token_pair_list {
+                // one broken pair must not stop the window for the others
+                match self.publish_window_update(&token, window.timeframe).await {
+                    Ok(Some(snapshot)) => snapshots.push(snapshot),
+                    Ok(None) => {}
+                    Err(e) => println!("Error updating {} metrics for {} : {}", label, token, e),
                 }
-            };
+            }
+            if let Err(e) = self.ranking_service.rebuild(window.timeframe, &snapshots).await {
+                println!("Error rebuilding {} leaderboards : {}", label, e);
+            }
         }
     }
-    async fn start_6h_scheduler(&self) {
-        let mut interval = interval(Duration::from_secs(21600));
-        loop {
-            interval.tick().await;
-            println!("⏰ Running 6hr metrics update...");
-            if let Ok(token_pair_list) = self.get_active_token_pairs().await {
-                for token in token_pair_list {
-                    let current_price  = self.metrics_manager.get_current_price(&token).await.expect("Error getting current price from redis");
-                    if let Ok(token_metrics) = self.metrics_manager.get_metrics(&token).await{
-                        match self.metrics_manager.get_historical_price(&token, TimeFrame::SixHours).await{
-                            Ok(Some(price)) => {
-                                println!("got historical price for : {}", token);
-                                let percentage_change = (current_price - price) / price;
-                                let period_stats_update = PeriodStatsUpdate{
-                                   token_pair : token,
-                                    timeframe : TimeFrame::SixHours,
-                                    price_change : percentage_change,
-                                    period_stats : Some(PeriodStats{
-                                        txns : token_metrics.txns,
-                                        volume : token_metrics.volume,
-                                        makers : token_metrics.makers,
-                                        buyers : token_metrics.buyers,
-                                        buy_volume : token_metrics.buy_volume,
-                                        buys : token_metrics.buys,
-                                        sells : token_metrics.sells,
-                                        sell_volume : token_metrics.sell_volume,
-                                        sellers : token_metrics.sellers
-                                    })
-                                };
-                                let _ = self.pubsub_manager.publish_price_and_metrics_update(period_stats_update).await;
-                            }
-                            Ok(None) => {
-                                println!("Received no historical price for : {}", token);
-                            }
-                            Err(e) => {
-                                println!("Redis error occured while getting historical price : {}", e);
-                            }
-                        };
-                    };
-                }
-            };
-        }
+
+    // publishes the window stats of one pair and hands them back for ranking
+    async fn publish_window_update(&self, token_pair : &str, timeframe : TimeFrame) -> Result<Option<WindowSnapshot>, anyhow::Error> {
+        let current_price = self.metrics_manager.get_current_price(token_pair).await?;
+        let token_metrics = self.metrics_manager.get_metrics(token_pair, &timeframe).await?;
+        let historical_price = match self.metrics_manager.get_historical_price(token_pair, timeframe).await? {
+            Some(price) if price > 0.0 => price,
+            _ => {
+                println!("Received no historical price for : {}", token_pair);
+                return Ok(None);
+            }
+        };
+        let price_change = (current_price - historical_price) / historical_price;
+        let period_stats_update = PeriodStatsUpdate{
+            token_pair : token_pair.to_string(),
+            timeframe,
+            price_change,
+            period_stats : Some(token_metrics.clone())
+        };
+        self.pubsub_manager.publish_price_and_metrics_update(period_stats_update).await?;
+
+        let previous = self.metrics_manager.get_previous_metrics(token_pair, &timeframe).await?;
+        Ok(Some(WindowSnapshot {
+            token_pair : token_pair.to_string(),
+            price_change,
+            current : token_metrics,
+            previous
+        }))
     }
-    async fn start_24h_scheduler(&self) {
-        let mut interval = interval(Duration::from_secs(86400));
+
+    // pairs that traded inside the window, plus one refresh of slack so a pair that just went quiet gets a final zeroed update
+    async fn get_active_token_pairs(&self, window : &MetricsWindow) -> RedisResult<Vec<String>> {
+        let active_since = Utc::now().timestamp() - window.timeframe.to_seconds() - window.refresh_every.as_secs() as i64;
+        self.market_registry.get_active_pairs(active_since).await
+    }
+
+    // archives and deletes the live state of markets that have not traded for MARKET_INACTIVE_AFTER_SECS
+    async fn run_market_janitor(&self) {
+        let inactive_after = std::env::var("MARKET_INACTIVE_AFTER_SECS")
+            .ok()
+            .and_then(|secs| secs.parse::<i64>().ok())
+            .unwrap_or(MARKET_INACTIVE_AFTER_SECS);
+        let mut interval = interval(JANITOR_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
         loop {
             interval.tick().await;
-            println!("⏰ Running 24hr metrics update...");
-            if let Ok(token_pair_list) = self.get_active_token_pairs().await {
-                for token in token_pair_list {
-                    let current_price  = self.metrics_manager.get_current_price(&token).await.expect("Error getting current price from redis");
-                    if let Ok(token_metrics) = self.metrics_manager.get_metrics(&token).await{
-                        match self.metrics_manager.get_historical_price(&token, TimeFrame::TwentyFourHours).await{
-                            Ok(Some(price)) => {
-                                println!("got historical price for : {}", token);
-                                let percentage_change = (current_price - price) / price;
-                                let period_stats_update = PeriodStatsUpdate{
-                                    token_pair : token,
-                                    timeframe : TimeFrame::TwentyFourHours,
-                                    price_change : percentage_change,
-                                    period_stats : Some(PeriodStats{
-                                        txns : token_metrics.txns,
-                                        volume : token_metrics.volume,
-                                        makers : token_metrics.makers,
-                                        buyers : token_metrics.buyers,
-                                        buy_volume : token_metrics.buy_volume,
-                                        buys : token_metrics.buys,
-                                        sells : token_metrics.sells,
-                                        sell_volume : token_metrics.sell_volume,
-                                        sellers : token_metrics.sellers
-                                    })
-                                };
-                                let _ = self.pubsub_manager.publish_price_and_metrics_update(period_stats_update).await;
-                            }
-                            Ok(None) => {
-                                println!("Received no historical price for : {}", token);
-                            }
-                            Err(e) => {
-                                println!("Redis error occured while getting historical price : {}", e);
-                            }
-                        };
-                    };
+            let inactive_before = Utc::now().timestamp() - inactive_after;
+            let markets = match self.market_registry.get_inactive_markets(inactive_before).await {
+                Ok(markets) => markets,
+                Err(e) => {
+                    println!("Error getting inactive markets : {}", e);
+                    continue;
                 }
             };
-        }
-    }
-
-    async fn get_active_token_pairs(&self) -> RedisResult<Vec<String>> {
-        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
-        let pattern = "token:*:current-price";
-        let keys: Vec<String> = match conn.keys(pattern).await {
-            Ok(keys) => keys,
-            Err(e) => {
-                println!("Error getting keys for active tokens from redis : {}", e);
-                Vec::new()
+            if !markets.is_empty() {
+                println!("🧹 Archiving {} inactive markets", markets.len());
+            }
+            for market in markets {
+                if let Err(e) = self.market_registry.archive_market(&market).await {
+                    println!("Error archiving market {} : {}", market.token_pair, e);
+                }
             }
-        };
-        let mut token_pairs = HashSet::new();
-        for key in keys {
-            let parts: Vec<&str> = key.split(":").collect();
-            let token_pair = parts[1].to_string();
-            token_pairs.insert(token_pair);
         }
-        Ok(token_pairs.into_iter().collect()) //.iter gives a iterator over references (&String) and .into_iter() gives iterator over owned values (String). then .collect consumes those iterator and creates a collection based on fn return type or type annotation.
     }
 }
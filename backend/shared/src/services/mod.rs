@@ -1,2 +1,17 @@
 pub mod price_service;
-pub mod metrics_service;
\ No newline at end of file
+pub mod metrics_service;
+pub mod price_oracle;
+pub mod metadata_resolver;
+pub mod supply_service;
+pub mod outbound;
+pub mod liquidity_service;
+pub mod pool_state;
+pub mod ranking_service;
+pub mod holder_service;
+pub mod wallet_service;
+pub mod alert_service;
+pub mod anomaly_service;
+pub mod candle_store;
+pub mod candle_rollup;
+pub mod swap_store;
+pub mod registry_store;
\ No newline at end of file
@@ -0,0 +1,355 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Once},
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use redis::{AsyncCommands, Client};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+lazy_static::lazy_static! {
+    // one guard per process so every caller of a host shares the same bucket and breaker
+    pub static ref OUTBOUND: OutboundGuard = OutboundGuard::new();
+}
+
+static REPORTER: Once = Once::new();
+const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+pub struct HostPolicy {
+    pub requests_per_sec: f64,
+    pub burst: f64,
+    pub timeout: Duration,
+    pub max_retries: u32,
+    pub failure_threshold: u32, // consecutive failures before the breaker opens
+    pub open_cooldown: Duration,
+}
+
+impl HostPolicy {
+    fn for_host(host: &str) -> Self {
+        let mut policy = if host.contains("coingecko") {
+            // public coingecko tier allows roughly 30 calls a minute
+            Self { requests_per_sec: 0.5, burst: 2.0, ..Self::default() }
+        } else if host.contains("rpc") {
+            Self { requests_per_sec: 10.0, burst: 20.0, ..Self::default() }
+        } else {
+            Self::default()
+        };
+        // OUTBOUND_RATE_LIMITS="api.coingecko.com=0.5,mainnet.helius-rpc.com=25"
+        if let Ok(limits) = std::env::var("OUTBOUND_RATE_LIMITS") {
+            for limit in limits.split(',') {
+                if let Some((limit_host, rps)) = limit.split_once('=')
+                    && limit_host.trim() == host
+                    && let Ok(rps) = rps.trim().parse::<f64>() {
+                    policy.requests_per_sec = rps;
+                    policy.burst = policy.burst.max(rps);
+                }
+            }
+        }
+        policy
+    }
+}
+
+impl Default for HostPolicy {
+    fn default() -> Self {
+        Self {
+            requests_per_sec: 5.0,
+            burst: 10.0,
+            timeout: Duration::from_secs(5),
+            max_retries: 2,
+            failure_threshold: 5,
+            open_cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BreakerState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HostStats {
+    pub calls: u64,
+    pub successes: u64,
+    pub failures: u64,
+    pub timeouts: u64,
+    pub retries: u64,
+    pub rejected: u64, // calls failed fast by an open breaker
+    pub throttled: u64, // calls that had to wait for a rate limit token
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostSnapshot {
+    pub service: String,
+    pub host: String,
+    pub breaker: BreakerState,
+    pub stats: HostStats,
+    pub updated_at: i64,
+}
+
+#[derive(Debug)]
+struct HostState {
+    policy: HostPolicy,
+    tokens: f64,
+    last_refill: Instant,
+    breaker: BreakerState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    half_open_trial: bool,
+    stats: HostStats,
+}
+
+impl HostState {
+    fn new(policy: HostPolicy) -> Self {
+        Self {
+            tokens: policy.burst,
+            policy,
+            last_refill: Instant::now(),
+            breaker: BreakerState::Closed,
+            consecutive_failures: 0,
+            opened_at: None,
+            half_open_trial: false,
+            stats: HostStats::default(),
+        }
+    }
+
+    // takes a token if one is available, otherwise returns how long to wait for the next one
+    fn try_take_token(&mut self) -> Result<(), Duration> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.policy.requests_per_sec).min(self.policy.burst);
+        self.last_refill = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            let missing = 1.0 - self.tokens;
+            Err(Duration::from_secs_f64(missing / self.policy.requests_per_sec.max(0.001)))
+        }
+    }
+
+    fn admit(&mut self) -> bool {
+        match self.breaker {
+            BreakerState::Closed => true,
+            BreakerState::Open => {
+                let cooled_down = self
+                    .opened_at
+                    .map(|opened_at| opened_at.elapsed() >= self.policy.open_cooldown)
+                    .unwrap_or(true);
+                if cooled_down {
+                    self.breaker = BreakerState::HalfOpen;
+                    self.half_open_trial = true;
+                    true
+                } else {
+                    false
+                }
+            }
+            // only the single trial call goes through while half open
+            BreakerState::HalfOpen => {
+                if self.half_open_trial {
+                    false
+                } else {
+                    self.half_open_trial = true;
+                    true
+                }
+            }
+        }
+    }
+
+    fn record_success(&mut self) {
+        self.stats.successes += 1;
+        self.consecutive_failures = 0;
+        self.breaker = BreakerState::Closed;
+        self.opened_at = None;
+        self.half_open_trial = false;
+    }
+
+    fn record_failure(&mut self, error: String) {
+        self.stats.failures += 1;
+        self.stats.last_error = Some(error);
+        self.consecutive_failures += 1;
+        if self.breaker == BreakerState::HalfOpen || self.consecutive_failures >= self.policy.failure_threshold {
+            if self.breaker != BreakerState::Open {
+                println!("⚠️ Circuit opened after {} consecutive failures", self.consecutive_failures);
+            }
+            self.breaker = BreakerState::Open;
+            self.opened_at = Some(Instant::now());
+        }
+        self.half_open_trial = false;
+    }
+}
+
+#[derive(Debug)]
+pub struct OutboundGuard {
+    hosts: Mutex<HashMap<String, Arc<Mutex<HostState>>>>,
+}
+
+impl OutboundGuard {
+    fn new() -> Self {
+        Self { hosts: Mutex::new(HashMap::new()) }
+    }
+
+    async fn host_state(&self, host: &str) -> Arc<Mutex<HostState>> {
+        let mut hosts = self.hosts.lock().await;
+        Arc::clone(
+            hosts
+                .entry(host.to_string())
+                .or_insert_with(|| Arc::new(Mutex::new(HostState::new(HostPolicy::for_host(host))))),
+        )
+    }
+
+    /// Runs `op` against `host` behind its rate limit, timeout, retry budget and circuit breaker.
+    pub async fn call<T, E, F, Fut>(&self, host: &str, op: F) -> Result<T, anyhow::Error>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: Into<anyhow::Error>,
+    {
+        Self::start_reporter();
+        let state = self.host_state(host).await;
+        let policy = state.lock().await.policy.clone();
+
+        let mut attempt = 0;
+        loop {
+            {
+                let mut host_state = state.lock().await;
+                host_state.stats.calls += 1;
+                if !host_state.admit() {
+                    host_state.stats.rejected += 1;
+                    return Err(anyhow::anyhow!("circuit open for {}, failing fast", host));
+                }
+            }
+            self.wait_for_token(&state).await;
+
+            let error = match tokio::time::timeout(policy.timeout, op()).await {
+                Ok(Ok(value)) => {
+                    state.lock().await.record_success();
+                    return Ok(value);
+                }
+                Ok(Err(e)) => e.into(),
+                Err(_) => {
+                    state.lock().await.stats.timeouts += 1;
+                    anyhow::anyhow!("call to {} timed out after {:?}", host, policy.timeout)
+                }
+            };
+
+            let breaker_open = {
+                let mut host_state = state.lock().await;
+                host_state.record_failure(error.to_string());
+                host_state.breaker == BreakerState::Open
+            };
+            if attempt >= policy.max_retries || breaker_open {
+                return Err(error);
+            }
+
+            attempt += 1;
+            state.lock().await.stats.retries += 1;
+            // exponential backoff with full jitter
+            let backoff_ms = 200u64 * 2u64.pow(attempt);
+            let delay = Duration::from_millis(fastrand::u64(backoff_ms / 2..=backoff_ms));
+            println!("Retrying {} in {:?} (attempt {}) after error : {}", host, delay, attempt, error);
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    async fn wait_for_token(&self, state: &Arc<Mutex<HostState>>) {
+        let mut throttled = false;
+        loop {
+            let wait = {
+                let mut host_state = state.lock().await;
+                match host_state.try_take_token() {
+                    Ok(()) => {
+                        if throttled {
+                            host_state.stats.throttled += 1;
+                        }
+                        return;
+                    }
+                    Err(wait) => wait,
+                }
+            };
+            throttled = true;
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    pub async fn snapshot(&self, service: &str) -> Vec<HostSnapshot> {
+        let hosts: Vec<(String, Arc<Mutex<HostState>>)> = self
+            .hosts
+            .lock()
+            .await
+            .iter()
+            .map(|(host, state)| (host.clone(), Arc::clone(state)))
+            .collect();
+        let mut snapshots = Vec::with_capacity(hosts.len());
+        for (host, state) in hosts {
+            let host_state = state.lock().await;
+            snapshots.push(HostSnapshot {
+                service: service.to_string(),
+                host,
+                breaker: host_state.breaker,
+                stats: host_state.stats.clone(),
+                updated_at: Utc::now().timestamp(),
+            });
+        }
+        snapshots
+    }
+
+    // writes this process' breaker state and call counters to redis for the api server
+    fn start_reporter() {
+        REPORTER.call_once(|| {
+            let service = std::env::current_exe()
+                .ok()
+                .and_then(|path| path.file_stem().map(|name| name.to_string_lossy().to_string()))
+                .unwrap_or_else(|| "unknown".to_string());
+            tokio::spawn(async move {
+                let redis_client = match std::env::var("REDIS_URL").map(Client::open) {
+                    Ok(Ok(client)) => client,
+                    _ => {
+                        println!("Outbound stats reporter has no redis client, stats stay local");
+                        return;
+                    }
+                };
+                let mut interval = tokio::time::interval(REPORT_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    if let Err(e) = Self::report(&redis_client, &service).await {
+                        println!("Error reporting outbound stats : {}", e);
+                    }
+                }
+            });
+        });
+    }
+
+    async fn report(redis_client: &Client, service: &str) -> Result<(), anyhow::Error> {
+        let mut conn = redis_client.get_multiplexed_async_connection().await?;
+        for snapshot in OUTBOUND.snapshot(service).await {
+            let field = format!("{}:{}", snapshot.service, snapshot.host);
+            let _: () = conn.hset("outbound:stats", field, serde_json::to_string(&snapshot)?).await?;
+        }
+        Ok(())
+    }
+}
+
+pub async fn get_outbound_stats() -> Result<Vec<HostSnapshot>, anyhow::Error> {
+    let redis_client = Client::open(std::env::var("REDIS_URL").expect("unable to fetch redis url from env"))?;
+    let mut conn = redis_client.get_multiplexed_async_connection().await?;
+    let entries: HashMap<String, String> = conn.hgetall("outbound:stats").await?;
+    Ok(entries
+        .values()
+        .filter_map(|snapshot| serde_json::from_str(snapshot).ok())
+        .collect())
+}
+
+pub fn host_of(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_else(|| "unknown".to_string())
+}
@@ -0,0 +1,281 @@
+use sha2::{Digest, Sha256};
+use solana_program::pubkey::Pubkey;
+
+use crate::{types::pool::PoolInfo, METEORA_DAMM_V1, METEORA_DAMM_V2, METEORA_DLMM, ORCA_CLMM, RADUIM_AMM_V4, RADUIM_CLMM};
+
+const RAYDIUM_AMM_V4_LEN: usize = 752;
+
+/// Reserve related fields of a pool state account, amounts are raw token units.
+///
+/// `fees_a`/`fees_b` are held in the vaults but are not liquidity : protocol and fund fees, and for
+/// raydium the lp fees not collected yet. whirlpool and dlmm keep lp fees per position, so only their
+/// protocol fees are known here. `liquidity` and `sqrt_price_x64` are set for concentrated liquidity
+/// pools, where only the liquidity around the current price is active.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PoolState {
+    pub vault_a: String,
+    pub vault_b: String,
+    pub fees_a: u64,
+    pub fees_b: u64,
+    pub liquidity: Option<u128>,
+    pub sqrt_price_x64: Option<u128>,
+}
+
+// reserve adjustments of a pool, in ui amounts of its token and sol sides
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StateReserves {
+    pub token_fees: f64,
+    pub sol_fees: f64,
+    pub active_sol_reserve: Option<f64>, // sol side of the virtual reserves at the current price
+}
+
+// programs whose pool state accounts are decoded, meteora damm pools keep their vault balances
+pub fn is_decodable(program_id: &str) -> bool {
+    [RADUIM_AMM_V4, RADUIM_CLMM, ORCA_CLMM, METEORA_DLMM].contains(&program_id)
+}
+
+/// The pool state account of a swap instruction, `None` when the program is not supported.
+///
+/// The vaults of concentrated liquidity and dlmm pools are owned by their state account, the
+/// constant product programs take it as an instruction account next to a shared authority.
+pub fn pool_state_account(program_id: &str, accounts: &[String], vault_owner: &str) -> Option<String> {
+    match program_id {
+        RADUIM_CLMM | ORCA_CLMM | METEORA_DLMM => Some(vault_owner.to_string()),
+        RADUIM_AMM_V4 | METEORA_DAMM_V2 => accounts.get(1).cloned(),
+        METEORA_DAMM_V1 => accounts.first().cloned(),
+        _ => None,
+    }
+}
+
+pub fn decode_pool_state(program_id: &str, data: &[u8]) -> Result<PoolState, anyhow::Error> {
+    match program_id {
+        RADUIM_AMM_V4 => decode_raydium_amm_v4(data),
+        RADUIM_CLMM => decode_raydium_clmm(data),
+        ORCA_CLMM => decode_whirlpool(data),
+        METEORA_DLMM => decode_meteora_dlmm(data),
+        _ => Err(anyhow::anyhow!("pool state of program {} is not decoded", program_id)),
+    }
+}
+
+impl PoolState {
+    // maps the a/b sides onto the pool's token and sol sides, `None` when the vaults are not the pool's
+    pub fn reserves_for(&self, pool: &PoolInfo) -> Option<StateReserves> {
+        let sol_is_b = if self.vault_a == pool.token_vault && self.vault_b == pool.sol_vault {
+            true
+        } else if self.vault_a == pool.sol_vault && self.vault_b == pool.token_vault {
+            false
+        } else {
+            return None;
+        };
+        let (token_fees, sol_fees) = if sol_is_b { (self.fees_a, self.fees_b) } else { (self.fees_b, self.fees_a) };
+        // virtual reserves of a concentrated pool : x = L / sqrt(p) and y = L * sqrt(p), p being the raw price of a in b
+        let active_sol_reserve = self.liquidity.zip(self.sqrt_price_x64).and_then(|(liquidity, sqrt_price_x64)| {
+            let sqrt_price = sqrt_price_x64 as f64 / 2f64.powi(64);
+            if sqrt_price <= 0.0 {
+                return None;
+            }
+            let sol_raw = if sol_is_b { liquidity as f64 * sqrt_price } else { liquidity as f64 / sqrt_price };
+            Some(sol_raw / 10f64.powi(pool.sol_decimals as i32))
+        });
+        Some(StateReserves {
+            token_fees: token_fees as f64 / 10f64.powi(pool.token_decimals as i32),
+            sol_fees: sol_fees as f64 / 10f64.powi(pool.sol_decimals as i32),
+            active_sol_reserve,
+        })
+    }
+}
+
+// anchor accounts start with the first 8 bytes of sha256("account:<name>")
+fn check_discriminator(data: &[u8], account_name: &str, min_len: usize) -> Result<(), anyhow::Error> {
+    let discriminator = Sha256::digest(format!("account:{}", account_name));
+    if data.len() < min_len || data[..8] != discriminator[..8] {
+        return Err(anyhow::anyhow!("account is not a {} account", account_name));
+    }
+    Ok(())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().expect("length checked by the decoder"))
+}
+
+fn read_u128(data: &[u8], offset: usize) -> u128 {
+    u128::from_le_bytes(data[offset..offset + 16].try_into().expect("length checked by the decoder"))
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> String {
+    Pubkey::new_from_array(data[offset..offset + 32].try_into().expect("length checked by the decoder")).to_string()
+}
+
+// AmmInfo has no discriminator : 16 u64 params and 8 u64 fees, then the state data starting with the pnl owed to the program
+fn decode_raydium_amm_v4(data: &[u8]) -> Result<PoolState, anyhow::Error> {
+    if data.len() != RAYDIUM_AMM_V4_LEN {
+        return Err(anyhow::anyhow!("account is not a raydium amm v4 pool"));
+    }
+    Ok(PoolState {
+        vault_a: read_pubkey(data, 336),
+        vault_b: read_pubkey(data, 368),
+        fees_a: read_u64(data, 192), // need_take_pnl_coin
+        fees_b: read_u64(data, 200), // need_take_pnl_pc
+        liquidity: None,
+        sqrt_price_x64: None,
+    })
+}
+
+// PoolState is packed, lp fees not collected yet are the total fees minus the claimed ones
+fn decode_raydium_clmm(data: &[u8]) -> Result<PoolState, anyhow::Error> {
+    check_discriminator(data, "PoolState", 1080)?;
+    let unclaimed = |total: usize, claimed: usize| read_u64(data, total).saturating_sub(read_u64(data, claimed));
+    Ok(PoolState {
+        vault_a: read_pubkey(data, 137),
+        vault_b: read_pubkey(data, 169),
+        fees_a: read_u64(data, 309) + read_u64(data, 1064) + unclaimed(1032, 1040),
+        fees_b: read_u64(data, 317) + read_u64(data, 1072) + unclaimed(1048, 1056),
+        liquidity: Some(read_u128(data, 237)),
+        sqrt_price_x64: Some(read_u128(data, 253)),
+    })
+}
+
+fn decode_whirlpool(data: &[u8]) -> Result<PoolState, anyhow::Error> {
+    check_discriminator(data, "Whirlpool", 245)?;
+    Ok(PoolState {
+        vault_a: read_pubkey(data, 133),
+        vault_b: read_pubkey(data, 213),
+        fees_a: read_u64(data, 85), // protocol_fee_owed_a
+        fees_b: read_u64(data, 93),
+        liquidity: Some(read_u128(data, 49)),
+        sqrt_price_x64: Some(read_u128(data, 65)),
+    })
+}
+
+// the active bin's reserves live in bin array accounts, so dlmm pools only get their fees taken out
+fn decode_meteora_dlmm(data: &[u8]) -> Result<PoolState, anyhow::Error> {
+    check_discriminator(data, "LbPair", 232)?;
+    Ok(PoolState {
+        vault_a: read_pubkey(data, 152), // reserve_x
+        vault_b: read_pubkey(data, 184),
+        fees_a: read_u64(data, 216), // protocol_fee.amount_x
+        fees_b: read_u64(data, 224),
+        liquidity: None,
+        sqrt_price_x64: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN_VAULT: [u8; 32] = [7; 32];
+    const SOL_VAULT: [u8; 32] = [9; 32];
+
+    fn pool(program_id: &str) -> PoolInfo {
+        PoolInfo {
+            pool_id: "pool".to_string(),
+            pool_owner: "owner".to_string(),
+            program_id: program_id.to_string(),
+            dex_tag: "CLMM".to_string(),
+            token_pair: "TEST/SOL".to_string(),
+            token_mint: "mint".to_string(),
+            token_vault: Pubkey::new_from_array(TOKEN_VAULT).to_string(),
+            sol_vault: Pubkey::new_from_array(SOL_VAULT).to_string(),
+            token_decimals: 6,
+            sol_decimals: 9,
+        }
+    }
+
+    fn anchor_account(account_name: &str, len: usize) -> Vec<u8> {
+        let mut data = vec![0u8; len];
+        data[..8].copy_from_slice(&Sha256::digest(format!("account:{}", account_name))[..8]);
+        data
+    }
+
+    fn put(data: &mut [u8], offset: usize, bytes: &[u8]) {
+        data[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    #[test]
+    fn decodes_raydium_amm_v4_pnl_as_fees() {
+        let mut data = vec![0u8; RAYDIUM_AMM_V4_LEN];
+        put(&mut data, 192, &5_000_000u64.to_le_bytes());
+        put(&mut data, 200, &2_000_000_000u64.to_le_bytes());
+        put(&mut data, 336, &TOKEN_VAULT);
+        put(&mut data, 368, &SOL_VAULT);
+
+        let state = decode_pool_state(RADUIM_AMM_V4, &data).unwrap();
+        let reserves = state.reserves_for(&pool(RADUIM_AMM_V4)).unwrap();
+        assert_eq!(reserves.token_fees, 5.0);
+        assert_eq!(reserves.sol_fees, 2.0);
+        assert_eq!(reserves.active_sol_reserve, None);
+
+        assert!(decode_pool_state(RADUIM_AMM_V4, &data[..700]).is_err());
+    }
+
+    #[test]
+    fn decodes_raydium_clmm_protocol_fund_and_unclaimed_fees() {
+        let mut data = anchor_account("PoolState", 1544);
+        // sol is token 0 here
+        put(&mut data, 137, &SOL_VAULT);
+        put(&mut data, 169, &TOKEN_VAULT);
+        put(&mut data, 237, &4_000_000_000u128.to_le_bytes());
+        put(&mut data, 253, &(2u128 << 64).to_le_bytes());
+        put(&mut data, 309, &1_000_000_000u64.to_le_bytes());
+        put(&mut data, 317, &1_000_000u64.to_le_bytes());
+        put(&mut data, 1032, &3_000_000_000u64.to_le_bytes());
+        put(&mut data, 1040, &2_500_000_000u64.to_le_bytes());
+        put(&mut data, 1048, &4_000_000u64.to_le_bytes());
+        put(&mut data, 1056, &4_000_000u64.to_le_bytes());
+        put(&mut data, 1064, &500_000_000u64.to_le_bytes());
+        put(&mut data, 1072, &1_000_000u64.to_le_bytes());
+
+        let state = decode_pool_state(RADUIM_CLMM, &data).unwrap();
+        let reserves = state.reserves_for(&pool(RADUIM_CLMM)).unwrap();
+        assert_eq!(reserves.sol_fees, 2.0);
+        assert_eq!(reserves.token_fees, 2.0);
+        // sol is token 0, x = L / sqrt(p) = 4e9 / 2 raw
+        assert_eq!(reserves.active_sol_reserve, Some(2.0));
+
+        let mut not_a_pool = data.clone();
+        not_a_pool[0] ^= 1;
+        assert!(decode_pool_state(RADUIM_CLMM, &not_a_pool).is_err());
+    }
+
+    #[test]
+    fn decodes_whirlpool_protocol_fees_and_virtual_reserves() {
+        let mut data = anchor_account("Whirlpool", 653);
+        put(&mut data, 49, &1_000_000_000u128.to_le_bytes());
+        put(&mut data, 65, &(3u128 << 64).to_le_bytes());
+        put(&mut data, 85, &1_000_000u64.to_le_bytes());
+        put(&mut data, 93, &3_000_000_000u64.to_le_bytes());
+        put(&mut data, 133, &TOKEN_VAULT);
+        put(&mut data, 213, &SOL_VAULT);
+
+        let reserves = decode_pool_state(ORCA_CLMM, &data).unwrap().reserves_for(&pool(ORCA_CLMM)).unwrap();
+        assert_eq!(reserves.token_fees, 1.0);
+        assert_eq!(reserves.sol_fees, 3.0);
+        // sol is token b, y = L * sqrt(p) = 1e9 * 3 raw
+        assert_eq!(reserves.active_sol_reserve, Some(3.0));
+    }
+
+    #[test]
+    fn decodes_dlmm_reserves_and_rejects_foreign_vaults() {
+        let mut data = anchor_account("LbPair", 904);
+        put(&mut data, 152, &TOKEN_VAULT);
+        put(&mut data, 184, &[1; 32]);
+        put(&mut data, 216, &1_000_000u64.to_le_bytes());
+
+        let state = decode_pool_state(METEORA_DLMM, &data).unwrap();
+        assert_eq!(state.fees_a, 1_000_000);
+        assert_eq!(state.liquidity, None);
+        assert_eq!(state.reserves_for(&pool(METEORA_DLMM)), None);
+        assert!(decode_pool_state(METEORA_DAMM_V2, &data).is_err());
+    }
+
+    #[test]
+    fn finds_the_pool_state_account_per_program() {
+        let accounts: Vec<String> = ["first", "second", "third"].iter().map(|account| account.to_string()).collect();
+        assert_eq!(pool_state_account(RADUIM_AMM_V4, &accounts, "authority").as_deref(), Some("second"));
+        assert_eq!(pool_state_account(METEORA_DAMM_V2, &accounts, "authority").as_deref(), Some("second"));
+        assert_eq!(pool_state_account(METEORA_DAMM_V1, &accounts, "authority").as_deref(), Some("first"));
+        assert_eq!(pool_state_account(RADUIM_CLMM, &accounts, "state").as_deref(), Some("state"));
+        assert_eq!(pool_state_account("other", &accounts, "state"), None);
+    }
+}
@@ -0,0 +1,464 @@
+use std::collections::VecDeque;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use reqwest::Client;
+use serde_json::Value;
+
+use crate::{redis::token_symbol_manager::TokenSymbolManager, services::outbound::OUTBOUND, types::price::OracleQuote};
+
+pub const PYTH_RECEIVER_PROGRAM: &str = "rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ";
+pub const PYTH_SOL_USD_ACCOUNT: &str = "7UVimffxr9ow1uXYxsr4LHAcV58mLzhmwaeKvJ1pjLiE"; // sponsored SOL/USD price update account
+const PRICE_UPDATE_V2_DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+const DEX_PRICE_STORE_INTERVAL_SECS: i64 = 1;
+
+#[async_trait]
+pub trait PriceOracle: Send + Sync {
+    fn name(&self) -> &'static str;
+    async fn fetch_sol_price(&self) -> Result<OracleQuote, anyhow::Error>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OracleStrategy {
+    Priority, // first fresh quote in the configured order wins
+    Median    // median of every fresh quote
+}
+
+#[derive(Debug, Clone)]
+pub struct OracleConfig {
+    pub sources: Vec<String>,
+    pub strategy: OracleStrategy,
+    pub max_staleness: Duration,
+    pub cache_ttl: Duration, // how long the aggregated price is reused before the sources are queried again
+    pub max_confidence_ratio: f64, // conf / price, quotes wider than this are rejected
+    pub dex_min_quote_size: f64, // stablecoin amount a swap needs to count towards the dex price
+    pub dex_window: Duration, // swaps averaged into the dex price
+    pub dex_min_swaps: usize,
+}
+
+impl OracleConfig {
+    pub fn from_env() -> Self {
+        let sources = std::env::var("SOL_PRICE_SOURCES")
+            .unwrap_or_else(|_| "pyth,dex,coingecko".to_string())
+            .split(',')
+            .map(|source| source.trim().to_lowercase())
+            .filter(|source| !source.is_empty())
+            .collect();
+        let strategy = match std::env::var("SOL_PRICE_STRATEGY").unwrap_or_default().to_lowercase().as_str() {
+            "median" => OracleStrategy::Median,
+            _ => OracleStrategy::Priority,
+        };
+        let max_staleness_secs = std::env::var("SOL_PRICE_MAX_STALENESS_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .unwrap_or(60i64);
+        let cache_ttl_secs = std::env::var("SOL_PRICE_CACHE_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .unwrap_or(15)
+            .min(max_staleness_secs);
+        let max_confidence_bps: f64 = std::env::var("SOL_PRICE_MAX_CONFIDENCE_BPS")
+            .ok()
+            .and_then(|bps| bps.parse().ok())
+            .unwrap_or(200.0);
+        let dex_min_quote_size = std::env::var("DEX_SOL_PRICE_MIN_QUOTE_USD")
+            .ok()
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(100.0);
+        let dex_window_secs = std::env::var("DEX_SOL_PRICE_WINDOW_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .unwrap_or(60);
+        let dex_min_swaps = std::env::var("DEX_SOL_PRICE_MIN_SWAPS")
+            .ok()
+            .and_then(|swaps| swaps.parse().ok())
+            .unwrap_or(3)
+            .max(1);
+        Self {
+            sources,
+            strategy,
+            max_staleness: Duration::seconds(max_staleness_secs),
+            cache_ttl: Duration::seconds(cache_ttl_secs),
+            max_confidence_ratio: max_confidence_bps / 10_000.0,
+            dex_min_quote_size,
+            dex_window: Duration::seconds(dex_window_secs),
+            dex_min_swaps,
+        }
+    }
+
+    pub fn is_fresh(&self, published_at: DateTime<Utc>) -> bool {
+        Utc::now().signed_duration_since(published_at) <= self.max_staleness
+    }
+
+    pub fn accepts(&self, quote: &OracleQuote) -> bool {
+        if quote.price <= 0.0 || !quote.price.is_finite() {
+            println!("Rejected {} quote with invalid price {}", quote.source, quote.price);
+            return false;
+        }
+        if !self.is_fresh(quote.published_at) {
+            println!("Rejected stale {} quote published at {}", quote.source, quote.published_at);
+            return false;
+        }
+        if let Some(confidence) = quote.confidence
+            && confidence / quote.price > self.max_confidence_ratio {
+            println!("Rejected {} quote, confidence {} too wide for price {}", quote.source, confidence, quote.price);
+            return false;
+        }
+        true
+    }
+
+    // reduces the accepted quotes to a single price based on the configured strategy
+    pub fn select(&self, quotes: &[OracleQuote]) -> Option<f64> {
+        match self.strategy {
+            OracleStrategy::Priority => quotes.first().map(|quote| quote.price),
+            OracleStrategy::Median => {
+                if quotes.is_empty() {
+                    return None;
+                }
+                let mut prices: Vec<f64> = quotes.iter().map(|quote| quote.price).collect();
+                prices.sort_by(|a, b| a.total_cmp(b));
+                let mid = prices.len() / 2;
+                if prices.len().is_multiple_of(2) {
+                    Some((prices[mid - 1] + prices[mid]) / 2.0)
+                } else {
+                    Some(prices[mid])
+                }
+            }
+        }
+    }
+}
+
+/// Recent stablecoin swaps behind the dex quote.
+///
+/// Swaps below the minimum size are ignored and the stored price is the volume weighted average of
+/// the window, so one dust or skewed swap cannot set the SOL price on its own.
+#[derive(Debug, Default)]
+pub struct DexPriceWindow {
+    swaps: VecDeque<(DateTime<Utc>, f64, f64)>, // (seen at, price, stablecoin size)
+    last_stored: Option<DateTime<Utc>>,
+}
+
+impl DexPriceWindow {
+    // returns the averaged price when enough swaps are in the window and the last store is old enough
+    pub fn record(&mut self, config: &OracleConfig, price: f64, quote_size: f64, now: DateTime<Utc>) -> Option<f64> {
+        if !price.is_finite() || price <= 0.0 || quote_size < config.dex_min_quote_size {
+            return None;
+        }
+        self.swaps.push_back((now, price, quote_size));
+        while self.swaps.front().is_some_and(|(seen_at, _, _)| now.signed_duration_since(*seen_at) > config.dex_window) {
+            self.swaps.pop_front();
+        }
+        if self.swaps.len() < config.dex_min_swaps {
+            return None;
+        }
+        if self.last_stored.is_some_and(|stored_at| now.signed_duration_since(stored_at) < Duration::seconds(DEX_PRICE_STORE_INTERVAL_SECS)) {
+            return None;
+        }
+        let volume: f64 = self.swaps.iter().map(|(_, _, size)| size).sum();
+        let weighted: f64 = self.swaps.iter().map(|(_, price, size)| price * size).sum();
+        self.last_stored = Some(now);
+        Some(weighted / volume)
+    }
+}
+
+#[derive(Debug)]
+pub struct CoinGeckoOracle {
+    http_client: Client,
+}
+
+impl Default for CoinGeckoOracle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CoinGeckoOracle {
+    pub fn new() -> Self {
+        Self { http_client: Client::new() } // timeouts are enforced by the outbound guard
+    }
+}
+
+#[async_trait]
+impl PriceOracle for CoinGeckoOracle {
+    fn name(&self) -> &'static str {
+        "coingecko"
+    }
+
+    async fn fetch_sol_price(&self) -> Result<OracleQuote, anyhow::Error> {
+        let url = "https://api.coingecko.com/api/v3/simple/price?ids=solana&vs_currencies=usd&include_last_updated_at=true";
+        let response: Value = OUTBOUND
+            .call("api.coingecko.com", || async {
+                self.http_client
+                    .get(url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await
+            })
+            .await?;
+
+        let price = response["solana"]["usd"]
+            .as_f64()
+            .ok_or_else(|| anyhow::anyhow!("Error parsing sol value from response"))?;
+        let published_at = response["solana"]["last_updated_at"]
+            .as_i64()
+            .and_then(|ts| Utc.timestamp_opt(ts, 0).single())
+            .unwrap_or_else(Utc::now);
+
+        Ok(OracleQuote {
+            source: self.name().to_string(),
+            price,
+            confidence: None,
+            published_at,
+        })
+    }
+}
+
+// reads the latest pyth quote that the grpc server decodes from the price update account
+#[derive(Debug)]
+pub struct PythOracle {
+    token_manager: TokenSymbolManager,
+}
+
+impl PythOracle {
+    pub fn new(token_manager: TokenSymbolManager) -> Self {
+        Self { token_manager }
+    }
+}
+
+#[async_trait]
+impl PriceOracle for PythOracle {
+    fn name(&self) -> &'static str {
+        "pyth"
+    }
+
+    async fn fetch_sol_price(&self) -> Result<OracleQuote, anyhow::Error> {
+        self.token_manager
+            .get_oracle_quote(self.name())
+            .await
+            .ok_or_else(|| anyhow::anyhow!("No pyth quote received from the account subscription yet"))
+    }
+}
+
+// reads the SOL price implied by SOL/USDC and SOL/USDT swaps that the txn worker observes
+#[derive(Debug)]
+pub struct DexOracle {
+    token_manager: TokenSymbolManager,
+}
+
+impl DexOracle {
+    pub fn new(token_manager: TokenSymbolManager) -> Self {
+        Self { token_manager }
+    }
+}
+
+#[async_trait]
+impl PriceOracle for DexOracle {
+    fn name(&self) -> &'static str {
+        "dex"
+    }
+
+    async fn fetch_sol_price(&self) -> Result<OracleQuote, anyhow::Error> {
+        self.token_manager
+            .get_oracle_quote(self.name())
+            .await
+            .ok_or_else(|| anyhow::anyhow!("No stablecoin swap observed yet for the dex price"))
+    }
+}
+
+pub fn build_oracles(config: &OracleConfig) -> Vec<Box<dyn PriceOracle>> {
+    let mut oracles: Vec<Box<dyn PriceOracle>> = Vec::new();
+    for source in &config.sources {
+        match source.as_str() {
+            "coingecko" => oracles.push(Box::new(CoinGeckoOracle::new())),
+            "pyth" => oracles.push(Box::new(PythOracle::new(
+                TokenSymbolManager::new().expect("Error creating token manager for pyth oracle"),
+            ))),
+            "dex" => oracles.push(Box::new(DexOracle::new(
+                TokenSymbolManager::new().expect("Error creating token manager for dex oracle"),
+            ))),
+            unknown => println!("⚠️ Ignoring unknown SOL price source : {}", unknown),
+        }
+    }
+    oracles
+}
+
+/// Decodes a pyth `PriceUpdateV2` account (pull oracle receiver program) into a quote.
+pub fn decode_pyth_price_update(data: &[u8]) -> Result<OracleQuote, anyhow::Error> {
+    if data.len() < 8 || data[..8] != PRICE_UPDATE_V2_DISCRIMINATOR {
+        return Err(anyhow::anyhow!("account is not a pyth PriceUpdateV2 account"));
+    }
+    // discriminator (8) + write authority (32), then the borsh encoded verification level
+    let mut offset = 40;
+    offset += match data.get(offset) {
+        Some(0) => 2, // Partial { num_signatures: u8 }
+        Some(1) => 1, // Full
+        _ => return Err(anyhow::anyhow!("unknown pyth verification level")),
+    };
+    offset += 32; // feed id
+
+    let price = read_i64(data, offset)?;
+    let conf = read_u64(data, offset + 8)?;
+    let exponent = read_i32(data, offset + 16)?;
+    let publish_time = read_i64(data, offset + 20)?;
+
+    let scale = 10f64.powi(exponent);
+    let published_at = Utc
+        .timestamp_opt(publish_time, 0)
+        .single()
+        .ok_or_else(|| anyhow::anyhow!("invalid pyth publish time {}", publish_time))?;
+
+    Ok(OracleQuote {
+        source: "pyth".to_string(),
+        price: price as f64 * scale,
+        confidence: Some(conf as f64 * scale),
+        published_at,
+    })
+}
+
+fn read_bytes<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N], anyhow::Error> {
+    data.get(offset..offset + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow::anyhow!("pyth account data too short"))
+}
+
+fn read_i64(data: &[u8], offset: usize) -> Result<i64, anyhow::Error> {
+    Ok(i64::from_le_bytes(read_bytes(data, offset)?))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, anyhow::Error> {
+    Ok(u64::from_le_bytes(read_bytes(data, offset)?))
+}
+
+fn read_i32(data: &[u8], offset: usize) -> Result<i32, anyhow::Error> {
+    Ok(i32::from_le_bytes(read_bytes(data, offset)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(strategy: OracleStrategy) -> OracleConfig {
+        OracleConfig {
+            sources: vec!["pyth".to_string(), "dex".to_string(), "coingecko".to_string()],
+            strategy,
+            max_staleness: Duration::seconds(60),
+            cache_ttl: Duration::seconds(15),
+            max_confidence_ratio: 0.02,
+            dex_min_quote_size: 100.0,
+            dex_window: Duration::seconds(60),
+            dex_min_swaps: 3,
+        }
+    }
+
+    fn quote(source: &str, price: f64, confidence: Option<f64>, age_secs: i64) -> OracleQuote {
+        OracleQuote {
+            source: source.to_string(),
+            price,
+            confidence,
+            published_at: Utc::now() - Duration::seconds(age_secs),
+        }
+    }
+
+    // a PriceUpdateV2 account laid out the way the receiver program writes it
+    fn price_update(verification: &[u8], price: i64, conf: u64, exponent: i32, publish_time: i64) -> Vec<u8> {
+        let mut data = PRICE_UPDATE_V2_DISCRIMINATOR.to_vec();
+        data.extend([7u8; 32]); // write authority
+        data.extend(verification);
+        data.extend([9u8; 32]); // feed id
+        data.extend(price.to_le_bytes());
+        data.extend(conf.to_le_bytes());
+        data.extend(exponent.to_le_bytes());
+        data.extend(publish_time.to_le_bytes());
+        data.extend(publish_time.to_le_bytes()); // prev publish time
+        data.extend(price.to_le_bytes()); // ema price
+        data.extend(conf.to_le_bytes()); // ema conf
+        data.extend(42u64.to_le_bytes()); // posted slot
+        data
+    }
+
+    #[test]
+    fn decodes_fully_verified_price_update() {
+        let data = price_update(&[1], 15_012_345_678, 7_500_000, -8, 1_760_918_400);
+        let quote = decode_pyth_price_update(&data).expect("valid account");
+
+        assert_eq!(quote.source, "pyth");
+        assert!((quote.price - 150.12345678).abs() < 1e-9);
+        assert!((quote.confidence.unwrap() - 0.075).abs() < 1e-12);
+        assert_eq!(quote.published_at.timestamp(), 1_760_918_400);
+    }
+
+    #[test]
+    fn decodes_partially_verified_price_update() {
+        let data = price_update(&[0, 3], 9_950, 5, -2, 1_760_918_401);
+        let quote = decode_pyth_price_update(&data).expect("valid account");
+
+        assert!((quote.price - 99.5).abs() < 1e-9);
+        assert!((quote.confidence.unwrap() - 0.05).abs() < 1e-12);
+        assert_eq!(quote.published_at.timestamp(), 1_760_918_401);
+    }
+
+    #[test]
+    fn rejects_foreign_and_truncated_accounts() {
+        let mut foreign = price_update(&[1], 100, 1, 0, 1_760_918_400);
+        foreign[0] ^= 0xff;
+        assert!(decode_pyth_price_update(&foreign).is_err());
+
+        let unknown_level = price_update(&[2], 100, 1, 0, 1_760_918_400);
+        assert!(decode_pyth_price_update(&unknown_level).is_err());
+
+        let full = price_update(&[1], 100, 1, 0, 1_760_918_400);
+        assert!(decode_pyth_price_update(&full[..100]).is_err());
+    }
+
+    #[test]
+    fn median_of_odd_and_even_quote_counts() {
+        let config = config(OracleStrategy::Median);
+        let odd = [quote("pyth", 151.0, None, 0), quote("dex", 149.0, None, 0), quote("coingecko", 150.0, None, 0)];
+        assert_eq!(config.select(&odd), Some(150.0));
+
+        let even = [quote("pyth", 151.0, None, 0), quote("dex", 149.0, None, 0), quote("coingecko", 150.0, None, 0), quote("dex", 160.0, None, 0)];
+        assert_eq!(config.select(&even), Some(150.5));
+
+        assert_eq!(config.select(&[]), None);
+    }
+
+    #[test]
+    fn priority_takes_the_first_quote() {
+        let config = config(OracleStrategy::Priority);
+        let quotes = [quote("dex", 149.0, None, 0), quote("coingecko", 150.0, None, 0)];
+        assert_eq!(config.select(&quotes), Some(149.0));
+    }
+
+    #[test]
+    fn rejects_stale_wide_and_invalid_quotes() {
+        let config = config(OracleStrategy::Priority);
+
+        assert!(config.accepts(&quote("pyth", 150.0, Some(1.5), 10)));
+        assert!(!config.accepts(&quote("pyth", 150.0, Some(1.5), 61)));
+        assert!(!config.accepts(&quote("pyth", 150.0, Some(3.5), 0)));
+        assert!(!config.accepts(&quote("coingecko", 0.0, None, 0)));
+        assert!(!config.accepts(&quote("coingecko", f64::NAN, None, 0)));
+    }
+
+    #[test]
+    fn dex_window_ignores_dust_and_averages_by_size() {
+        let config = config(OracleStrategy::Priority);
+        let mut window = DexPriceWindow::default();
+        let t0 = Utc::now();
+
+        assert_eq!(window.record(&config, 10.0, 5.0, t0), None); // dust
+        assert_eq!(window.record(&config, 150.0, 1_000.0, t0), None);
+        assert_eq!(window.record(&config, 152.0, 1_000.0, t0), None);
+        let price = window.record(&config, 149.0, 2_000.0, t0).expect("three swaps in the window");
+        assert!((price - 150.0).abs() < 1e-9);
+
+        // stored at most once a second
+        assert_eq!(window.record(&config, 151.0, 1_000.0, t0), None);
+
+        // earlier swaps leave the window
+        let later = t0 + Duration::seconds(120);
+        assert_eq!(window.record(&config, 160.0, 1_000.0, later), None);
+    }
+}
@@ -1,12 +1,6 @@
-use anyhow::Context;
-use chrono::{DateTime, Utc, Duration};
-use reqwest::Client;
-use serde_json::Value;
-use solana_program::{pubkey::Pubkey};
-use mpl_token_metadata::{programs::MPL_TOKEN_METADATA_ID, accounts::Metadata};
-use crate::{redis::token_symbol_manager::TokenSymbolManager, types::price::TokenInfo};
-use std::str::FromStr;
-use solana_client::rpc_client::RpcClient;
+use chrono::{DateTime, Utc};
+use tokio::sync::Mutex;
+use crate::{redis::{pubsub_manager::PubSubManager, token_symbol_manager::TokenSymbolManager}, services::{metadata_resolver::MetadataResolver, price_oracle::{build_oracles, DexPriceWindow, OracleConfig, OracleStrategy, PriceOracle}}, types::price::{OracleQuote, TokenInfo}};
 
 #[derive(Clone, Debug)]
 struct SolPrice {
@@ -16,23 +10,33 @@ struct SolPrice {
 
 pub struct PriceService {
     token_manager : TokenSymbolManager,
-    rpc_client : RpcClient
+    metadata_resolver : MetadataResolver,
+    oracles : Vec<Box<dyn PriceOracle>>,
+    oracle_config : OracleConfig,
+    dex_window : Mutex<DexPriceWindow>
 }
 
 impl std::fmt::Debug for PriceService {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("PriceService")
             .field("token_manager", &self.token_manager)
-            .field("rpc_client", &"RpcClient")
+            .field("metadata_resolver", &self.metadata_resolver)
+            .field("oracles", &self.oracles.iter().map(|oracle| oracle.name()).collect::<Vec<_>>())
+            .field("oracle_config", &self.oracle_config)
+            .field("dex_window", &self.dex_window)
             .finish()
     }
 }
 
 impl PriceService {
-    pub fn new(token_manager : TokenSymbolManager) -> Self {
+    pub fn new(token_manager : TokenSymbolManager, pubsub_manager : PubSubManager) -> Self {
+        let oracle_config = OracleConfig::from_env();
         Self {
+            metadata_resolver : MetadataResolver::new(token_manager.clone(), pubsub_manager),
             token_manager,
-            rpc_client : RpcClient::new(std::env::var("HELIUS_URL").expect("Helius url not present in env"))
+            oracles : build_oracles(&oracle_config),
+            oracle_config,
+            dex_window : Mutex::new(DexPriceWindow::default())
         }
     }
 
@@ -44,130 +48,96 @@ impl PriceService {
            Some(SolPrice { sol_usd: sol_info.sol_price, last_updated: sol_info.last_updated })
         } else {
             println!("Got no SOL value from redis");
-          return None;  
+          None
         }
         
     }
 
     pub async fn get_sol_price(&self) -> Option<f64> {
-        if let Some(cached_price) = self.get_cache_price().await {
-            if Utc::now().signed_duration_since(cached_price.last_updated) < Duration::seconds(300) {
-                return Some(cached_price.sol_usd);
-            }
+        if let Some(cached_price) = self.get_cache_price().await
+            && Utc::now().signed_duration_since(cached_price.last_updated) < self.oracle_config.cache_ttl {
+            return Some(cached_price.sol_usd);
         }
 
         match self.fetch_price().await {
             Ok(sol_price) => Some(sol_price),
             Err(e) => {
                 println!("Error fetching sol price: {}", e);
-                self.get_cache_price().await.map(|cached| cached.sol_usd)
+                // only fall back to the cache while it is still within the staleness threshold
+                match self.get_cache_price().await {
+                    Some(cached) if self.oracle_config.is_fresh(cached.last_updated) => Some(cached.sol_usd),
+                    Some(cached) => {
+                        println!("Cached SOL price from {} is stale, refusing to use it", cached.last_updated);
+                        None
+                    }
+                    None => None,
+                }
             }
         }
     }
 
     async fn fetch_price(&self) -> Result<f64, anyhow::Error> {
-        let url = "https://api.coingecko.com/api/v3/simple/price?ids=solana&vs_currencies=usd";
-
-        let client = Client::new();
-        let response: Value = client
-            .get(url)
-            .send()
-            .await?
-            .json()
-            .await?;
+        let mut quotes = Vec::new();
+        for oracle in &self.oracles {
+            match oracle.fetch_sol_price().await {
+                Ok(quote) if self.oracle_config.accepts(&quote) => {
+                    quotes.push(quote);
+                    if self.oracle_config.strategy == OracleStrategy::Priority {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => println!("SOL price source {} failed : {}", oracle.name(), e),
+            }
+        }
 
-        let sol_price = response["solana"]["usd"]
-            .as_f64()
-            .ok_or_else(|| anyhow::anyhow!("Error parsing sol value from response"))?;
+        let sol_price = self.oracle_config
+            .select(&quotes)
+            .ok_or_else(|| anyhow::anyhow!("No SOL price source returned a fresh and confident quote"))?;
 
         self.token_manager.store_sol_value(sol_price).await?;
-        println!("Successfully stored SOL price in redis");
+        println!("Successfully stored SOL price in redis from {} source(s)", quotes.len());
 
         Ok(sol_price)
     }
 
-    pub async fn get_mint_info(&self, mint_address: &String) -> Option<TokenInfo>{
-        let token_info  =  self.token_manager.get_mint_info(mint_address).await;
-           
-       match token_info {
-            Some(info) => Some(info),
-            None => {
-                println!("Token not in cache, fetching from blockchain for mint: {}", mint_address);
-                match self.parse_metadata_pda_data(mint_address.clone()).await {
-                    Ok(Some(info)) => Some(info),
-                    Ok(None) => {
-                        println!("No metadata found on-chain for mint: {}", mint_address);
-                        None
-                    },
-                    Err(e) => {
-                        println!("Error fetching metadata for mint {}: {}", mint_address, e);
-                        None
-                    }
-                }
-            }
+    // quote_size is the stablecoin side of the swap, the quote is only stored once the window is averaged
+    pub async fn record_dex_sol_price(&self, sol_price: f64, quote_size: f64) {
+        let Some(price) = self.dex_window.lock().await.record(&self.oracle_config, sol_price, quote_size, Utc::now()) else {
+            return;
+        };
+        let quote = OracleQuote {
+            source: "dex".to_string(),
+            price,
+            confidence: None,
+            published_at: Utc::now(),
+        };
+        if let Err(e) = self.token_manager.store_oracle_quote(&quote).await {
+            println!("Error storing dex SOL price : {}", e);
         }
     }
 
-    pub fn get_metadata_pda_address(
-        &self,
-        mint_address: &str,
-    ) -> Result<Pubkey, anyhow::Error> {
-        let mint_pubkey = Pubkey::from_str(mint_address).context("Error occured while parsing pubkey")?;
-        let mpl_program_id = Pubkey::new_from_array(MPL_TOKEN_METADATA_ID.to_bytes());
-        let meta_seeds = &[
-            b"metadata",
-            MPL_TOKEN_METADATA_ID.as_ref(),
-            mint_pubkey.as_ref(),
-        ];
-        let (metadata_pda, _) = Pubkey::find_program_address(meta_seeds, &mpl_program_id);
-        Ok(metadata_pda)
-    }
-
-     fn get_metadata_pda_data(
-        &self,
-        mint_address: String,
-    ) -> Result<Option<Vec<u8>>, anyhow::Error> {
-        let metadata_pda = self.get_metadata_pda_address(&mint_address)?;
-
-        match self.rpc_client.get_account(&metadata_pda) {
-            Ok(account) => {
-                println!("Metadata account found");
-                println!("Data length {} bytes", account.data.len());
-
-                let mpl_program_id = Pubkey::new_from_array(MPL_TOKEN_METADATA_ID.to_bytes());
-                if account.owner == mpl_program_id {
-                    Ok(Some(account.data)) // we're returning vector of bytes
-                } else {
-                    println!("But account not owned by metaplex program");
-                    Ok(None)
-                }
-            }
-            Err(rpc_error) => {
-                println!("❌ RPC Error fetching metadata account: {:?}", rpc_error);
-                Ok(None)
+    // cache only lookup, misses are handed to the resolver so the caller never waits on RPC
+    pub async fn get_cached_mint_info(&self, mint_address: &String) -> Option<TokenInfo>{
+        if let Err(e) = self.token_manager.track_mint(mint_address).await {
+            println!("Error tracking mint {} for metadata refresh : {}", mint_address, e);
+        }
+        let token_info = self.token_manager.get_mint_info(mint_address).await;
+        if token_info.is_none() {
+            if self.token_manager.is_metadata_missing(mint_address).await {
+                println!("Mint {} is negatively cached, skipping metadata lookup", mint_address);
+                return None;
             }
+            println!("Token not in cache, queued metadata lookup for mint: {}", mint_address);
+            self.metadata_resolver.request(mint_address).await;
         }
+        token_info
     }
 
-    pub async fn parse_metadata_pda_data(
-        &self,
-        mint_address: String
-    ) -> Result<Option<TokenInfo>, anyhow::Error> {
-        let metadata_account_data = match self.get_metadata_pda_data(mint_address.clone()) {
-            Ok(Some(data_byte)) => data_byte, // the return type is result of option, so we check for both some and none
-            Ok(None) => return Ok(None),
-            Err(e) => return Err(e),
-        };
-        println!("📋 Parsing metadata account...");
-
-        match Metadata::safe_deserialize(&metadata_account_data) {
-            Ok(metadeta) => {
-                self.token_manager.create_mint_info(mint_address, metadeta.symbol.clone().trim().to_string(), metadeta.name.clone().trim().to_string()).await.expect("Error saving mint info to redis cache");
-                return Ok(Some(TokenInfo { token_symbol: metadeta.symbol, token_name: metadeta.name }));
-                }
-            Err(_) => {
-                println!("no metadata found for the mint : {}", mint_address);
-                Ok(None)}
-        }                
+    pub async fn get_mint_info(&self, mint_address: &String) -> Option<TokenInfo>{
+        match self.token_manager.get_mint_info(mint_address).await {
+            Some(info) => Some(info),
+            None => self.metadata_resolver.resolve(mint_address).await,
+        }
     }
 }
@@ -0,0 +1,117 @@
+use chrono::Utc;
+
+use crate::{
+    redis::{
+        leaderboard_manager::LeaderboardManager, market_registry_manager::MarketRegistryManager,
+        metric_and_ohlcv_manager::{PeriodStats, TimeFrame}, pubsub_manager::PubSubManager,
+    },
+    types::ranking::{LeaderboardKind, LeaderboardUpdate},
+};
+
+const BOT_TXNS_PER_MAKER: f64 = 4.0; // above this many trades per wallet volume is treated as mostly bot churn
+const PUBLISHED_ENTRIES: isize = 50;
+
+// the stats of one pair for one window tick, collected by the metrics scheduler
+#[derive(Debug, Clone)]
+pub struct WindowSnapshot {
+    pub token_pair: String,
+    pub price_change: f64,
+    pub current: PeriodStats,
+    pub previous: PeriodStats,
+}
+
+/// Trending score of a pair over a window.
+///
+/// Volume is damped when wallets trade far more often than a person would, then weighted by how fast
+/// volume and makers grow against the previous window, the buy share and the price change. Growth is
+/// neutral when the previous window has no data (new pair or expired buckets).
+pub fn trending_score(current: &PeriodStats, previous: &PeriodStats, price_change: f64) -> f64 {
+    if current.txns == 0 {
+        return 0.0;
+    }
+    let makers = current.makers.max(1) as f64;
+    let txns_per_maker = current.txns as f64 / makers;
+    let bot_damping = (BOT_TXNS_PER_MAKER / txns_per_maker).min(1.0);
+    let organic_volume = current.volume * bot_damping;
+
+    let growth = |current: f64, previous: f64| {
+        if previous <= 0.0 { 1.0 } else { (current / previous).clamp(0.25, 4.0) }
+    };
+    let volume_acceleration = growth(current.volume, previous.volume);
+    let maker_growth = growth(current.makers as f64, previous.makers as f64);
+    let buy_ratio = current.buys as f64 / current.txns as f64;
+    let price_factor = 1.0 + price_change.clamp(-0.9, 3.0) * 0.5;
+
+    (1.0 + organic_volume).ln()
+        * (2.0 + makers).ln()
+        * volume_acceleration.sqrt()
+        * maker_growth.sqrt()
+        * (0.5 + buy_ratio)
+        * price_factor
+}
+
+#[derive(Debug)]
+pub struct RankingService {
+    leaderboard_manager: LeaderboardManager,
+    market_registry: MarketRegistryManager,
+    pubsub_manager: PubSubManager,
+}
+
+impl RankingService {
+    pub fn new(market_registry: MarketRegistryManager, pubsub_manager: PubSubManager) -> Self {
+        Self {
+            leaderboard_manager: LeaderboardManager::new().expect("Error creating leaderboard manager"),
+            market_registry,
+            pubsub_manager,
+        }
+    }
+
+    // rebuilds every leaderboard of the window from one scheduler tick and publishes the top entries
+    pub async fn rebuild(&self, timeframe: TimeFrame, snapshots: &[WindowSnapshot]) -> Result<(), anyhow::Error> {
+        let newest = self
+            .market_registry
+            .get_newest_markets(Utc::now().timestamp() - timeframe.to_seconds())
+            .await?;
+
+        for kind in LeaderboardKind::all() {
+            let scores: Vec<(String, f64)> = match kind {
+                LeaderboardKind::Trending => snapshots
+                    .iter()
+                    .map(|snapshot| (snapshot.token_pair.clone(), trending_score(&snapshot.current, &snapshot.previous, snapshot.price_change)))
+                    .filter(|(_, score)| *score > 0.0)
+                    .collect(),
+                LeaderboardKind::Gainers => snapshots
+                    .iter()
+                    .filter(|snapshot| snapshot.price_change > 0.0)
+                    .map(|snapshot| (snapshot.token_pair.clone(), snapshot.price_change))
+                    .collect(),
+                LeaderboardKind::Losers => snapshots
+                    .iter()
+                    .filter(|snapshot| snapshot.price_change < 0.0)
+                    .map(|snapshot| (snapshot.token_pair.clone(), -snapshot.price_change))
+                    .collect(),
+                LeaderboardKind::Volume => snapshots
+                    .iter()
+                    .filter(|snapshot| snapshot.current.volume > 0.0)
+                    .map(|snapshot| (snapshot.token_pair.clone(), snapshot.current.volume))
+                    .collect(),
+                LeaderboardKind::Newest => newest
+                    .iter()
+                    .map(|(token_pair, first_seen)| (token_pair.clone(), *first_seen as f64))
+                    .collect(),
+            };
+            self.leaderboard_manager.replace_leaderboard(&timeframe, kind, &scores).await?;
+
+            let entries = self.leaderboard_manager.get_leaderboard(&timeframe, kind, 0, PUBLISHED_ENTRIES).await?;
+            let leaderboard_update = LeaderboardUpdate {
+                timeframe,
+                kind,
+                entries,
+                updated_at: Utc::now().timestamp(),
+            };
+            self.pubsub_manager.publish_leaderboard_update(leaderboard_update).await?;
+        }
+        println!("Rebuilt {} leaderboards from {} pairs", timeframe.label(), snapshots.len());
+        Ok(())
+    }
+}
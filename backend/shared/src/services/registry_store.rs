@@ -0,0 +1,309 @@
+use std::collections::HashSet;
+
+use chrono::Utc;
+use sea_orm::{sea_query::OnConflict, ActiveValue::Set, ColumnTrait, Database, DatabaseConnection, EntityTrait, QueryFilter};
+
+use crate::{
+    entities::{alerts, pools, tokens},
+    redis::{alert_manager::AlertManager, pool_manager::PoolManager, token_symbol_manager::TokenSymbolManager},
+    types::{
+        alert::AlertRule,
+        pool::{PoolInfo, PoolReserves},
+        price::{TokenMetadata, TokenSupply},
+    },
+};
+
+const FLUSH_BATCH_SIZE: usize = 500;
+
+fn to_datetime(timestamp: i64) -> chrono::NaiveDateTime {
+    chrono::DateTime::from_timestamp(timestamp, 0).unwrap_or_default().naive_utc()
+}
+
+fn token_model(mint: &str, metadata: Option<TokenMetadata>, supply: Option<TokenSupply>, now: chrono::NaiveDateTime) -> tokens::ActiveModel {
+    let metadata = metadata.unwrap_or_default();
+    let decimals = metadata.decimals.or(supply.as_ref().map(|supply| supply.decimals));
+    tokens::ActiveModel {
+        mint: Set(mint.to_string()),
+        symbol: Set(metadata.symbol),
+        name: Set(metadata.name),
+        uri: Set(metadata.uri),
+        image: Set(metadata.image),
+        description: Set(metadata.description),
+        website: Set(metadata.website),
+        twitter: Set(metadata.twitter),
+        telegram: Set(metadata.telegram),
+        discord: Set(metadata.discord),
+        decimals: Set(decimals.map(i16::from)),
+        total_supply: Set(supply.as_ref().map(|supply| supply.total_supply)),
+        circulating_supply: Set(supply.as_ref().map(|supply| supply.circulating_supply)),
+        update_authority: Set(metadata.update_authority),
+        mint_authority: Set(metadata.mint_authority),
+        freeze_authority: Set(metadata.freeze_authority),
+        created_at: Set(now),
+        updated_at: Set(now),
+    }
+}
+
+fn pool_model(pool: PoolInfo, reserves: PoolReserves, now: chrono::NaiveDateTime) -> pools::ActiveModel {
+    pools::ActiveModel {
+        pool_id: Set(pool.pool_id),
+        pool_owner: Set(pool.pool_owner),
+        program_id: Set(pool.program_id),
+        dex_tag: Set(pool.dex_tag),
+        token_pair: Set(pool.token_pair),
+        token_mint: Set(pool.token_mint),
+        token_vault: Set(pool.token_vault),
+        sol_vault: Set(pool.sol_vault),
+        token_decimals: Set(pool.token_decimals as i32),
+        sol_decimals: Set(pool.sol_decimals as i32),
+        token_reserve: Set(reserves.token_reserve),
+        sol_reserve: Set(reserves.sol_reserve),
+        token_fees: Set(reserves.token_fees),
+        sol_fees: Set(reserves.sol_fees),
+        liquidity_usd: Set(reserves.liquidity_usd),
+        active_liquidity_usd: Set(reserves.active_liquidity_usd),
+        created_at: Set(now),
+        updated_at: Set(now),
+    }
+}
+
+fn alert_model(rule: AlertRule, last_triggered_at: Option<i64>) -> Result<alerts::ActiveModel, anyhow::Error> {
+    Ok(alerts::ActiveModel {
+        id: Set(rule.id),
+        owner: Set(rule.owner),
+        token_pair: Set(rule.token_pair),
+        condition: Set(serde_json::to_value(&rule.condition)?),
+        webhook_url: Set(rule.webhook_url),
+        cooldown_secs: Set(rule.cooldown_secs),
+        last_triggered_at: Set(last_triggered_at.map(to_datetime)),
+        created_at: Set(to_datetime(rule.created_at)),
+    })
+}
+
+fn alert_rule(model: alerts::Model) -> Result<AlertRule, anyhow::Error> {
+    Ok(AlertRule {
+        id: model.id,
+        owner: model.owner,
+        token_pair: model.token_pair,
+        condition: serde_json::from_value(model.condition)?,
+        webhook_url: model.webhook_url,
+        cooldown_secs: model.cooldown_secs,
+        created_at: model.created_at.and_utc().timestamp(),
+    })
+}
+
+/// Postgres copy of the token, pool and alert registries that are served from redis.
+///
+/// Writers add what they changed to a dirty set per registry, `flush` upserts the current redis
+/// state of those entries. Tokens and pools that expire from redis keep their last row, alert
+/// rules deleted from redis are deleted here too. Entries of a batch that fails to write are put
+/// back in their dirty set for the next flush.
+///
+/// Without DATABASE_URL the dirty sets are drained and nothing is kept.
+pub struct RegistryStore {
+    token_manager: TokenSymbolManager,
+    pool_manager: PoolManager,
+    alert_manager: AlertManager,
+    db: Option<DatabaseConnection>,
+}
+
+impl RegistryStore {
+    pub async fn connect() -> Result<Self, anyhow::Error> {
+        let db = match std::env::var("DATABASE_URL") {
+            Ok(database_url) => {
+                println!("Connecting registry store to PostgreSQL...");
+                Some(Database::connect(&database_url).await?)
+            }
+            Err(_) => {
+                println!("DATABASE_URL not set, tokens, pools and alerts are only kept in redis");
+                None
+            }
+        };
+        Ok(Self {
+            token_manager: TokenSymbolManager::new()?,
+            pool_manager: PoolManager::new()?,
+            alert_manager: AlertManager::new()?,
+            db,
+        })
+    }
+
+    // returns how many tokens, pools and alert rules were written
+    pub async fn flush(&self) -> Result<usize, anyhow::Error> {
+        Ok(self.flush_tokens().await? + self.flush_pools().await? + self.flush_alerts().await?)
+    }
+
+    async fn flush_tokens(&self) -> Result<usize, anyhow::Error> {
+        let mut flushed = 0;
+        loop {
+            let mints = self.token_manager.take_dirty_mints(FLUSH_BATCH_SIZE).await?;
+            if mints.is_empty() {
+                return Ok(flushed);
+            }
+            let Some(db) = &self.db else { continue };
+
+            let now = Utc::now().naive_utc();
+            let mut rows = Vec::with_capacity(mints.len());
+            for mint in &mints {
+                let metadata = self.token_manager.get_token_metadata(mint).await;
+                let supply = self.token_manager.get_token_supply(mint).await;
+                if metadata.is_some() || supply.is_some() {
+                    rows.push(token_model(mint, metadata, supply, now));
+                }
+            }
+            if rows.is_empty() {
+                continue;
+            }
+            let written = rows.len();
+            let result = tokens::Entity::insert_many(rows)
+                .on_conflict(
+                    OnConflict::column(tokens::Column::Mint)
+                        .update_columns([
+                            tokens::Column::Symbol,
+                            tokens::Column::Name,
+                            tokens::Column::Uri,
+                            tokens::Column::Image,
+                            tokens::Column::Description,
+                            tokens::Column::Website,
+                            tokens::Column::Twitter,
+                            tokens::Column::Telegram,
+                            tokens::Column::Discord,
+                            tokens::Column::Decimals,
+                            tokens::Column::TotalSupply,
+                            tokens::Column::CirculatingSupply,
+                            tokens::Column::UpdateAuthority,
+                            tokens::Column::MintAuthority,
+                            tokens::Column::FreezeAuthority,
+                            tokens::Column::UpdatedAt,
+                        ])
+                        .to_owned(),
+                )
+                .exec(db)
+                .await;
+            if let Err(e) = result {
+                self.token_manager.mark_mints_dirty(&mints).await?;
+                return Err(e.into());
+            }
+            flushed += written;
+        }
+    }
+
+    async fn flush_pools(&self) -> Result<usize, anyhow::Error> {
+        let mut flushed = 0;
+        loop {
+            let pool_ids = self.pool_manager.take_dirty_pools(FLUSH_BATCH_SIZE).await?;
+            if pool_ids.is_empty() {
+                return Ok(flushed);
+            }
+            let Some(db) = &self.db else { continue };
+
+            let now = Utc::now().naive_utc();
+            let mut rows = Vec::with_capacity(pool_ids.len());
+            for pool_id in &pool_ids {
+                let Some(pool) = self.pool_manager.get_pool(pool_id).await? else { continue };
+                let reserves = self.pool_manager.get_reserves(pool_id).await?.unwrap_or_default();
+                rows.push(pool_model(pool, reserves, now));
+            }
+            if rows.is_empty() {
+                continue;
+            }
+            let written = rows.len();
+            let result = pools::Entity::insert_many(rows)
+                .on_conflict(
+                    OnConflict::column(pools::Column::PoolId)
+                        .update_columns([
+                            pools::Column::PoolOwner,
+                            pools::Column::ProgramId,
+                            pools::Column::DexTag,
+                            pools::Column::TokenPair,
+                            pools::Column::TokenVault,
+                            pools::Column::SolVault,
+                            pools::Column::TokenReserve,
+                            pools::Column::SolReserve,
+                            pools::Column::TokenFees,
+                            pools::Column::SolFees,
+                            pools::Column::LiquidityUsd,
+                            pools::Column::ActiveLiquidityUsd,
+                            pools::Column::UpdatedAt,
+                        ])
+                        .to_owned(),
+                )
+                .exec(db)
+                .await;
+            if let Err(e) = result {
+                self.pool_manager.mark_pools_dirty(&pool_ids).await?;
+                return Err(e.into());
+            }
+            flushed += written;
+        }
+    }
+
+    async fn flush_alerts(&self) -> Result<usize, anyhow::Error> {
+        let mut flushed = 0;
+        loop {
+            let ids = self.alert_manager.take_dirty_rules(FLUSH_BATCH_SIZE).await?;
+            if ids.is_empty() {
+                return Ok(flushed);
+            }
+            let Some(db) = &self.db else { continue };
+
+            let mut rows = Vec::with_capacity(ids.len());
+            let mut deleted = Vec::new();
+            for id in &ids {
+                match self.alert_manager.get_rule(id).await? {
+                    Some(rule) => rows.push(alert_model(rule, self.alert_manager.get_last_triggered(id).await?)?),
+                    None => deleted.push(id.clone()),
+                }
+            }
+            let written = rows.len() + deleted.len();
+            let result = async {
+                if !rows.is_empty() {
+                    alerts::Entity::insert_many(rows)
+                        .on_conflict(
+                            OnConflict::column(alerts::Column::Id)
+                                .update_columns([
+                                    alerts::Column::TokenPair,
+                                    alerts::Column::Condition,
+                                    alerts::Column::WebhookUrl,
+                                    alerts::Column::CooldownSecs,
+                                    alerts::Column::LastTriggeredAt,
+                                ])
+                                .to_owned(),
+                        )
+                        .exec(db)
+                        .await?;
+                }
+                if !deleted.is_empty() {
+                    alerts::Entity::delete_many().filter(alerts::Column::Id.is_in(deleted)).exec(db).await?;
+                }
+                Ok::<(), sea_orm::DbErr>(())
+            }
+            .await;
+            if let Err(e) = result {
+                self.alert_manager.mark_rules_dirty(&ids).await?;
+                return Err(e.into());
+            }
+            flushed += written;
+        }
+    }
+
+    // puts alert rules stored in postgres but missing from redis back, e.g. after redis lost its data
+    pub async fn restore_alerts(&self) -> Result<usize, anyhow::Error> {
+        let Some(db) = &self.db else { return Ok(0) };
+        let live: HashSet<String> = self.alert_manager.get_rules().await?.into_iter().map(|rule| rule.id).collect();
+        let mut restored = 0;
+        for model in alerts::Entity::find().all(db).await? {
+            if live.contains(&model.id) {
+                continue;
+            }
+            let id = model.id.clone();
+            match alert_rule(model) {
+                Ok(rule) => {
+                    self.alert_manager.save_rule(&rule).await?;
+                    restored += 1;
+                }
+                Err(e) => println!("Skipping unreadable stored alert rule {} : {}", id, e),
+            }
+        }
+        Ok(restored)
+    }
+}
@@ -0,0 +1,164 @@
+use std::{collections::HashSet, str::FromStr, sync::Arc};
+
+use chrono::Utc;
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_request::TokenAccountsFilter};
+use solana_program::pubkey::Pubkey;
+use tokio::sync::{mpsc, Mutex};
+
+use crate::{redis::token_symbol_manager::TokenSymbolManager, services::outbound::{host_of, OUTBOUND}, types::price::{MintAccountInfo, TokenSupply}};
+
+const SUPPLY_TTL_SECS: i64 = 600;
+const INCINERATOR: &str = "1nc1nerator11111111111111111111111111111111";
+
+// serves cached supply to the trade path and refreshes it from RPC in the background
+#[derive(Debug, Clone)]
+pub struct SupplyService {
+    token_manager: TokenSymbolManager,
+    refresh_tx: mpsc::UnboundedSender<String>,
+    in_flight: Arc<Mutex<HashSet<String>>>,
+}
+
+// reads total supply from the mint and subtracts balances held by burn and locker wallets
+struct SupplyFetcher {
+    token_manager: TokenSymbolManager,
+    rpc_client: RpcClient,
+    rpc_host: String,
+    excluded_owners: Vec<Pubkey>,
+}
+
+impl SupplyService {
+    pub fn new(token_manager: TokenSymbolManager) -> Self {
+        let (refresh_tx, mut refresh_rx) = mpsc::unbounded_channel::<String>();
+        let in_flight = Arc::new(Mutex::new(HashSet::new()));
+        let fetcher = SupplyFetcher::new(token_manager.clone());
+        let fetch_in_flight = Arc::clone(&in_flight);
+        tokio::spawn(async move {
+            while let Some(mint_address) = refresh_rx.recv().await {
+                fetcher.refresh(&mint_address).await;
+                fetch_in_flight.lock().await.remove(&mint_address);
+            }
+        });
+        Self { token_manager, refresh_tx, in_flight }
+    }
+
+    // cached supply only, a missing or stale entry (or `force_refresh` after a mint/burn) is refreshed in the background
+    pub async fn get_supply(&self, mint_address: &str, force_refresh: bool) -> Option<TokenSupply> {
+        let cached = self.token_manager.get_token_supply(mint_address).await;
+        let fresh = cached
+            .as_ref()
+            .is_some_and(|supply| Utc::now().timestamp() - supply.updated_at < SUPPLY_TTL_SECS);
+        if force_refresh || !fresh {
+            self.request_refresh(mint_address).await;
+        }
+        cached
+    }
+
+    async fn request_refresh(&self, mint_address: &str) {
+        if !self.in_flight.lock().await.insert(mint_address.to_string()) {
+            return;
+        }
+        if self.refresh_tx.send(mint_address.to_string()).is_err() {
+            println!("Supply refresh stopped, dropping refresh for mint : {}", mint_address);
+            self.in_flight.lock().await.remove(mint_address);
+        }
+    }
+}
+
+/// Applies a supply change read from a mint account update.
+///
+/// Mints and burns move total and circulating supply alike, so circulating supply is shifted by the
+/// change in total and the excluded balances are corrected by the next scheduled refresh.
+pub async fn apply_mint_account(token_manager: &TokenSymbolManager, mint_address: &str, mint_account: &MintAccountInfo) -> Result<(), anyhow::Error> {
+    let Some(cached) = token_manager.get_token_supply(mint_address).await else {
+        return Ok(());
+    };
+    let total_supply = mint_account.supply as f64 / 10f64.powi(mint_account.decimals as i32);
+    if total_supply == cached.total_supply {
+        return Ok(());
+    }
+    let supply = TokenSupply {
+        total_supply,
+        circulating_supply: (cached.circulating_supply + total_supply - cached.total_supply).max(0.0),
+        decimals: mint_account.decimals,
+        updated_at: cached.updated_at,
+    };
+    println!(
+        "Supply of {} changed on chain : total {} -> {}",
+        mint_address, cached.total_supply, supply.total_supply
+    );
+    token_manager.save_token_supply(mint_address, &supply).await
+}
+
+impl SupplyFetcher {
+    fn new(token_manager: TokenSymbolManager) -> Self {
+        // SUPPLY_EXCLUDED_OWNERS lists extra wallets (lockers, team vesting) whose balances are not circulating
+        let mut excluded_owners = vec![Pubkey::from_str(INCINERATOR).expect("invalid incinerator address")];
+        if let Ok(owners) = std::env::var("SUPPLY_EXCLUDED_OWNERS") {
+            for owner in owners.split(',').map(str::trim).filter(|owner| !owner.is_empty()) {
+                match Pubkey::from_str(owner) {
+                    Ok(pubkey) => excluded_owners.push(pubkey),
+                    Err(e) => println!("Ignoring invalid excluded supply owner {} : {}", owner, e),
+                }
+            }
+        }
+        let rpc_url = std::env::var("HELIUS_URL").expect("Helius url not present in env");
+        Self {
+            token_manager,
+            rpc_host: host_of(&rpc_url),
+            rpc_client: RpcClient::new(rpc_url),
+            excluded_owners,
+        }
+    }
+
+    async fn refresh(&self, mint_address: &str) {
+        match self.fetch_supply(mint_address).await {
+            Ok(supply) => {
+                if let Err(e) = self.token_manager.save_token_supply(mint_address, &supply).await {
+                    println!("Error saving supply for mint {} : {}", mint_address, e);
+                }
+            }
+            Err(e) => println!("Error fetching supply for mint {} : {}", mint_address, e),
+        }
+    }
+
+    async fn fetch_supply(&self, mint_address: &str) -> Result<TokenSupply, anyhow::Error> {
+        let mint = Pubkey::from_str(mint_address)?;
+        let token_supply = OUTBOUND
+            .call(&self.rpc_host, || self.rpc_client.get_token_supply(&mint))
+            .await?;
+        let raw_total: u64 = token_supply.amount.parse()?;
+
+        let mut raw_excluded: u64 = 0;
+        for owner in &self.excluded_owners {
+            let accounts = OUTBOUND
+                .call(&self.rpc_host, || {
+                    self.rpc_client.get_token_accounts_by_owner(owner, TokenAccountsFilter::Mint(mint))
+                })
+                .await?;
+            for keyed_account in accounts {
+                // jsonParsed token account, the raw amount lives under info.tokenAmount.amount
+                let account = serde_json::to_value(&keyed_account.account)?;
+                let amount = account["data"]["parsed"]["info"]["tokenAmount"]["amount"]
+                    .as_str()
+                    .and_then(|amount| amount.parse::<u64>().ok())
+                    .unwrap_or(0);
+                raw_excluded = raw_excluded.saturating_add(amount);
+            }
+        }
+
+        let scale = 10f64.powi(token_supply.decimals as i32);
+        let total_supply = raw_total as f64 / scale;
+        let circulating_supply = raw_total.saturating_sub(raw_excluded) as f64 / scale;
+        println!(
+            "Fetched supply for {} : total {} circulating {}",
+            mint_address, total_supply, circulating_supply
+        );
+
+        Ok(TokenSupply {
+            total_supply,
+            circulating_supply,
+            decimals: token_supply.decimals,
+            updated_at: Utc::now().timestamp(),
+        })
+    }
+}
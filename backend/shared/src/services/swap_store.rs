@@ -0,0 +1,194 @@
+use chrono::Utc;
+use sea_orm::{
+    sea_query::OnConflict, ActiveValue::Set, ColumnTrait, Condition, Database, DatabaseConnection, DbErr, EntityTrait, QueryFilter,
+    QueryOrder, QuerySelect, Select,
+};
+
+use crate::{
+    entities::swaps::{self, Column},
+    types::worker::{StructeredTransaction, Type},
+};
+
+fn to_active_model(txn: &StructeredTransaction, now: chrono::NaiveDateTime) -> swaps::ActiveModel {
+    swaps::ActiveModel {
+        signature: Set(txn.signature.clone()),
+        leg: Set(txn.leg as i32),
+        token_pair: Set(txn.token_pair.clone()),
+        token_mint: Set(txn.token_mint.clone()),
+        wallet: Set(txn.owner.clone()),
+        side: Set(match txn.purchase_type {
+            Type::Buy => "buy".to_string(),
+            Type::Sell => "sell".to_string(),
+        }),
+        token_quantity: Set(txn.token_quantity),
+        quote_quantity: Set(txn.quote_quantity),
+        token_price: Set(txn.token_price),
+        usd_value: Set(txn.usd_value),
+        dex_type: Set(txn.dex_type.clone()),
+        dex_tag: Set(txn.dex_tag.clone()),
+        slot: Set(txn.order.slot.min(i64::MAX as u64) as i64),
+        tx_index: Set(txn.order.index.min(i64::MAX as u64) as i64),
+        block_time: Set(txn.date.naive_utc()),
+        created_at: Set(now),
+    }
+}
+
+/// Position of the last swap of a page, `{unix micros}:{signature}:{leg}` on the wire.
+///
+/// Listings are ordered by block time, signature and leg, newest first, so the next page starts right
+/// after this swap even when many swaps share a block time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapCursor {
+    pub block_time: chrono::NaiveDateTime,
+    pub signature: String,
+    pub leg: i32,
+}
+
+impl SwapCursor {
+    pub fn of(swap: &swaps::Model) -> Self {
+        Self {
+            block_time: swap.block_time,
+            signature: swap.signature.clone(),
+            leg: swap.leg,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        format!("{}:{}:{}", self.block_time.and_utc().timestamp_micros(), self.signature, self.leg)
+    }
+
+    pub fn parse(cursor: &str) -> Option<Self> {
+        let mut parts = cursor.splitn(3, ':');
+        let block_time = chrono::DateTime::from_timestamp_micros(parts.next()?.parse().ok()?)?.naive_utc();
+        let signature = parts.next()?.to_string();
+        let leg = parts.next()?.parse().ok()?;
+        Some(Self { block_time, signature, leg })
+    }
+
+    // rows strictly after the cursor in listing order
+    fn after(&self) -> Condition {
+        Condition::any()
+            .add(Column::BlockTime.lt(self.block_time))
+            .add(Condition::all().add(Column::BlockTime.eq(self.block_time)).add(Column::Signature.lt(self.signature.as_str())))
+            .add(
+                Condition::all()
+                    .add(Column::BlockTime.eq(self.block_time))
+                    .add(Column::Signature.eq(self.signature.as_str()))
+                    .add(Column::Leg.lt(self.leg)),
+            )
+    }
+}
+
+// a page of `query` newest first, starting after `before`
+fn page(mut query: Select<swaps::Entity>, before: Option<&SwapCursor>, limit: u64) -> Select<swaps::Entity> {
+    if let Some(before) = before {
+        query = query.filter(before.after());
+    }
+    query
+        .order_by_desc(Column::BlockTime)
+        .order_by_desc(Column::Signature)
+        .order_by_desc(Column::Leg)
+        .limit(limit)
+}
+
+/// Trade history in Postgres, one row per swap keyed by signature and leg.
+pub struct SwapStore {
+    db: DatabaseConnection,
+}
+
+impl SwapStore {
+    pub async fn connect() -> Result<Self, anyhow::Error> {
+        let database_url = std::env::var("DATABASE_URL").map_err(|_| anyhow::anyhow!("DATABASE_URL not set, swaps can't be stored"))?;
+        println!("Connecting swap store to PostgreSQL...");
+        Ok(Self {
+            db: Database::connect(&database_url).await?,
+        })
+    }
+
+    /// Inserts the swaps in one statement and returns how many were new.
+    ///
+    /// Swaps already stored are skipped, so a batch can be retried or redelivered safely.
+    /// Swaps without a signature can't be deduplicated and are left out.
+    pub async fn insert_swaps(&self, txns: &[StructeredTransaction]) -> Result<u64, anyhow::Error> {
+        let now = Utc::now().naive_utc();
+        let rows: Vec<_> = txns.iter().filter(|txn| !txn.signature.is_empty()).map(|txn| to_active_model(txn, now)).collect();
+        if rows.is_empty() {
+            return Ok(0);
+        }
+        let inserted = swaps::Entity::insert_many(rows)
+            .on_conflict(OnConflict::columns([Column::Signature, Column::Leg]).do_nothing().to_owned())
+            .exec_without_returning(&self.db)
+            .await?;
+        Ok(inserted)
+    }
+
+    // the database could not be reached, as opposed to rejecting the swaps
+    pub fn is_unavailable(error: &anyhow::Error) -> bool {
+        matches!(error.downcast_ref::<DbErr>(), Some(DbErr::ConnectionAcquire(_) | DbErr::Conn(_)))
+    }
+
+    // newest swaps of a pair, `before` pages back through older ones
+    pub async fn recent_swaps(&self, token_pair: &str, before: Option<&SwapCursor>, limit: u64) -> Result<Vec<swaps::Model>, anyhow::Error> {
+        let query = swaps::Entity::find().filter(Column::TokenPair.eq(token_pair));
+        Ok(page(query, before, limit).all(&self.db).await?)
+    }
+
+    // newest swaps of a wallet across all pairs
+    pub async fn wallet_swaps(&self, wallet: &str, before: Option<&SwapCursor>, limit: u64) -> Result<Vec<swaps::Model>, anyhow::Error> {
+        let query = swaps::Entity::find().filter(Column::Wallet.eq(wallet));
+        Ok(page(query, before, limit).all(&self.db).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sea_orm::{DbBackend, QueryTrait};
+
+    fn cursor(micros: i64, signature: &str, leg: i32) -> SwapCursor {
+        SwapCursor {
+            block_time: chrono::DateTime::from_timestamp_micros(micros).unwrap().naive_utc(),
+            signature: signature.to_string(),
+            leg,
+        }
+    }
+
+    #[test]
+    fn cursor_round_trips_with_micros() {
+        let cursor = cursor(1_760_918_400_123_456, "5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnb", 2);
+        let encoded = cursor.encode();
+        assert_eq!(encoded, "1760918400123456:5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnb:2");
+        assert_eq!(SwapCursor::parse(&encoded), Some(cursor));
+    }
+
+    #[test]
+    fn cursor_rejects_malformed_input() {
+        assert_eq!(SwapCursor::parse(""), None);
+        assert_eq!(SwapCursor::parse("1760918400123456:sig"), None);
+        assert_eq!(SwapCursor::parse("yesterday:sig:0"), None);
+        assert_eq!(SwapCursor::parse("1760918400123456:sig:first"), None);
+    }
+
+    #[test]
+    fn pages_continue_strictly_after_the_cursor_in_listing_order() {
+        let sql = page(swaps::Entity::find(), Some(&cursor(1_760_918_400_123_456, "5sig", 1)), 50)
+            .build(DbBackend::Postgres)
+            .to_string();
+        let time = "'2025-10-20 00:00:00.123456'";
+        let expected_where = format!(
+            r#"WHERE "swaps"."block_time" < {time} OR ("swaps"."block_time" = {time} AND "swaps"."signature" < '5sig') OR ("swaps"."block_time" = {time} AND "swaps"."signature" = '5sig' AND "swaps"."leg" < 1)"#
+        );
+        assert!(sql.contains(&expected_where), "{}", sql);
+        assert!(
+            sql.ends_with(r#"ORDER BY "swaps"."block_time" DESC, "swaps"."signature" DESC, "swaps"."leg" DESC LIMIT 50"#),
+            "{}",
+            sql
+        );
+    }
+
+    #[test]
+    fn first_page_has_no_cursor_filter() {
+        let sql = page(swaps::Entity::find(), None, 20).build(DbBackend::Postgres).to_string();
+        assert!(!sql.contains("WHERE"), "{}", sql);
+    }
+}
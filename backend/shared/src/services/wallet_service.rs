@@ -0,0 +1,280 @@
+use chrono::Utc;
+
+use crate::{
+    redis::{metric_and_ohlcv_manager::MetricOHLCVManager, token_symbol_manager::TokenSymbolManager, wallet_manager::WalletManager},
+    types::{
+        wallet::{PositionView, TopTrader, TraderSort, WalletPosition, WalletProfile},
+        worker::{StructeredTransaction, Type},
+    },
+};
+
+const DUST_BALANCE: f64 = 1e-9; // a balance below this closes the position
+
+/// Applies one trade to a position at average cost.
+///
+/// Sells only realize against tokens bought while the wallet was tracked; the rest of a sell (tokens
+/// acquired before tracking or by transfer) has no known cost and is counted as volume only.
+pub fn apply_trade(position: &mut WalletPosition, txn: &StructeredTransaction, trade_usd: f64, trade_sol: f64) {
+    let timestamp = txn.date.timestamp();
+    match txn.purchase_type {
+        Type::Buy => {
+            if position.token_balance <= DUST_BALANCE {
+                position.opened_at = Some(timestamp);
+            }
+            position.token_balance += txn.token_quantity;
+            position.cost_basis_usd += trade_usd;
+            position.cost_basis_sol += trade_sol;
+            position.bought_usd += trade_usd;
+            position.buys += 1;
+        }
+        Type::Sell => {
+            position.sold_usd += trade_usd;
+            position.sells += 1;
+            let matched = txn.token_quantity.min(position.token_balance);
+            if matched > 0.0 && txn.token_quantity > 0.0 {
+                let basis_share = matched / position.token_balance;
+                let proceeds_share = matched / txn.token_quantity;
+                let basis_usd = position.cost_basis_usd * basis_share;
+                let basis_sol = position.cost_basis_sol * basis_share;
+                let pnl_usd = trade_usd * proceeds_share - basis_usd;
+                position.realized_pnl_usd += pnl_usd;
+                position.realized_pnl_sol += trade_sol * proceeds_share - basis_sol;
+                if pnl_usd > 0.0 {
+                    position.winning_sells += 1;
+                } else {
+                    position.losing_sells += 1;
+                }
+                position.cost_basis_usd -= basis_usd;
+                position.cost_basis_sol -= basis_sol;
+                position.token_balance -= matched;
+            }
+            if position.token_balance <= DUST_BALANCE {
+                if let Some(opened_at) = position.opened_at.take() {
+                    position.closed_positions += 1;
+                    position.closed_hold_secs += (timestamp - opened_at).max(0);
+                }
+                position.token_balance = 0.0;
+                position.cost_basis_usd = 0.0;
+                position.cost_basis_sol = 0.0;
+            }
+        }
+    }
+    position.last_trade_at = position.last_trade_at.max(timestamp);
+}
+
+#[derive(Debug)]
+pub struct WalletService {
+    wallet_manager: WalletManager,
+    metric_manager: MetricOHLCVManager,
+    token_manager: TokenSymbolManager,
+}
+
+impl WalletService {
+    pub fn new(token_manager: TokenSymbolManager) -> Self {
+        Self {
+            wallet_manager: WalletManager::new().expect("Error creating wallet manager"),
+            metric_manager: MetricOHLCVManager::new().expect("Error creating metric manager for wallets"),
+            token_manager,
+        }
+    }
+
+    pub async fn record_trade(&self, txn: &StructeredTransaction, sol_price: f64) -> Result<(), anyhow::Error> {
+        let trade_usd = match txn.usd_value {
+            Some(usd_value) if usd_value > 0.0 && sol_price > 0.0 => usd_value,
+            _ => return Ok(()),
+        };
+        if txn.owner.is_empty() {
+            return Ok(());
+        }
+        let mut position = self
+            .wallet_manager
+            .get_position(&txn.owner, &txn.token_pair)
+            .await?
+            .unwrap_or_else(|| WalletPosition {
+                wallet: txn.owner.clone(),
+                token_pair: txn.token_pair.clone(),
+                token_mint: txn.token_mint.clone(),
+                ..WalletPosition::default()
+            });
+        apply_trade(&mut position, txn, trade_usd, trade_usd / sol_price);
+        self.wallet_manager.save_position(&position, trade_usd).await
+    }
+
+    pub async fn get_profile(&self, wallet: &str) -> Result<WalletProfile, anyhow::Error> {
+        let sol_price = self.token_manager.get_sol_value().await.map(|sol_info| sol_info.sol_price);
+        let mut positions = Vec::new();
+        for position in self.wallet_manager.get_positions(wallet).await? {
+            positions.push(self.view(position, sol_price).await);
+        }
+        positions.sort_by_key(|entry| std::cmp::Reverse(entry.position.last_trade_at));
+
+        let sum = |value: fn(&PositionView) -> f64| positions.iter().map(value).sum::<f64>();
+        let winning_sells: u64 = positions.iter().map(|view| view.position.winning_sells).sum();
+        let realizing_sells: u64 = positions.iter().map(|view| view.position.winning_sells + view.position.losing_sells).sum();
+        let closed_positions: u64 = positions.iter().map(|view| view.position.closed_positions).sum();
+        let closed_hold_secs: i64 = positions.iter().map(|view| view.position.closed_hold_secs).sum();
+
+        Ok(WalletProfile {
+            wallet: wallet.to_string(),
+            realized_pnl_usd: sum(|view| view.position.realized_pnl_usd),
+            realized_pnl_sol: sum(|view| view.position.realized_pnl_sol),
+            unrealized_pnl_usd: sum(|view| view.unrealized_pnl_usd.unwrap_or(0.0)),
+            unrealized_pnl_sol: sum(|view| view.unrealized_pnl_sol.unwrap_or(0.0)),
+            volume_usd: sum(|view| view.position.bought_usd + view.position.sold_usd),
+            trades: positions.iter().map(|view| view.position.buys + view.position.sells).sum(),
+            win_rate: (realizing_sells > 0).then(|| winning_sells as f64 / realizing_sells as f64),
+            avg_hold_secs: (closed_positions > 0).then(|| closed_hold_secs / closed_positions as i64),
+            positions,
+        })
+    }
+
+    pub async fn get_top_traders(&self, token_pair: &str, sort: TraderSort, limit: isize) -> Result<Vec<TopTrader>, anyhow::Error> {
+        let sol_price = self.token_manager.get_sol_value().await.map(|sol_info| sol_info.sol_price);
+        let mut traders = Vec::new();
+        for (rank, (position, volume_usd)) in self.wallet_manager.get_top_traders(token_pair, sort, limit).await?.into_iter().enumerate() {
+            let position = self.view(position, sol_price).await;
+            traders.push(TopTrader {
+                rank: rank + 1,
+                total_pnl_usd: position.position.realized_pnl_usd + position.unrealized_pnl_usd.unwrap_or(0.0),
+                volume_usd,
+                position,
+            });
+        }
+        Ok(traders)
+    }
+
+    // marks the open part of a position to the market's current price
+    async fn view(&self, position: WalletPosition, sol_price: Option<f64>) -> PositionView {
+        let current_price = self.metric_manager.get_current_price(&position.token_pair).await.ok();
+        let has_balance = position.token_balance > DUST_BALANCE;
+        let market_value_usd = current_price.filter(|_| has_balance).map(|price| position.token_balance * price);
+        let unrealized_pnl_usd = market_value_usd.map(|value| value - position.cost_basis_usd);
+        let unrealized_pnl_sol = market_value_usd
+            .zip(sol_price.filter(|sol_price| *sol_price > 0.0))
+            .map(|(value, sol_price)| value / sol_price - position.cost_basis_sol);
+        PositionView {
+            current_price,
+            avg_entry_price: has_balance.then(|| position.cost_basis_usd / position.token_balance),
+            unrealized_pnl_usd,
+            unrealized_pnl_sol,
+            hold_secs: position.opened_at.map(|opened_at| Utc::now().timestamp() - opened_at),
+            position,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::worker::TradeOrder;
+
+    const T0: i64 = 1_760_918_400;
+
+    fn txn(purchase_type: Type, token_quantity: f64, timestamp: i64) -> StructeredTransaction {
+        StructeredTransaction {
+            date: chrono::DateTime::from_timestamp(timestamp, 0).unwrap(),
+            purchase_type,
+            usd_value: None,
+            token_quantity,
+            quote_quantity: 0.0,
+            token_price: 0.0,
+            token_pair: "BONK/SOL".to_string(),
+            token_name: "Bonk".to_string(),
+            token_mint: "mint".to_string(),
+            supply_changed: false,
+            owner: "wallet".to_string(),
+            dex_type: "Raydium".to_string(),
+            dex_tag: "CPMM".to_string(),
+            order: TradeOrder::default(),
+            signature: String::new(),
+            leg: 0,
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "expected {}, got {}", expected, actual);
+    }
+
+    #[test]
+    fn partial_sells_realize_against_the_average_cost() {
+        let mut position = WalletPosition::default();
+        apply_trade(&mut position, &txn(Type::Buy, 100.0, T0), 100.0, 1.0);
+        apply_trade(&mut position, &txn(Type::Buy, 100.0, T0 + 60), 300.0, 3.0);
+        assert_eq!(position.opened_at, Some(T0));
+
+        // average entry is 2 usd, 40 tokens sold at 3 usd
+        apply_trade(&mut position, &txn(Type::Sell, 40.0, T0 + 120), 120.0, 1.2);
+        assert_close(position.realized_pnl_usd, 40.0);
+        assert_close(position.realized_pnl_sol, 0.4);
+        assert_close(position.token_balance, 160.0);
+        assert_close(position.cost_basis_usd, 320.0);
+        assert_eq!((position.winning_sells, position.losing_sells), (1, 0));
+        assert_eq!(position.closed_positions, 0);
+
+        // the rest at 1 usd
+        apply_trade(&mut position, &txn(Type::Sell, 160.0, T0 + 3600), 160.0, 1.6);
+        assert_close(position.realized_pnl_usd, 40.0 - 160.0);
+        assert_eq!((position.winning_sells, position.losing_sells), (1, 1));
+        assert_eq!((position.buys, position.sells), (2, 2));
+        assert_close(position.bought_usd, 400.0);
+        assert_close(position.sold_usd, 280.0);
+    }
+
+    #[test]
+    fn oversells_only_realize_the_tracked_balance() {
+        let mut position = WalletPosition::default();
+        apply_trade(&mut position, &txn(Type::Buy, 50.0, T0), 50.0, 0.5);
+
+        // half of the sold tokens were acquired before tracking and have no known cost
+        apply_trade(&mut position, &txn(Type::Sell, 100.0, T0 + 60), 150.0, 1.5);
+        assert_close(position.realized_pnl_usd, 25.0);
+        assert_close(position.realized_pnl_sol, 0.25);
+        assert_close(position.sold_usd, 150.0);
+        assert_eq!(position.token_balance, 0.0);
+        assert_eq!(position.closed_positions, 1);
+    }
+
+    #[test]
+    fn sells_without_a_tracked_balance_are_volume_only() {
+        let mut position = WalletPosition::default();
+        apply_trade(&mut position, &txn(Type::Sell, 100.0, T0), 150.0, 1.5);
+
+        assert_eq!(position.realized_pnl_usd, 0.0);
+        assert_eq!((position.winning_sells, position.losing_sells), (0, 0));
+        assert_eq!(position.closed_positions, 0);
+        assert_close(position.sold_usd, 150.0);
+        assert_eq!(position.last_trade_at, T0);
+    }
+
+    #[test]
+    fn dust_left_after_a_sell_closes_the_position() {
+        let mut position = WalletPosition::default();
+        apply_trade(&mut position, &txn(Type::Buy, 100.0, T0), 100.0, 1.0);
+        apply_trade(&mut position, &txn(Type::Sell, 100.0 - 1e-10, T0 + 90), 110.0, 1.1);
+
+        assert_eq!(position.token_balance, 0.0);
+        assert_eq!(position.cost_basis_usd, 0.0);
+        assert_eq!(position.cost_basis_sol, 0.0);
+        assert_eq!(position.opened_at, None);
+        assert_eq!(position.closed_positions, 1);
+        assert_eq!(position.closed_hold_secs, 90);
+    }
+
+    #[test]
+    fn hold_time_sums_closed_positions_and_reopens_on_the_next_buy() {
+        let mut position = WalletPosition::default();
+        apply_trade(&mut position, &txn(Type::Buy, 10.0, T0), 10.0, 0.1);
+        apply_trade(&mut position, &txn(Type::Sell, 10.0, T0 + 600), 12.0, 0.12);
+        apply_trade(&mut position, &txn(Type::Buy, 10.0, T0 + 1000), 10.0, 0.1);
+        assert_eq!(position.opened_at, Some(T0 + 1000));
+
+        apply_trade(&mut position, &txn(Type::Sell, 10.0, T0 + 1300), 9.0, 0.09);
+        assert_eq!(position.closed_positions, 2);
+        assert_eq!(position.closed_hold_secs, 900);
+        assert_close(position.realized_pnl_usd, 1.0);
+
+        // a trade replayed out of order does not move the last trade back
+        apply_trade(&mut position, &txn(Type::Buy, 1.0, T0 + 100), 1.0, 0.01);
+        assert_eq!(position.last_trade_at, T0 + 1300);
+    }
+}
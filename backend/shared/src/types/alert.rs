@@ -0,0 +1,97 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::redis::metric_and_ohlcv_manager::TimeFrame;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AlertCondition {
+    PriceAbove { price: f64 }, // fires when the usd price crosses upwards
+    PriceBelow { price: f64 },
+    PriceChange { timeframe: TimeFrame, percent: f64 }, // absolute move of at least `percent` within the window
+    VolumeSpike { timeframe: TimeFrame, multiplier: f64 }, // window volume against the previous window
+    LargeTrade { min_usd: f64 },
+}
+
+impl AlertCondition {
+    pub fn validate(&self) -> Result<(), String> {
+        let valid = match self {
+            AlertCondition::PriceAbove { price } | AlertCondition::PriceBelow { price } => *price > 0.0,
+            AlertCondition::PriceChange { percent, .. } => *percent > 0.0,
+            AlertCondition::VolumeSpike { multiplier, .. } => *multiplier > 1.0,
+            AlertCondition::LargeTrade { min_usd } => *min_usd > 0.0,
+        };
+        if valid { Ok(()) } else { Err(format!("invalid threshold for {:?}", self)) }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub id: String,
+    pub owner: String, // derived from the owner's bearer token, see `owner_of_token`
+    pub token_pair: String,
+    pub condition: AlertCondition,
+    pub webhook_url: Option<String>,
+    pub cooldown_secs: i64,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewAlertRule {
+    pub token_pair: String,
+    pub condition: AlertCondition,
+    pub webhook_url: Option<String>,
+    pub cooldown_secs: Option<i64>,
+}
+
+const DEFAULT_COOLDOWN_SECS: i64 = 300;
+const MIN_OWNER_TOKEN_LEN: usize = 32;
+
+/// The owner id behind a bearer token, `None` for tokens too short to be unguessable.
+///
+/// Clients generate the token themselves and send it on every alert request and websocket
+/// subscription. Only its hash is stored, so rules can't be read or deleted without the token.
+pub fn owner_of_token(token: &str) -> Option<String> {
+    let token = token.trim();
+    if token.len() < MIN_OWNER_TOKEN_LEN {
+        return None;
+    }
+    Some(Sha256::digest(token.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+impl NewAlertRule {
+    pub fn into_rule(self, owner: String) -> Result<AlertRule, String> {
+        if self.token_pair.trim().is_empty() {
+            return Err("token_pair is required".to_string());
+        }
+        self.condition.validate()?;
+        if let Some(webhook_url) = &self.webhook_url {
+            match reqwest::Url::parse(webhook_url) {
+                Ok(url) if url.scheme() == "https" || url.scheme() == "http" => {}
+                _ => return Err(format!("invalid webhook url {}", webhook_url)),
+            }
+        }
+        let created_at = Utc::now();
+        Ok(AlertRule {
+            id: format!("{:x}{:08x}", created_at.timestamp_millis(), fastrand::u32(..)),
+            owner,
+            token_pair: self.token_pair,
+            condition: self.condition,
+            webhook_url: self.webhook_url,
+            cooldown_secs: self.cooldown_secs.unwrap_or(DEFAULT_COOLDOWN_SECS).max(0),
+            created_at: created_at.timestamp(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertNotification {
+    pub alert_id: String,
+    pub owner: String,
+    pub token_pair: String,
+    pub condition: AlertCondition,
+    pub value: f64, // the price, change, volume ratio or trade size that met the condition
+    pub message: String,
+    pub triggered_at: i64,
+}
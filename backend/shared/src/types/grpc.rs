@@ -1,11 +1,27 @@
 use serde::{Deserialize, Serialize};
 use yellowstone_grpc_proto::solana::storage::confirmed_block::{TokenBalance, UiTokenAmount};
 
+use crate::types::worker::TradeOrder;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TransactionMetadata {
     pub log_messages: Vec<String>,
     pub pre_token_balances: Vec<CustomTokenBalance>,
     pub post_token_balances: Vec<CustomTokenBalance>,
+    #[serde(default)]
+    pub account_keys: Vec<String>, // static keys followed by loaded writable and readonly addresses, indexed by account_index
+    #[serde(default)]
+    pub order: TradeOrder,
+    #[serde(default)]
+    pub signature: String, // base58 transaction signature
+    #[serde(default)]
+    pub dex_instructions: Vec<DexInstruction>, // top level then inner instructions of the supported dex programs
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DexInstruction {
+    pub program_id: String,
+    pub accounts: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Holder {
+    pub owner: String,
+    pub balance: f64,
+    pub share: Option<f64>, // fraction of total supply, None while supply is unknown
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HolderStats {
+    pub mint_address: String,
+    pub holder_count: usize,
+    pub top_holders: Vec<Holder>,
+    pub top10_share: Option<f64>,
+    pub dev_wallet: Option<String>,
+    pub dev_balance: Option<f64>,
+    pub dev_share: Option<f64>,
+    pub snapshot_at: Option<i64>, // last full token account scan, balances since then come from swaps
+}
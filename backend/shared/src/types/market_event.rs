@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::worker::Type;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MarketEventKind {
+    Pump { sigma: f64, change: f64 }, // minute return `sigma` standard deviations above the pair's baseline
+    Dump { sigma: f64, change: f64 },
+    VolumeSpike { ratio: f64, minute_volume: f64 }, // current minute against the rolling minute average
+    WhaleTrade { side: Type, usd_value: f64, wallet: String },
+    LiquidityPull { previous_usd: f64, current_usd: f64, drop: f64 },
+}
+
+impl MarketEventKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            MarketEventKind::Pump { .. } => "pump",
+            MarketEventKind::Dump { .. } => "dump",
+            MarketEventKind::VolumeSpike { .. } => "volume_spike",
+            MarketEventKind::WhaleTrade { .. } => "whale_trade",
+            MarketEventKind::LiquidityPull { .. } => "liquidity_pull",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketEvent {
+    pub token_pair: String,
+    pub event: MarketEventKind,
+    pub price: Option<f64>,
+    pub detected_at: i64,
+}
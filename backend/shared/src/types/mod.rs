@@ -2,4 +2,10 @@ pub mod grpc;
 pub mod worker;
 pub mod price;
 pub mod ohlcv;
-pub mod elasticsearch;
\ No newline at end of file
+pub mod elasticsearch;
+pub mod pool;
+pub mod ranking;
+pub mod holder;
+pub mod wallet;
+pub mod alert;
+pub mod market_event;
\ No newline at end of file
@@ -1,21 +1,91 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug,Serialize, Deserialize)]
+use crate::types::worker::{StructeredTransaction, TradeOrder, Type};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OHLCVcandle{
     pub token_pair : String,
     pub timeframe : String,
     pub timestamp : i64,
-    pub open : f64,
+    pub open : f64, // usd
     pub high : f64,
     pub low : f64,
     pub close : f64,
-    pub volume : f64,
+    pub volume : f64, // base token units
     pub buy_volume : f64,
     pub sell_volume : f64,
-    pub trade_count : u32
+    pub trade_count : u32,
+    #[serde(default)]
+    pub quote_open : f64, // in the quote token of the pair (SOL)
+    #[serde(default)]
+    pub quote_high : f64,
+    #[serde(default)]
+    pub quote_low : f64,
+    #[serde(default)]
+    pub quote_close : f64,
+    #[serde(default)]
+    pub quote_volume : f64,
+    #[serde(default)]
+    pub usd_volume : f64,
+    #[serde(default)]
+    pub first_trade : TradeOrder, // the trades that set open and close
+    #[serde(default)]
+    pub last_trade : TradeOrder,
+    #[serde(default)]
+    pub revision : u32 // bumped whenever a late trade corrects the candle after it closed
+}
+
+// one trade as the candle builders see it
+#[derive(Debug, Clone)]
+pub struct CandleTrade{
+    pub price : f64, // usd
+    pub quote_price : f64,
+    pub quantity : f64,
+    pub quote_quantity : f64,
+    pub usd_value : f64,
+    pub side : Type,
+    pub order : TradeOrder,
+    pub id : String // `{signature}:{leg}`, empty when the message carried no signature
+}
+
+impl CandleTrade {
+    pub fn from_txn(txn : &StructeredTransaction) -> Self{
+        // transactions queued before quote amounts were recorded have no quote price
+        let quote_price = if txn.token_quantity > 0.0 { txn.quote_quantity / txn.token_quantity } else { 0.0 };
+        CandleTrade{
+            price : txn.token_price,
+            quote_price,
+            quantity : txn.token_quantity,
+            quote_quantity : txn.quote_quantity,
+            usd_value : txn.usd_value.unwrap_or(txn.token_price * txn.token_quantity),
+            side : txn.purchase_type.clone(),
+            order : txn.order,
+            id : if txn.signature.is_empty() { String::new() } else { format!("{}:{}", txn.signature, txn.leg) }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CandleDenomination{
+    #[default]
+    Usd,
+    Quote
+}
+
+impl CandleDenomination {
+    pub fn from_label(label : &str) -> Option<CandleDenomination>{
+        match label {
+            "usd" => Some(CandleDenomination::Usd),
+            "quote" | "sol" => Some(CandleDenomination::Quote),
+            _ => None
+        }
+    }
 }
 
-#[derive(Debug)]
+// unix time 0 is a thursday, weekly candles start on monday like most charting libraries
+const WEEK_OFFSET_SECS : i64 = 4 * 86400;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum CandleTimeFrame{
     OneSecond,
     OneMinute,
@@ -27,20 +97,23 @@ pub enum CandleTimeFrame{
     OneWeek
 }
 
-impl CandleTimeFrame {
-    pub fn to_string(&self) -> String{
-        match self{
-            CandleTimeFrame::OneSecond => "1s".to_string(),
-            CandleTimeFrame::OneMinute => "1m".to_string(),
-            CandleTimeFrame::FiveMinutes => "5m".to_string(),
-            CandleTimeFrame::FifteenMinutes => "15m".to_string(),
-            CandleTimeFrame::OneHour => "1h".to_string(),
-            CandleTimeFrame::FourHours => "4h".to_string(),
-            CandleTimeFrame::OneDay => "1d".to_string(),
-            CandleTimeFrame::OneWeek => "1w".to_string(),
-        }
+impl std::fmt::Display for CandleTimeFrame {
+    fn fmt(&self, f : &mut std::fmt::Formatter<'_>) -> std::fmt::Result{
+        let label = match self{
+            CandleTimeFrame::OneSecond => "1s",
+            CandleTimeFrame::OneMinute => "1m",
+            CandleTimeFrame::FiveMinutes => "5m",
+            CandleTimeFrame::FifteenMinutes => "15m",
+            CandleTimeFrame::OneHour => "1h",
+            CandleTimeFrame::FourHours => "4h",
+            CandleTimeFrame::OneDay => "1d",
+            CandleTimeFrame::OneWeek => "1w",
+        };
+        f.write_str(label)
     }
+}
 
+impl CandleTimeFrame {
     pub fn to_seconds(&self) -> i64{
          match self{
             CandleTimeFrame::OneSecond => 1,
@@ -65,16 +138,376 @@ impl CandleTimeFrame {
             CandleTimeFrame::OneWeek
         ]
     }
+    // how long the live buckets stay in redis, older chart ranges are read from postgres
+    pub fn redis_retention_secs(&self) -> i64{
+        match self{
+            CandleTimeFrame::OneSecond => 6 * 3600,
+            CandleTimeFrame::OneMinute => 2 * 86400,
+            CandleTimeFrame::FiveMinutes => 7 * 86400,
+            CandleTimeFrame::FifteenMinutes => 14 * 86400,
+            _ => 30 * 86400
+        }
+    }
+
+    // 1s candles only live in redis
+    pub fn is_persisted(&self) -> bool{
+        !matches!(self, CandleTimeFrame::OneSecond)
+    }
+
+    // None keeps the rows forever
+    pub fn postgres_retention_secs(&self) -> Option<i64>{
+        match self{
+            CandleTimeFrame::OneMinute => Some(30 * 86400),
+            CandleTimeFrame::FiveMinutes => Some(90 * 86400),
+            CandleTimeFrame::FifteenMinutes => Some(180 * 86400),
+            _ => None
+        }
+    }
+
+    pub fn from_label(label : &str) -> Option<CandleTimeFrame>{
+        CandleTimeFrame::all().into_iter().find(|timeframe| timeframe.to_string() == label)
+    }
+
+    // resolution names of the TradingView UDF protocol
+    pub fn udf_resolution(&self) -> &'static str{
+        match self{
+            CandleTimeFrame::OneSecond => "1S",
+            CandleTimeFrame::OneMinute => "1",
+            CandleTimeFrame::FiveMinutes => "5",
+            CandleTimeFrame::FifteenMinutes => "15",
+            CandleTimeFrame::OneHour => "60",
+            CandleTimeFrame::FourHours => "240",
+            CandleTimeFrame::OneDay => "1D",
+            CandleTimeFrame::OneWeek => "1W",
+        }
+    }
+
+    pub fn from_udf_resolution(resolution : &str) -> Option<CandleTimeFrame>{
+        match resolution {
+            "D" => Some(CandleTimeFrame::OneDay),
+            "W" => Some(CandleTimeFrame::OneWeek),
+            _ => CandleTimeFrame::all().into_iter().find(|timeframe| timeframe.udf_resolution() == resolution)
+        }
+    }
+
     pub fn round_timestamp(&self, txn_timestamp : i64) -> i64{
+        let seconds = self.to_seconds();
         match self{
-            CandleTimeFrame::OneSecond => 1,
-            CandleTimeFrame::OneMinute => {(txn_timestamp / self.to_seconds()) * self.to_seconds()},
-            CandleTimeFrame::FiveMinutes => {(txn_timestamp / self.to_seconds()) * self.to_seconds()},
-            CandleTimeFrame::FifteenMinutes => {(txn_timestamp / self.to_seconds()) * self.to_seconds()},
-            CandleTimeFrame::OneHour => {(txn_timestamp / self.to_seconds()) * self.to_seconds()},
-            CandleTimeFrame::FourHours => {(txn_timestamp / self.to_seconds()) * self.to_seconds()},
-            CandleTimeFrame::OneDay => {(txn_timestamp / self.to_seconds()) * self.to_seconds()},
-            CandleTimeFrame::OneWeek => {(txn_timestamp / self.to_seconds()) * self.to_seconds()}
+            CandleTimeFrame::OneWeek => (txn_timestamp - WEEK_OFFSET_SECS).div_euclid(seconds) * seconds + WEEK_OFFSET_SECS,
+            _ => txn_timestamp.div_euclid(seconds) * seconds
         }
     }
-}
\ No newline at end of file
+}
+
+impl OHLCVcandle {
+    // a candle for a bucket without trades, flat at the close of `previous`
+    pub fn flat(token_pair : &str, timeframe : &str, timestamp : i64, previous : &OHLCVcandle) -> Self{
+        OHLCVcandle{
+            token_pair : token_pair.to_string(),
+            timeframe : timeframe.to_string(),
+            timestamp,
+            open : previous.close,
+            high : previous.close,
+            low : previous.close,
+            close : previous.close,
+            volume : 0.0,
+            buy_volume : 0.0,
+            sell_volume : 0.0,
+            trade_count : 0,
+            quote_open : previous.quote_close,
+            quote_high : previous.quote_close,
+            quote_low : previous.quote_close,
+            quote_close : previous.quote_close,
+            quote_volume : 0.0,
+            usd_volume : 0.0,
+            first_trade : TradeOrder::default(),
+            last_trade : TradeOrder::default(),
+            revision : 0
+        }
+    }
+
+    pub fn from_trade(token_pair : &str, timeframe : &str, timestamp : i64, trade : &CandleTrade) -> Self{
+        let mut candle = OHLCVcandle{
+            token_pair : token_pair.to_string(),
+            timeframe : timeframe.to_string(),
+            timestamp,
+            open : trade.price,
+            high : trade.price,
+            low : trade.price,
+            close : trade.price,
+            volume : 0.0,
+            buy_volume : 0.0,
+            sell_volume : 0.0,
+            trade_count : 0,
+            quote_open : trade.quote_price,
+            quote_high : trade.quote_price,
+            quote_low : trade.quote_price,
+            quote_close : trade.quote_price,
+            quote_volume : 0.0,
+            usd_volume : 0.0,
+            first_trade : trade.order,
+            last_trade : trade.order,
+            revision : 0
+        };
+        candle.absorb_trade(trade);
+        candle
+    }
+
+    /// Counts a trade in the candle. Open and close follow the chain order of the trades rather than their
+    /// arrival, a trade only moves the close when the current close is not known to be later.
+    pub fn absorb_trade(&mut self, trade : &CandleTrade){
+        if trade.order.is_before(&self.first_trade) {
+            self.open = trade.price;
+            if trade.quote_price > 0.0 {
+                self.quote_open = trade.quote_price;
+            }
+            self.first_trade = trade.order;
+        }
+        if !trade.order.is_before(&self.last_trade) {
+            self.close = trade.price;
+            if trade.quote_price > 0.0 {
+                self.quote_close = trade.quote_price;
+            }
+            self.last_trade = trade.order;
+        }
+        self.high = self.high.max(trade.price);
+        self.low = self.low.min(trade.price);
+        self.absorb_quote_range(trade.quote_price, trade.quote_price, trade.quote_price);
+        self.volume += trade.quantity;
+        match trade.side {
+            Type::Buy => self.buy_volume += trade.quantity,
+            Type::Sell => self.sell_volume += trade.quantity,
+        }
+        self.quote_volume += trade.quote_quantity;
+        self.usd_volume += trade.usd_value;
+        self.trade_count += 1;
+    }
+
+    // a zero quote price means unknown, it never becomes the low
+    fn absorb_quote_range(&mut self, open : f64, high : f64, low : f64){
+        if self.quote_open <= 0.0 {
+            self.quote_open = open;
+        }
+        if high > 0.0 {
+            self.quote_high = self.quote_high.max(high);
+        }
+        if low > 0.0 {
+            self.quote_low = if self.quote_low > 0.0 { self.quote_low.min(low) } else { low };
+        }
+    }
+
+    // folds a candle of a later or overlapping period into this one, open and close go by trade order
+    pub fn merge(&mut self, later : &OHLCVcandle){
+        if later.first_trade.is_before(&self.first_trade) {
+            self.open = later.open;
+            if later.quote_open > 0.0 {
+                self.quote_open = later.quote_open;
+            }
+            self.first_trade = later.first_trade;
+        }
+        if !later.last_trade.is_before(&self.last_trade) {
+            self.close = later.close;
+            if later.quote_close > 0.0 {
+                self.quote_close = later.quote_close;
+            }
+            self.last_trade = later.last_trade;
+        }
+        self.high = self.high.max(later.high);
+        self.low = self.low.min(later.low);
+        self.absorb_quote_range(later.quote_open, later.quote_high, later.quote_low);
+        self.volume += later.volume;
+        self.buy_volume += later.buy_volume;
+        self.sell_volume += later.sell_volume;
+        self.quote_volume += later.quote_volume;
+        self.usd_volume += later.usd_volume;
+        self.trade_count += later.trade_count;
+    }
+
+    // the same values under another timeframe and bucket, for rolling a candle up into its parent
+    pub fn relabel(&self, timeframe : &str, timestamp : i64) -> Self{
+        OHLCVcandle{
+            timeframe : timeframe.to_string(),
+            timestamp,
+            ..self.clone()
+        }
+    }
+
+    /// The candle with its OHLC and volumes in the requested denomination.
+    ///
+    /// `open` to `close`, `volume` and the buy and sell volumes carry the chosen prices and volumes, the
+    /// `quote_*` and `usd_volume` fields are left as they are. Candles written before the denominated
+    /// fields existed have them at 0 and keep their base values.
+    pub fn denominated(mut self, denomination : CandleDenomination) -> Self{
+        let volume = match denomination {
+            CandleDenomination::Usd => self.usd_volume,
+            CandleDenomination::Quote => self.quote_volume
+        };
+        if denomination == CandleDenomination::Quote && self.quote_low > 0.0 {
+            self.open = self.quote_open;
+            self.high = self.quote_high;
+            self.low = self.quote_low;
+            self.close = self.quote_close;
+        }
+        if volume > 0.0 {
+            // buy and sell volume keep their share of the total
+            let scale = if self.volume > 0.0 { volume / self.volume } else { 0.0 };
+            self.buy_volume *= scale;
+            self.sell_volume *= scale;
+            self.volume = volume;
+        }
+        self
+    }
+}
+
+/// Fills every empty bucket between `from` and `to` with a flat candle at the previous close.
+///
+/// `candles` must be sorted by timestamp. Buckets before the first candle are only filled when
+/// `previous` (the last candle before `from`) is known, so the series never starts with an invented
+/// price. Only the latest `max_candles` buckets are returned.
+pub fn fill_gaps(
+    candles : Vec<OHLCVcandle>,
+    timeframe : CandleTimeFrame,
+    token_pair : &str,
+    from : i64,
+    to : i64,
+    previous : Option<OHLCVcandle>,
+    max_candles : usize
+) -> Vec<OHLCVcandle>{
+    let step = timeframe.to_seconds();
+    let label = timeframe.to_string();
+    let last_bucket = timeframe.round_timestamp(to);
+    let mut next_bucket = timeframe.round_timestamp(from);
+    if next_bucket < from {
+        next_bucket += step;
+    }
+    // bounds the work for wide ranges on small timeframes
+    next_bucket = next_bucket.max(last_bucket - (max_candles.max(1) as i64 - 1) * step);
+
+    let mut filled = Vec::with_capacity(candles.len());
+    let mut last = previous;
+    for candle in candles {
+        if candle.timestamp < next_bucket {
+            last = Some(candle);
+            continue;
+        }
+        if let Some(last) = &last {
+            while next_bucket < candle.timestamp {
+                filled.push(OHLCVcandle::flat(token_pair, &label, next_bucket, last));
+                next_bucket += step;
+            }
+        }
+        next_bucket = candle.timestamp + step;
+        last = Some(candle.clone());
+        filled.push(candle);
+    }
+    if let Some(last) = &last {
+        while next_bucket <= last_bucket {
+            filled.push(OHLCVcandle::flat(token_pair, &label, next_bucket, last));
+            next_bucket += step;
+        }
+    }
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAIR: &str = "BONK/SOL";
+    // a monday, so every timeframe up to 1w starts a bucket here
+    const T0: i64 = 1_760_918_400;
+
+    fn candle(timestamp: i64, price: f64) -> OHLCVcandle {
+        let trade = CandleTrade {
+            price,
+            quote_price: price / 200.0,
+            quantity: 10.0,
+            quote_quantity: 10.0 * price / 200.0,
+            usd_value: 10.0 * price,
+            side: Type::Buy,
+            order: TradeOrder { block_time: timestamp * 1000, slot: 1, index: 0 },
+            id: format!("sig{}:0", timestamp),
+        };
+        OHLCVcandle::from_trade(PAIR, "1m", timestamp, &trade)
+    }
+
+    fn timestamps(candles: &[OHLCVcandle]) -> Vec<i64> {
+        candles.iter().map(|candle| candle.timestamp - T0).collect()
+    }
+
+    #[test]
+    fn round_timestamp_floors_to_the_bucket_start() {
+        assert_eq!(CandleTimeFrame::OneSecond.round_timestamp(T0 + 7), T0 + 7);
+        assert_eq!(CandleTimeFrame::OneMinute.round_timestamp(T0 + 59), T0);
+        assert_eq!(CandleTimeFrame::OneMinute.round_timestamp(T0 + 60), T0 + 60);
+        assert_eq!(CandleTimeFrame::FifteenMinutes.round_timestamp(T0 + 1799), T0 + 900);
+        assert_eq!(CandleTimeFrame::FourHours.round_timestamp(T0 + 5 * 3600), T0 + 4 * 3600);
+        assert_eq!(CandleTimeFrame::OneDay.round_timestamp(T0 + 86399), T0);
+    }
+
+    #[test]
+    fn round_timestamp_floors_negative_and_epoch_timestamps() {
+        assert_eq!(CandleTimeFrame::OneMinute.round_timestamp(0), 0);
+        assert_eq!(CandleTimeFrame::OneMinute.round_timestamp(-1), -60);
+        assert_eq!(CandleTimeFrame::OneHour.round_timestamp(-3600), -3600);
+        assert_eq!(CandleTimeFrame::OneDay.round_timestamp(-1), -86400);
+    }
+
+    #[test]
+    fn weeks_start_on_monday() {
+        let week = CandleTimeFrame::OneWeek;
+        assert_eq!(week.round_timestamp(T0), T0);
+        assert_eq!(week.round_timestamp(T0 + 7 * 86400 - 1), T0);
+        assert_eq!(week.round_timestamp(T0 + 7 * 86400), T0 + 7 * 86400);
+        // sunday night still belongs to the previous week
+        assert_eq!(week.round_timestamp(T0 - 1), T0 - 7 * 86400);
+        // the epoch was a thursday, its week started on monday 1969-12-29
+        assert_eq!(week.round_timestamp(0), -3 * 86400);
+    }
+
+    #[test]
+    fn fill_gaps_without_previous_starts_at_the_first_candle() {
+        let candles = vec![candle(T0 + 120, 1.0), candle(T0 + 300, 2.0)];
+        let filled = fill_gaps(candles, CandleTimeFrame::OneMinute, PAIR, T0, T0 + 420, None, 100);
+
+        assert_eq!(timestamps(&filled), vec![120, 180, 240, 300, 360, 420]);
+        let gap = &filled[1];
+        assert_eq!((gap.open, gap.high, gap.low, gap.close), (1.0, 1.0, 1.0, 1.0));
+        assert_eq!(gap.volume, 0.0);
+        assert_eq!(gap.trade_count, 0);
+        assert_eq!(filled[4].close, 2.0);
+        assert_eq!(filled[5].quote_close, 2.0 / 200.0);
+    }
+
+    #[test]
+    fn fill_gaps_with_previous_fills_from_the_range_start() {
+        let candles = vec![candle(T0 + 120, 3.0)];
+        let previous = candle(T0 - 600, 1.5);
+        let filled = fill_gaps(candles, CandleTimeFrame::OneMinute, PAIR, T0, T0 + 180, Some(previous), 100);
+
+        assert_eq!(timestamps(&filled), vec![0, 60, 120, 180]);
+        assert_eq!(filled[0].close, 1.5);
+        assert_eq!(filled[1].open, 1.5);
+        assert_eq!(filled[3].close, 3.0);
+    }
+
+    #[test]
+    fn fill_gaps_with_nothing_known_returns_nothing() {
+        let filled = fill_gaps(Vec::new(), CandleTimeFrame::OneMinute, PAIR, T0, T0 + 600, None, 100);
+        assert!(filled.is_empty());
+    }
+
+    #[test]
+    fn fill_gaps_keeps_only_the_latest_max_candles() {
+        let candles = vec![candle(T0 + 60, 1.0), candle(T0 + 300, 2.0)];
+        let filled = fill_gaps(candles, CandleTimeFrame::OneMinute, PAIR, T0, T0 + 420, None, 3);
+
+        assert_eq!(timestamps(&filled), vec![300, 360, 420]);
+
+        // a candle before the capped range still prices the gaps after it
+        let candles = vec![candle(T0 + 60, 1.0)];
+        let filled = fill_gaps(candles, CandleTimeFrame::OneMinute, PAIR, T0, T0 + 420, None, 2);
+        assert_eq!(timestamps(&filled), vec![360, 420]);
+        assert!(filled.iter().all(|candle| candle.close == 1.0 && candle.trade_count == 0));
+    }
+}
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+
+// a pool is identified by its state account, or by its token-side vault when the swap did not show
+// which program account it went through. vault owners are shared authorities on some programs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolInfo {
+    pub pool_id: String,
+    pub pool_owner: String,
+    #[serde(default)]
+    pub program_id: String, // empty when the pool id is the token vault
+    pub dex_tag: String,
+    pub token_pair: String,
+    pub token_mint: String,
+    pub token_vault: String,
+    pub sol_vault: String,
+    pub token_decimals: u32,
+    pub sol_decimals: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PoolReserves {
+    pub token_reserve: f64, // vault balance net of the fees it holds
+    pub sol_reserve: f64,
+    #[serde(default)]
+    pub token_fees: f64,
+    #[serde(default)]
+    pub sol_fees: f64,
+    #[serde(default)]
+    pub active_sol_reserve: Option<f64>, // concentrated liquidity pools only
+    pub liquidity_usd: f64,
+    #[serde(default)]
+    pub active_liquidity_usd: Option<f64>,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiquidityUpdate {
+    pub token_pair: String,
+    pub pool_id: String,
+    pub dex_tag: String,
+    pub token_reserve: f64,
+    pub sol_reserve: f64,
+    pub liquidity_usd: f64,
+    pub active_liquidity_usd: Option<f64>, // liquidity at the current price of a concentrated pool
+    pub pair_liquidity_usd: f64, // summed over every pool of the pair
+    pub updated_at: i64,
+}
@@ -1,7 +1,101 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenInfo {
     pub token_symbol: String,
     pub token_name: String,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OracleQuote {
+    pub source: String,
+    pub price: f64,
+    pub confidence: Option<f64>,
+    pub published_at: DateTime<Utc>,
+}
+
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenMetadataUpdate {
+    pub mint_address: String,
+    pub token_symbol: String,
+    pub token_name: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TokenMetadata {
+    pub mint_address: String,
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub uri: Option<String>,
+    pub update_authority: Option<String>, // used as the dev wallet for holder concentration
+    pub decimals: Option<u8>,
+    pub supply: Option<u64>,
+    pub mint_authority: Option<String>,
+    pub freeze_authority: Option<String>,
+    pub image: Option<String>,
+    pub description: Option<String>,
+    pub website: Option<String>,
+    pub twitter: Option<String>,
+    pub telegram: Option<String>,
+    pub discord: Option<String>,
+    pub onchain_updated_at: Option<i64>,
+    pub mint_account_updated_at: Option<i64>,
+    pub offchain_updated_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MintAccountInfo {
+    pub decimals: u8,
+    pub supply: u64,
+    pub mint_authority: Option<String>,
+    pub freeze_authority: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum MetadataSection {
+    OnChain,     // name, symbol and uri from the metaplex metadata account
+    MintAccount, // decimals, supply and authorities from the mint account
+    OffChain     // fields from the json document behind the uri
+}
+
+impl MetadataSection {
+    pub fn ttl_seconds(&self) -> i64 {
+        match self {
+            MetadataSection::OnChain => 86400,
+            MetadataSection::MintAccount => 600,
+            MetadataSection::OffChain => 21600,
+        }
+    }
+
+    pub fn updated_at_field(&self) -> &'static str {
+        match self {
+            MetadataSection::OnChain => "onchain_updated_at",
+            MetadataSection::MintAccount => "mint_account_updated_at",
+            MetadataSection::OffChain => "offchain_updated_at",
+        }
+    }
+}
+
+impl TokenMetadata {
+    pub fn is_stale(&self, section: MetadataSection) -> bool {
+        let updated_at = match section {
+            MetadataSection::OnChain => self.onchain_updated_at,
+            MetadataSection::MintAccount => self.mint_account_updated_at,
+            MetadataSection::OffChain => self.offchain_updated_at,
+        };
+        match updated_at {
+            Some(ts) => Utc::now().timestamp() - ts > section.ttl_seconds(),
+            None => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenSupply {
+    pub total_supply: f64,
+    pub circulating_supply: f64,
+    pub decimals: u8,
+    pub updated_at: i64,
+}
@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+
+use crate::redis::metric_and_ohlcv_manager::TimeFrame;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum LeaderboardKind {
+    Trending,
+    Gainers,
+    Losers,
+    Volume,
+    Newest,
+}
+
+impl LeaderboardKind {
+    pub fn all() -> Vec<LeaderboardKind> {
+        vec![
+            LeaderboardKind::Trending,
+            LeaderboardKind::Gainers,
+            LeaderboardKind::Losers,
+            LeaderboardKind::Volume,
+            LeaderboardKind::Newest,
+        ]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LeaderboardKind::Trending => "trending",
+            LeaderboardKind::Gainers => "gainers",
+            LeaderboardKind::Losers => "losers",
+            LeaderboardKind::Volume => "volume",
+            LeaderboardKind::Newest => "newest",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<LeaderboardKind> {
+        LeaderboardKind::all().into_iter().find(|kind| kind.label() == label)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub rank: usize,
+    pub token_pair: String,
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeaderboardUpdate {
+    pub timeframe: TimeFrame,
+    pub kind: LeaderboardKind,
+    pub entries: Vec<LeaderboardEntry>,
+    pub updated_at: i64,
+}
@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+
+// a wallet's position in one market, kept at average cost so partial sells realize against the mean entry
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WalletPosition {
+    pub wallet: String,
+    pub token_pair: String,
+    pub token_mint: String,
+    pub token_balance: f64, // tokens bought while tracked and not yet sold
+    pub cost_basis_usd: f64,
+    pub cost_basis_sol: f64,
+    pub realized_pnl_usd: f64,
+    pub realized_pnl_sol: f64,
+    pub bought_usd: f64,
+    pub sold_usd: f64,
+    pub buys: u64,
+    pub sells: u64,
+    pub winning_sells: u64,
+    pub losing_sells: u64,
+    pub closed_positions: u64,
+    pub closed_hold_secs: i64, // summed hold time of closed positions
+    pub opened_at: Option<i64>, // first buy of the currently open position
+    pub last_trade_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PositionView {
+    #[serde(flatten)]
+    pub position: WalletPosition,
+    pub current_price: Option<f64>,
+    pub avg_entry_price: Option<f64>,
+    pub unrealized_pnl_usd: Option<f64>, // None while the market has no current price
+    pub unrealized_pnl_sol: Option<f64>,
+    pub hold_secs: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalletProfile {
+    pub wallet: String,
+    pub positions: Vec<PositionView>,
+    pub realized_pnl_usd: f64,
+    pub realized_pnl_sol: f64,
+    pub unrealized_pnl_usd: f64,
+    pub unrealized_pnl_sol: f64,
+    pub volume_usd: f64,
+    pub trades: u64,
+    pub win_rate: Option<f64>, // share of sells that closed above the average entry
+    pub avg_hold_secs: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TraderSort {
+    Pnl,
+    Volume,
+}
+
+impl TraderSort {
+    pub fn label(&self) -> &'static str {
+        match self {
+            TraderSort::Pnl => "pnl",
+            TraderSort::Volume => "volume",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<TraderSort> {
+        [TraderSort::Pnl, TraderSort::Volume].into_iter().find(|sort| sort.label() == label)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopTrader {
+    pub rank: usize,
+    pub position: PositionView,
+    pub total_pnl_usd: f64,
+    pub volume_usd: f64,
+}
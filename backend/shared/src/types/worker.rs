@@ -7,12 +7,50 @@ pub struct StructeredTransaction {
     pub purchase_type: Type,
     pub usd_value: Option<f64>,
     pub token_quantity: f64,
+    #[serde(default)]
+    pub quote_quantity: f64, // SOL side of the swap
     pub token_price: f64,
     pub token_pair : String,
     pub token_name : String,
+    #[serde(default)]
+    pub token_mint : String,
+    #[serde(default)]
+    pub supply_changed : bool, // the txn minted or burned tokens, cached supply must be refreshed
     pub owner: String,
     pub dex_type: String,
-    pub dex_tag : String
+    pub dex_tag : String,
+    #[serde(default)]
+    pub order: TradeOrder,
+    #[serde(default)]
+    pub signature: String, // base58, empty for messages published before it was carried
+    #[serde(default)]
+    pub leg: u32 // position of this swap among the swaps parsed from the same transaction, always 0 while one swap is parsed per transaction
+}
+
+/// Where a transaction landed on chain. Slots order blocks and `index` orders transactions within one,
+/// `block_time` (unix millis) orders trades recorded without a slot. All zero means unknown.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+pub struct TradeOrder {
+    pub block_time: i64,
+    pub slot: u64,
+    pub index: u64,
+}
+
+impl TradeOrder {
+    pub fn is_known(&self) -> bool {
+        self.slot > 0 || self.block_time > 0
+    }
+
+    // only true when both positions are known and this one landed first
+    pub fn is_before(&self, other: &TradeOrder) -> bool {
+        if !self.is_known() || !other.is_known() {
+            return false;
+        }
+        if self.slot > 0 && other.slot > 0 {
+            return (self.slot, self.index) < (other.slot, other.index);
+        }
+        self.block_time < other.block_time
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -11,7 +11,9 @@ use std::sync::Arc;
 use tokio::sync::{Mutex, RwLock};
 use std::collections::HashMap;
 
-use crate::{redis::pubsub_manager::{PubSubManager, PubSubMessage}};
+use serde::Deserialize;
+
+use crate::{redis::pubsub_manager::{PubSubManager, PubSubMessage}, types::alert::owner_of_token};
 
 type WebSocketSender = SplitSink<WebSocket, Message>;
 
@@ -20,6 +22,16 @@ lazy_static::lazy_static! {
     static ref WEBSOCKET_CONNECTIONS: Arc<RwLock<HashMap<usize, Arc<Mutex<WebSocketSender>>>>> = 
         Arc::new(RwLock::new(HashMap::new()));
     static ref CONNECTION_COUNTER: Arc<Mutex<usize>> = Arc::new(Mutex::new(0));
+    // alert owner of each connection that subscribed with its token
+    static ref ALERT_OWNERS: Arc<RwLock<HashMap<usize, String>>> = Arc::new(RwLock::new(HashMap::new()));
+}
+
+// messages clients send, e.g. {"type":"subscribe_alerts","token":"..."}
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    SubscribeAlerts { token: String },
+    UnsubscribeAlerts,
 }
 
 #[derive(Clone, Debug)]
@@ -28,6 +40,12 @@ pub struct WebsocketManager {
     pubsub_manager : Option<PubSubManager> //kept it option bcoz its fine to start websocket without creating pubsub
 }
 
+impl Default for WebsocketManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl WebsocketManager {
     pub fn new() -> Self {
         let pubsub_manager = match PubSubManager::new(){
@@ -44,12 +62,11 @@ impl WebsocketManager {
             pubsub_manager
         };
 
-        if let Some(_) = &instance.pubsub_manager{
+        if instance.pubsub_manager.is_some(){
             tokio::spawn(async move{
-                if let Some(pubsub) = pubsub_clone {
-                    if let Err(e) = Self::start_pubsub_listener(pubsub).await{
-                        println!("Pubsub listener error : {}",e);
-                    }
+                if let Some(pubsub) = pubsub_clone
+                    && let Err(e) = Self::start_pubsub_listener(pubsub).await{
+                    println!("Pubsub listener error : {}",e);
                 }
             });
         }
@@ -105,6 +122,61 @@ impl WebsocketManager {
                                 continue;
                             }
                         };
+                    },
+                    PubSubMessage::TokenMetadata(metadata) => {
+                         match serde_json::to_string(&metadata) {
+                            Ok(metadata) => {
+                                Self::push(metadata).await
+                            }
+                            Err(e) => {
+                                println!("Failed to serialize the token metadata from mpsc to send through socket : {}",e);
+                                continue;
+                            }
+                        };
+                    }
+                    PubSubMessage::Liquidity(liquidity) => {
+                         match serde_json::to_string(&liquidity) {
+                            Ok(liquidity) => {
+                                Self::push(liquidity).await
+                            }
+                            Err(e) => {
+                                println!("Failed to serialize the liquidity update from mpsc to send through socket : {}",e);
+                                continue;
+                            }
+                        };
+                    }
+                    PubSubMessage::Leaderboard(leaderboard) => {
+                         match serde_json::to_string(&leaderboard) {
+                            Ok(leaderboard) => {
+                                Self::push(leaderboard).await
+                            }
+                            Err(e) => {
+                                println!("Failed to serialize the leaderboard update from mpsc to send through socket : {}",e);
+                                continue;
+                            }
+                        };
+                    }
+                    PubSubMessage::Alert(alert) => {
+                         match serde_json::to_string(&alert) {
+                            Ok(message) => {
+                                Self::push_to_owner(&alert.owner, message).await
+                            }
+                            Err(e) => {
+                                println!("Failed to serialize the alert from mpsc to send through socket : {}",e);
+                                continue;
+                            }
+                        };
+                    }
+                    PubSubMessage::MarketEvent(event) => {
+                         match serde_json::to_string(&event) {
+                            Ok(event) => {
+                                Self::push(event).await
+                            }
+                            Err(e) => {
+                                println!("Failed to serialize the market event from mpsc to send through socket : {}",e);
+                                continue;
+                            }
+                        };
                     }
             }
         }
@@ -160,6 +232,45 @@ impl WebsocketManager {
         println!("✅ Message sent to {}/{} connections", success_count, count);
     }
 
+    // alerts only go to the connections their owner subscribed
+    pub async fn push_to_owner(owner: &str, msg: String) {
+        let ids: Vec<usize> = ALERT_OWNERS
+            .read()
+            .await
+            .iter()
+            .filter(|(_, subscribed_owner)| subscribed_owner.as_str() == owner)
+            .map(|(id, _)| *id)
+            .collect();
+        if ids.is_empty() {
+            return;
+        }
+
+        let senders: Vec<(usize, Arc<Mutex<WebSocketSender>>)> = {
+            let connections = WEBSOCKET_CONNECTIONS.read().await;
+            ids.iter().filter_map(|id| connections.get(id).map(|sender| (*id, sender.clone()))).collect()
+        };
+        for (id, sender) in senders {
+            if let Err(e) = sender.lock().await.send(Message::Text(msg.clone().into())).await {
+                println!("❌ Failed to send alert to connection {}: {}", id, e);
+            }
+        }
+    }
+
+    async fn handle_client_message(connection_id: usize, text: &str) {
+        match serde_json::from_str::<ClientMessage>(text) {
+            Ok(ClientMessage::SubscribeAlerts { token }) => match owner_of_token(&token) {
+                Some(owner) => {
+                    ALERT_OWNERS.write().await.insert(connection_id, owner);
+                }
+                None => println!("Connection {} sent an invalid alert token", connection_id),
+            },
+            Ok(ClientMessage::UnsubscribeAlerts) => {
+                ALERT_OWNERS.write().await.remove(&connection_id);
+            }
+            Err(_) => {}
+        }
+    }
+
     async fn add_connection(&self, sender: WebSocketSender) -> usize {
         let mut counter = CONNECTION_COUNTER.lock().await;
         *counter += 1;
@@ -179,6 +290,8 @@ impl WebsocketManager {
     async fn remove_connection(&self, connection_id: usize) {
         let mut connections = WEBSOCKET_CONNECTIONS.write().await;
         connections.remove(&connection_id);
+        drop(connections);
+        ALERT_OWNERS.write().await.remove(&connection_id);
         
         let mut client_count = self.client_count.lock().await;
         *client_count = client_count.saturating_sub(1);
@@ -200,12 +313,13 @@ async fn handle_socket(socket: WebSocket, manager: WebsocketManager) {
     // Add this connection to the global pool
     let connection_id = manager.add_connection(socket_sender).await;
     
-    // Handle incoming messages (mainly for disconnect detection)
+    // Handle incoming messages, alert subscriptions and disconnect detection
     while let Some(msg_result) = socket_receiver.next().await {
-        if msg_result.is_err() {
-            break;
+        match msg_result {
+            Ok(Message::Text(text)) => WebsocketManager::handle_client_message(connection_id, &text).await,
+            Ok(_) => {}
+            Err(_) => break,
         }
-        // Handle incoming messages if needed
     }
     
     // Remove connection when client disconnects
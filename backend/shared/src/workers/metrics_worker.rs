@@ -1,9 +1,11 @@
 use crate::{
     queues::{stream_manager::StreamManager},
     redis::{
+        market_registry_manager::MarketRegistryManager,
         metric_and_ohlcv_manager::MetricOHLCVManager, pubsub_manager::PubSubManager,
         token_symbol_manager::TokenSymbolManager,
     },
+    services::{anomaly_service::AnomalyService, supply_service::SupplyService, wallet_service::WalletService},
     types::worker::StructeredTransaction,
 };
 use std::time::Duration;
@@ -16,6 +18,10 @@ pub struct MetricsWorker {
     pub pubsub_manager: PubSubManager,
     pub token_manager: TokenSymbolManager,
     pub stream_manager: StreamManager,
+    pub supply_service: SupplyService,
+    pub market_registry: MarketRegistryManager,
+    pub wallet_service: WalletService,
+    pub anomaly_service: AnomalyService,
 }
 
 impl MetricsWorker {
@@ -26,11 +32,20 @@ impl MetricsWorker {
             .expect("Error creating token symbol manager in metrics worker");
         let stream_manager =
             StreamManager::new().expect("unable to initialize stream in metric worker");
+        let supply_service = SupplyService::new(token_manager.clone());
+        let wallet_service = WalletService::new(token_manager.clone());
+        let anomaly_service = AnomalyService::new(pubsub_manager.clone());
+        let market_registry = MarketRegistryManager::new()
+            .expect("Error creating market registry in metrics worker");
         Ok(Self {
             metric_manager,
             pubsub_manager,
             token_manager,
             stream_manager,
+            supply_service,
+            market_registry,
+            wallet_service,
+            anomaly_service,
         })
     }
 
@@ -66,7 +81,7 @@ impl MetricsWorker {
         let token_pair_clone = txn_message.token_pair.clone();
         if let Err(e) = self
             .metric_manager
-            .update_current_price(txn_message.token_pair.clone(), txn_message.token_price)
+            .update_current_price(txn_message.token_pair.clone(), txn_message.token_price, txn_message.date.timestamp())
             .await
         {
             println!(
@@ -75,6 +90,18 @@ impl MetricsWorker {
             )
         };
 
+        if let Err(e) = self
+            .market_registry
+            .touch_market(&txn_message.token_pair, &txn_message.token_mint, txn_message.date.timestamp())
+            .await
+        {
+            println!("Error occured while registering active market : {}", e);
+        };
+
+        if let Err(e) = self.anomaly_service.on_trade(&txn_message).await {
+            println!("Error occured while checking trade for market events : {}", e);
+        };
+
         if let Err(e) = self.metric_manager.update_period_stats(txn_clone).await {
             println!("Error occured updating period stats : {}", e);
         };
@@ -85,19 +112,37 @@ impl MetricsWorker {
                 return Ok(());
             }
         };
+        if let Err(e) = self.wallet_service.record_trade(&txn_message, sol_info.sol_price).await {
+            println!("Error occured while updating wallet position of {} : {}", txn_message.owner, e);
+        };
         if let Err(e) = self
             .pubsub_manager
             .publish_current_price(
                 txn_message.token_price,
                 sol_info.sol_price,
-                txn_message.token_pair,
+                txn_message.token_pair.clone(),
             )
             .await
         {
             println!("Error occured while publishing current price : {}", e)
         };
-        let market_cap = fastrand::i32(100_000..=1_000_000);
-        let fdv = fastrand::i32(100_000..=1_000_000);
+        if txn_message.token_mint.is_empty() {
+            println!("No mint on txn for {}, skipping market data", token_pair_clone);
+            return Ok(());
+        }
+        let supply = match self
+            .supply_service
+            .get_supply(&txn_message.token_mint, txn_message.supply_changed)
+            .await
+        {
+            Some(supply) => supply,
+            None => {
+                println!("No cached supply for mint {} yet, refresh queued", txn_message.token_mint);
+                return Ok(());
+            }
+        };
+        let market_cap = txn_message.token_price * supply.circulating_supply;
+        let fdv = txn_message.token_price * supply.total_supply;
         if let Err(e) = self
             .metric_manager
             .update_market_data(token_pair_clone, market_cap, fdv)
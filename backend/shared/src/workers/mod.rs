@@ -1,3 +1,4 @@
 pub mod txn_worker;
 pub mod ohlcv_worker;
-pub mod metrics_worker;
\ No newline at end of file
+pub mod metrics_worker;
+pub mod swap_worker;
\ No newline at end of file
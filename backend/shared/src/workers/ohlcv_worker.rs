@@ -1,12 +1,34 @@
-use std::time::Duration;
-use tokio::time::sleep;
-use crate::{queues::{stream_manager::StreamManager}, redis::{metric_and_ohlcv_manager::MetricOHLCVManager, pubsub_manager::PubSubManager}, types::{ohlcv::{CandleTimeFrame, OHLCVcandle}, worker::{StructeredTransaction, Type}}};
+use std::{collections::{HashMap, HashSet}, sync::Arc, time::Duration};
+use chrono::Utc;
+use tokio::{sync::Mutex, time::sleep};
+use crate::{queues::{stream_manager::StreamManager}, redis::{metric_and_ohlcv_manager::{CandleMerge, MetricOHLCVManager}, pubsub_manager::PubSubManager}, services::candle_rollup::{PairRollup, RollupBatch}, types::{ohlcv::{CandleTrade, OHLCVcandle}, worker::StructeredTransaction}};
 
+const CANDLE_BATCH_SIZE : usize = 200;
+// a pair without trades for this long leaves memory, all it loses is the last candle gaps are filled from
+const PAIR_IDLE_SECS : i64 = 600;
+
+/// Builds candles from the trade stream and merges them into redis in batches.
+///
+/// Trades are buffered per pair between flushes. Every `publish_interval` each pair's trades are
+/// rolled up from 1s into every timeframe (see `PairRollup`) and merged into the redis buckets with
+/// their stream messages acknowledged in the same script run, then the merged candles are published.
+/// Merging deltas instead of overwriting buckets lets any number of ohlcv workers share the consumer
+/// group, and trade ids already merged are skipped so a trade published twice is counted once.
 #[derive(Debug)]
 pub struct OHLCVWorker{
     pub ohlcv_manager : MetricOHLCVManager,
     pub pubsub_manager : PubSubManager,
-    pub stream_manager : StreamManager
+    pub stream_manager : StreamManager,
+    pub gap_fill : bool, // publish flat candles for empty buckets before a new candle
+    pub publish_interval : Duration,
+    pub lateness_secs : i64, // how long after a bucket closed a late trade still corrects it
+    rollups : Mutex<HashMap<String, PairRollup>>
+}
+
+impl Default for OHLCVWorker {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl OHLCVWorker {
@@ -14,28 +36,55 @@ impl OHLCVWorker {
         let ohlcv_manager = MetricOHLCVManager::new().expect("unable to access ohlcv manager");
         let pubsub_manager = PubSubManager::new().expect("Unable to create pubsub for ohlcv");
         let stream_manager = StreamManager::new().expect("unable to access stream from ohlcv worker");
-        Self {ohlcv_manager, pubsub_manager, stream_manager }
+        // CANDLE_GAP_FILL=false streams only candles that saw trades
+        let gap_fill = std::env::var("CANDLE_GAP_FILL").map(|value| value != "false").unwrap_or(true);
+        // CANDLE_PUBLISH_INTERVAL_MS sets how often live candles are written and published
+        let publish_interval = std::env::var("CANDLE_PUBLISH_INTERVAL_MS")
+            .ok()
+            .and_then(|value| value.parse::<u64>().ok())
+            .map(Duration::from_millis)
+            .unwrap_or(Duration::from_secs(1));
+        // CANDLE_LATENESS_SECS bounds how old a closed candle may be and still get corrected by a late trade
+        let lateness_secs = std::env::var("CANDLE_LATENESS_SECS")
+            .ok()
+            .and_then(|value| value.parse::<i64>().ok())
+            .unwrap_or(300);
+        Self {ohlcv_manager, pubsub_manager, stream_manager, gap_fill, publish_interval, lateness_secs, rollups : Mutex::new(HashMap::new()) }
     }
 
-    pub async fn start_processing(&self,  consumer_group: String, consumer_name: String) {
+    pub async fn start_processing(self : Arc<Self>,  consumer_group: String, consumer_name: String) {
         println!("Worker started and waiting for messages...");
         let _ = self.stream_manager.init_stream(&consumer_group).await;
+
+        let flusher = self.clone();
+        let flush_group = consumer_group.clone();
+        tokio::spawn(async move {
+            loop {
+                sleep(flusher.publish_interval).await;
+                if let Err(e) = flusher.flush_candles(&flush_group).await{
+                    println!("Error flushing candles : {}", e);
+                }
+            }
+        });
+
+        // messages read before a restart were never merged, they are acknowledged with the trades they carry
         loop {
-            match self.stream_manager.consume(&consumer_group, &consumer_name).await {
-                Ok(Some(stream_data)) => {
-                    println!("Got txn messsage from the queue");
-                    println!("Txn Metadata : {:?}", stream_data.1);
-                    if let Err(e) = self.transform_data(stream_data.1).await{
-                        println!("Error tranforming the structured txn into ohlcv data : {}",e);
-                    };
-                    if let Err(e) = self.stream_manager.ack(&consumer_group, &stream_data.0).await{
-                        println!("Error in acknowledment of message {} : {}", stream_data.0, e);
-                    }
+            match self.stream_manager.consume_pending(&consumer_group, &consumer_name, CANDLE_BATCH_SIZE).await {
+                Ok(batch) if batch.is_empty() => break,
+                Ok(batch) => self.buffer_trades(batch).await,
+                Err(e) => {
+                    println!("Error reading pending trades : {}", e);
+                    sleep(Duration::from_millis(100)).await;
                 }
-                Ok(None) => {
+            }
+        }
+
+        loop {
+            match self.stream_manager.consume_batch(&consumer_group, &consumer_name, CANDLE_BATCH_SIZE).await {
+                Ok(batch) if batch.is_empty() => {
                     println!("Queue empty, no message recieved");
-                    sleep(Duration::from_millis(100)).await;
                 }
+                Ok(batch) => self.buffer_trades(batch).await,
                 Err(e) => {
                     println!("Error in redis queue : {}", e);
                     sleep(Duration::from_millis(100)).await;
@@ -44,74 +93,82 @@ impl OHLCVWorker {
         }
     }
 
-    pub async fn transform_data(&self, txn : StructeredTransaction) -> Result<(), anyhow::Error>{
-        let txn_timestamp = txn.date.timestamp();
-        
-        for timeframe in CandleTimeFrame::all(){
-            let timeframe_str = timeframe.to_string();
-            let candle_timestamp = timeframe.round_timestamp(txn_timestamp);
-            println!(" {} : {} rounded to {}", timeframe_str, txn_timestamp, candle_timestamp);
-            let candle = self.build_or_update_candle(&txn, timeframe, candle_timestamp).await?;
-
-            if let Err(e) = self.ohlcv_manager.save_candle(&candle).await{
-                println!("Error saving candle to redis : {}",e)
-            };
-
-            if let Err(e) =  self.pubsub_manager.publish_candle_update(candle).await{
-                println!("Error pushing candle update through pubsub : {}", e)
+    // messages stay pending until the flush that merges their trades acknowledges them
+    async fn buffer_trades(&self, batch : Vec<(String, StructeredTransaction)>){
+        let now = Utc::now().timestamp();
+        let mut rollups = self.rollups.lock().await;
+        for (message_id, txn) in batch {
+            let trade = CandleTrade::from_txn(&txn);
+            let rollup = rollups.entry(txn.token_pair.clone()).or_insert_with(|| PairRollup::new(&txn.token_pair));
+            if !rollup.apply_trade(message_id, txn.date.timestamp(), trade, now) {
+                println!("Skipping duplicate trade {}:{} of {}", txn.signature, txn.leg, txn.token_pair);
             }
         }
-        println!("Processed transaction for all timeframes for token : {}", txn.token_pair);
-        Ok(())
     }
 
-    async fn build_or_update_candle(&self, txn : &StructeredTransaction, timeframe : CandleTimeFrame, candle_timestamp : i64) -> Result<OHLCVcandle, anyhow::Error>{
-        let timeframe_str = timeframe.to_string();
-
-       let existing_candle = self.ohlcv_manager.get_candle(&txn.token_pair, &timeframe_str, candle_timestamp).await?;
+    /// Merges every pair's buffered trades into redis and publishes the merged candles.
+    ///
+    /// A pair whose write fails keeps its trades, they go out with the next flush.
+    pub async fn flush_candles(&self, consumer_group : &str) -> Result<(), anyhow::Error>{
+        let now = Utc::now().timestamp();
+        let batches : Vec<RollupBatch> = {
+            let mut rollups = self.rollups.lock().await;
+            rollups.retain(|_, rollup| !rollup.is_idle(now, PAIR_IDLE_SECS));
+            rollups.values_mut().filter_map(|rollup| rollup.take_batch()).collect()
+        };
 
-            let candle = match existing_candle {
-                Some(mut candle) => {
-                    candle.high = candle.high.max(txn.token_price);
-                    candle.low = candle.low.min(txn.token_price);
-                    candle.close = txn.token_price;
-                    candle.volume += txn.token_quantity;
-                    candle.trade_count += 1;
-
-                    match txn.purchase_type {
-                        Type::Buy => candle.buy_volume += txn.token_quantity,
-                        Type::Sell => candle.sell_volume += txn.token_quantity
+        let mut failed = None;
+        for mut batch in batches {
+            match self.write_batch(&mut batch, consumer_group, now).await {
+                Ok(written) => {
+                    let gaps = self
+                        .rollups
+                        .lock()
+                        .await
+                        .get_mut(&batch.token_pair)
+                        .map(|rollup| rollup.record_written(&written))
+                        .unwrap_or_default();
+                    // the candles are stored and acked by now, a failed publish must not keep the other batches from being written
+                    if self.gap_fill && let Err(e) = self.pubsub_manager.publish_candle_updates(&gaps).await {
+                        println!("Error publishing {} gap candles of {} : {}", gaps.len(), batch.token_pair, e);
                     }
-                    println!("updated old candle for pair : {}", txn.token_pair);
-                    candle
-                }
-                None => {
-                    println!("created new candle for pair : {}", txn.token_pair);
-                    OHLCVcandle{
-                        token_pair : txn.token_pair.clone(),
-                        timeframe : timeframe_str,
-                        timestamp : candle_timestamp,
-                        open : txn.token_price,
-                        high : txn.token_price,
-                        low : txn.token_price,
-                        close : txn.token_price,
-                        volume : txn.token_quantity,
-                        buy_volume : if matches!(txn.purchase_type, Type::Buy){
-                            txn.token_quantity
-                        } else {
-                            0.0
-                        },
-                        sell_volume : if matches!(txn.purchase_type, Type::Sell){
-                            txn.token_quantity
-                        } else{
-                            0.0
-                        },
-                        trade_count : 1
+                    if let Err(e) = self.pubsub_manager.publish_candle_updates(&written).await {
+                        println!("Error publishing {} candles of {} : {}", written.len(), batch.token_pair, e);
                     }
                 }
-            };
-            Ok(candle)
+                Err(e) => {
+                    println!("Error writing {} trades of {}, keeping them for the next flush : {}", batch.trade_count(), batch.token_pair, e);
+                    let token_pair = batch.token_pair.clone();
+                    self.rollups.lock().await.entry(token_pair.clone()).or_insert_with(|| PairRollup::new(&token_pair)).restore(batch);
+                    failed = Some(e);
+                }
+            }
+        }
+        match failed {
+            Some(e) => Err(e),
+            None => Ok(())
+        }
     }
 
-    
-}
\ No newline at end of file
+    // retries without the trades redis reports as already merged, until the write goes through
+    async fn write_batch(&self, batch : &mut RollupBatch, consumer_group : &str, now : i64) -> Result<Vec<OHLCVcandle>, anyhow::Error>{
+        loop {
+            let deltas = batch.deltas(now - self.lateness_secs);
+            let trade_ids = batch.trade_ids();
+            let merge = self
+                .ohlcv_manager
+                .merge_candles(&batch.token_pair, &deltas, &trade_ids, &self.stream_manager.stream_name, consumer_group, &batch.message_ids)
+                .await?;
+            let duplicates = match merge {
+                CandleMerge::Written(candles) => return Ok(candles),
+                CandleMerge::Duplicates(duplicates) => duplicates
+            };
+            let duplicates : HashSet<String> = duplicates.into_iter().collect();
+            let dropped = batch.drop_trades(&duplicates);
+            println!("Dropped {} trades of {} that were already merged", dropped, batch.token_pair);
+            if dropped == 0 {
+                return Err(anyhow::anyhow!("redis reported duplicates the batch does not hold"));
+            }
+        }
+    }
+}
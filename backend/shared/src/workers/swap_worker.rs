@@ -0,0 +1,118 @@
+use std::time::Duration;
+use tokio::time::sleep;
+use crate::{queues::stream_manager::StreamManager, services::swap_store::SwapStore, types::worker::StructeredTransaction};
+
+const SWAP_BATCH_SIZE : usize = 200;
+const SWAP_RETRY_DELAY : Duration = Duration::from_secs(2);
+// inserts the database rejects this many times in a row are given up on
+const SWAP_MAX_ATTEMPTS : u32 = 5;
+const SWAP_DEAD_LETTER_STREAM : &str = "swap_dead_letter_stream";
+
+/// Copies every swap of the trade stream into Postgres with its own consumer group.
+///
+/// Messages are read and inserted in batches and only acknowledged once the batch is stored, so a
+/// crashed worker picks its unacknowledged batch up again on restart. Inserts skip swaps already
+/// stored, which makes the redelivery harmless. A batch the database keeps rejecting is retried one
+/// swap at a time and the swaps still rejected go to a dead letter stream, while an unreachable
+/// database is waited out.
+pub struct SwapWorker{
+    pub stream_manager : StreamManager,
+    pub swap_store : SwapStore,
+    pub batch_size : usize,
+}
+
+impl SwapWorker {
+    pub async fn new() -> Result<Self, anyhow::Error>{
+        let stream_manager = StreamManager::new()?;
+        let swap_store = SwapStore::connect().await?;
+        // SWAP_BATCH_SIZE caps how many swaps go into one insert
+        let batch_size = std::env::var("SWAP_BATCH_SIZE")
+            .ok()
+            .and_then(|value| value.parse::<usize>().ok())
+            .filter(|value| *value > 0)
+            .unwrap_or(SWAP_BATCH_SIZE);
+        Ok(Self { stream_manager, swap_store, batch_size })
+    }
+
+    pub async fn start_processing(&self, consumer_group: String, consumer_name: String) {
+        println!("Swap Worker started and waiting for messages...");
+        let _ = self.stream_manager.init_stream(&consumer_group).await;
+
+        // whatever this consumer read before stopping goes first
+        loop {
+            match self.stream_manager.consume_pending(&consumer_group, &consumer_name, self.batch_size).await {
+                Ok(batch) if batch.is_empty() => break,
+                Ok(batch) => self.store_batch(&consumer_group, batch).await,
+                Err(e) => {
+                    println!("Error reading pending swaps : {}", e);
+                    sleep(SWAP_RETRY_DELAY).await;
+                }
+            }
+        }
+
+        loop {
+            match self.stream_manager.consume_batch(&consumer_group, &consumer_name, self.batch_size).await {
+                Ok(batch) if batch.is_empty() => {}
+                Ok(batch) => self.store_batch(&consumer_group, batch).await,
+                Err(e) => {
+                    println!("Error in redis queue : {}", e);
+                    sleep(Duration::from_millis(100)).await;
+                }
+            }
+        }
+    }
+
+    async fn store_batch(&self, consumer_group : &String, batch : Vec<(String, StructeredTransaction)>){
+        let (message_ids, txns) : (Vec<String>, Vec<StructeredTransaction>) = batch.into_iter().unzip();
+        let mut settled = message_ids.clone();
+        match self.insert_with_retries(&txns).await {
+            Ok(inserted) => println!("Stored {} of {} swaps", inserted, txns.len()),
+            Err(e) => {
+                println!("Giving up on a batch of {} swaps, storing them one by one : {}", txns.len(), e);
+                settled.clear();
+                for (message_id, txn) in message_ids.iter().zip(&txns) {
+                    if self.store_alone(message_id, txn).await {
+                        settled.push(message_id.clone());
+                    }
+                }
+            }
+        }
+        if let Err(e) = self.stream_manager.ack_many(consumer_group, &settled).await{
+            println!("Error in acknowledment of {} swap messages : {}", settled.len(), e);
+        }
+    }
+
+    // false when the swap could neither be stored nor dead lettered, its message stays pending
+    async fn store_alone(&self, message_id : &str, txn : &StructeredTransaction) -> bool{
+        let Err(e) = self.insert_with_retries(std::slice::from_ref(txn)).await else { return true };
+        println!("Dead lettering swap {}:{} : {}", txn.signature, txn.leg, e);
+        match self.stream_manager.dead_letter(SWAP_DEAD_LETTER_STREAM, message_id, txn, &e.to_string()).await {
+            Ok(_) => true,
+            Err(e) => {
+                println!("Error dead lettering message {} : {}", message_id, e);
+                false
+            }
+        }
+    }
+
+    // waits out an unreachable database, gives up after SWAP_MAX_ATTEMPTS rejections
+    async fn insert_with_retries(&self, txns : &[StructeredTransaction]) -> Result<u64, anyhow::Error>{
+        let mut attempts = 0;
+        loop {
+            match self.swap_store.insert_swaps(txns).await {
+                Ok(inserted) => return Ok(inserted),
+                Err(e) if SwapStore::is_unavailable(&e) => {
+                    println!("Swap store unavailable, retrying {} swaps : {}", txns.len(), e);
+                }
+                Err(e) => {
+                    attempts += 1;
+                    if attempts >= SWAP_MAX_ATTEMPTS {
+                        return Err(e);
+                    }
+                    println!("Error storing {} swaps (attempt {} of {}), retrying : {}", txns.len(), attempts, SWAP_MAX_ATTEMPTS, e);
+                }
+            }
+            sleep(SWAP_RETRY_DELAY).await;
+        }
+    }
+}
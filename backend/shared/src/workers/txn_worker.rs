@@ -2,11 +2,12 @@ use crate::queues::stream_manager::StreamManager;
 use crate::queues::{
  swap_txn_manager::SwapTxnQueueManager,
 };
-use crate::services::price_service::PriceService;
+use crate::services::{holder_service::HolderService, liquidity_service::LiquidityService, pool_state::pool_state_account, price_service::PriceService};
 use crate::{
-    redis::{pubsub_manager::PubSubManager, token_symbol_manager::TokenSymbolManager},
+    redis::{pool_manager::PoolManager, pubsub_manager::PubSubManager, token_symbol_manager::TokenSymbolManager},
     types::{
         grpc::{CustomTokenBalance, TransactionMetadata},
+        pool::PoolInfo,
         worker::{StructeredTransaction, Type},
     },
 };
@@ -14,13 +15,16 @@ use crate::{
     METEORA_DAMM_V1, METEORA_DAMM_V2, METEORA_DLMM, ORCA_CLMM, RADUIM_AMM_V4, RADUIM_CLMM,
 };
 use std::{collections::HashMap, time::Duration};
-use tokio::time::sleep;
+use chrono::Utc;
+use tokio::time::{sleep, Instant};
 
 #[derive(Debug)]
 struct SwapAnalysis {
     user_owner: String,
+    pool_owner: String,
     user_token_change: f64,
     pool_sol_change: f64,
+    token_mint: String,
     token_name: String,
     token_symbol: String,
 }
@@ -31,30 +35,60 @@ pub struct TxnWorker {
     stream_manager : StreamManager,
     pubsub_manager: PubSubManager,
     price_service: PriceService,
+    liquidity_service: LiquidityService,
+    holder_service: HolderService,
+    token_manager: TokenSymbolManager,
+    metadata_hold_secs: i64,
 }
 const SOL_MINT: &str = "So11111111111111111111111111111111111111112";
+const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+const USDT_MINT: &str = "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BJEYnyB";
+const SUPPLY_CHANGING_INSTRUCTIONS: [&str; 4] = [
+    "Instruction: MintTo",
+    "Instruction: MintToChecked",
+    "Instruction: Burn",
+    "Instruction: BurnChecked",
+];
 
 impl TxnWorker {
     pub fn new(swap_queue: SwapTxnQueueManager) -> Self {
         let token_manager = TokenSymbolManager::new().expect("Error creating a token symbol manager");
         let pubsub_manager = PubSubManager::new().expect("Error creating pubsub manager");
         let stream_manager = StreamManager::new().expect("unable to access stream from txn worker");
+        let pool_manager = PoolManager::new().expect("Error creating pool manager");
+        let holder_service = HolderService::new(pool_manager.clone());
+        let liquidity_service = LiquidityService::new(pool_manager, token_manager.clone(), pubsub_manager.clone());
+        let price_service = PriceService::new(token_manager.clone(), pubsub_manager.clone());
+        // SWAP_METADATA_HOLD_SECS bounds how long a new mint's swaps wait for its symbol before it is keyed by mint
+        let metadata_hold_secs = std::env::var("SWAP_METADATA_HOLD_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .unwrap_or(10);
         Self {
             swap_queue,
             pubsub_manager,
-            price_service: PriceService::new(token_manager),
-            stream_manager
+            price_service,
+            liquidity_service,
+            holder_service,
+            stream_manager,
+            token_manager,
+            metadata_hold_secs
         }
     }
 
     pub async fn start_processing(&self) {
         println!("Worker started and waiting for messages...");
+        let mut last_release = Instant::now();
         loop {
+            if last_release.elapsed() >= Duration::from_secs(1) {
+                self.release_held_swaps().await;
+                last_release = Instant::now();
+            }
             match self.swap_queue.dequeue_message().await {
                 Ok(Some(txn_message)) => {
                     println!("Got txn messsage from the queue");
                     println!("Txn Metadata : {:?}", txn_message);
-                    self.filter_and_send_txns(txn_message).await;
+                    self.filter_and_send_txns(txn_message, false).await;
                 }
                 Ok(None) => {
                     println!("Queue empty, no message recieved");
@@ -67,18 +101,45 @@ impl TxnWorker {
             }
         }
     }
-    async fn filter_and_send_txns(&self, txn_meta: TransactionMetadata) {
-        if let Some(dex_type) = self.detect_dex_type(&txn_meta.log_messages) {
-            if let Some(structured_txn) = self.transform_swap(&txn_meta, dex_type).await {
-                if let Err(e) = self.pubsub_manager.publish_transaction(structured_txn.clone()).await{
-                    println!("Failed to publish transaction to redis channel: {}", e);
+    // swaps held for a mint's metadata go out once it resolves, is known to be missing, or the hold runs out
+    async fn release_held_swaps(&self) {
+        let held_mints = match self.swap_queue.get_held_mints().await {
+            Ok(held_mints) => held_mints,
+            Err(e) => {
+                println!("Error reading held swaps : {}", e);
+                return;
+            }
+        };
+        let now = Utc::now().timestamp();
+        for (mint_address, held_since) in held_mints {
+            let resolved = self.token_manager.get_mint_info(&mint_address).await.is_some()
+                || self.token_manager.is_metadata_missing(&mint_address).await;
+            if !resolved && now - held_since < self.metadata_hold_secs {
+                continue;
+            }
+            match self.swap_queue.release_held(&mint_address).await {
+                Ok(txns) => {
+                    println!("Releasing {} held swaps of mint : {}", txns.len(), mint_address);
+                    for txn_meta in txns {
+                        self.filter_and_send_txns(txn_meta, true).await;
+                    }
                 }
+                Err(e) => println!("Error releasing held swaps of {} : {}", mint_address, e),
+            }
+        }
+    }
 
-                if let Err(e) = self.stream_manager.publish(structured_txn).await{
-                    println!("Failed to publish structured transaction into streams: {}", e);
-                }
-            };
-        };
+    async fn filter_and_send_txns(&self, txn_meta: TransactionMetadata, released: bool) {
+        if let Some(dex_type) = self.detect_dex_type(&txn_meta.log_messages)
+            && let Some(structured_txn) = self.transform_swap(&txn_meta, dex_type, released).await {
+            if let Err(e) = self.pubsub_manager.publish_transaction(structured_txn.clone()).await{
+                println!("Failed to publish transaction to redis channel: {}", e);
+            }
+
+            if let Err(e) = self.stream_manager.publish(structured_txn).await{
+                println!("Failed to publish structured transaction into streams: {}", e);
+            }
+        }
     }
 
     fn detect_dex_type(&self, log_messages: &[String]) -> Option<&'static str> {
@@ -116,7 +177,7 @@ impl TxnWorker {
 
             owner_balances
                 .entry(pre.owner.clone())
-                .or_insert(Vec::new())
+                .or_default()
                 .push((pre.mint.clone(), pre_amount, post_amount));
         }
 
@@ -144,7 +205,11 @@ impl TxnWorker {
 
         if let Some(user_info) = owner_balances.get(&user_owner) {
             for (token_mint, _, _) in user_info {
-                if let Some(token_info) = self.price_service.get_mint_info(token_mint).await {
+                if token_mint == SOL_MINT {
+                    continue;
+                }
+                // never wait on RPC here, unresolved mints are enriched once the resolver publishes their metadata
+                if let Some(token_info) = self.price_service.get_cached_mint_info(token_mint).await {
                     token_name = token_info.token_name;
                     token_symbol = token_info.token_symbol;
                 }
@@ -155,6 +220,7 @@ impl TxnWorker {
         let pool_balances = owner_balances.get(&pool_owner)?;
         let mut pool_sol_change = 0.0;
         let mut user_token_change = 0.0;
+        let mut token_mint = String::new();
 
         for (mint, pre_amount, post_amount) in user_balances {
             let change = post_amount - pre_amount;
@@ -163,6 +229,7 @@ impl TxnWorker {
                 pool_sol_change = change;
             } else {
                 user_token_change = change;
+                token_mint = mint.clone();
             }
         }
 
@@ -175,8 +242,10 @@ impl TxnWorker {
 
         Some(SwapAnalysis {
             user_owner,
+            pool_owner,
             user_token_change,
             pool_sol_change,
+            token_mint,
             token_name,
             token_symbol,
         })
@@ -186,6 +255,7 @@ impl TxnWorker {
         &self,
         txn_meta: &TransactionMetadata,
         dex_type: &str,
+        released: bool,
     ) -> Option<StructeredTransaction> {
         let pre_balance_array = &txn_meta.pre_token_balances;
         let post_balance_array = &txn_meta.post_token_balances;
@@ -230,6 +300,17 @@ impl TxnWorker {
             };
         }
 
+        let token_pair = match self.token_pair(&analysis, released).await {
+            Some(token_pair) => token_pair,
+            None => match self.swap_queue.hold_message(&analysis.token_mint, txn_meta).await {
+                Ok(()) => return None,
+                Err(e) => {
+                    println!("Error holding swap of {}, keying it now : {}", analysis.token_mint, e);
+                    self.token_pair(&analysis, true).await?
+                }
+            },
+        };
+
         let (purchase_type, token_amount_change, sol_amount_abs) = if analysis.pool_sol_change < 0.0
         {
             println!(
@@ -258,11 +339,12 @@ impl TxnWorker {
             return None;
         };
 
-        let token_pair = if !analysis.token_symbol.is_empty() {
-            format!("{}/SOL", analysis.token_symbol)
-        } else {
-            format!("{}/SOL", "UNKNOWN")
-        };
+        if (analysis.token_mint == USDC_MINT || analysis.token_mint == USDT_MINT) && sol_amount_abs > 0.0 {
+            // a stablecoin leg prices SOL directly, feed it to the dex price oracle
+            self.price_service
+                .record_dex_sol_price(token_amount_change / sol_amount_abs, token_amount_change)
+                .await;
+        }
 
         let token_name = if !analysis.token_name.is_empty() {
             analysis.token_name
@@ -270,39 +352,132 @@ impl TxnWorker {
             analysis.token_symbol.clone()
         };
 
-        if let Some(sol_price) = self.price_service.get_sol_price().await {
-            let usd_value = sol_amount_abs * sol_price;
-            let token_price = if token_amount_change > 0.0 {
-                usd_value / token_amount_change
-            } else {
-                0.0
-            };
+        let supply_changed = logs.iter().any(|log| {
+            SUPPLY_CHANGING_INSTRUCTIONS.iter().any(|instruction| log.ends_with(instruction))
+        });
 
-            Some(StructeredTransaction {
-                date: chrono::Utc::now(),
-                purchase_type,
-                usd_value: Some(usd_value),
-                token_quantity: token_amount_change,
-                token_price,
-                token_pair,
-                token_name,
-                owner: analysis.user_owner,
-                dex_type: dex_type.to_string(),
-                dex_tag: dex_tag.to_string(),
-            })
+        let pool = self.pool_from_balances(txn_meta, &analysis.pool_owner, &analysis.token_mint, &token_pair, &dex_tag);
+        let sol_price = self.price_service.get_sol_price().await;
+        let usd_value = sol_price.map(|sol_price| sol_amount_abs * sol_price);
+        let token_price = match usd_value {
+            Some(usd_value) if token_amount_change > 0.0 => usd_value / token_amount_change,
+            _ => 0.0,
+        };
+
+        if let Some((pool, token_reserve, sol_reserve)) = pool {
+            let token_price_usd = if token_price > 0.0 { Some(token_price) } else { None };
+            if let Err(e) = self.liquidity_service.record_swap_reserves(&pool, token_reserve, sol_reserve, token_price_usd).await {
+                println!("Error recording pool reserves for {} : {}", pool.pool_id, e);
+            }
+        }
+
+        if !analysis.token_mint.is_empty()
+            && let Err(e) = self.holder_service.apply_post_balances(&analysis.token_mint, post_balance_array).await {
+            println!("Error updating holders for {} : {}", analysis.token_mint, e);
+        }
+
+        Some(StructeredTransaction {
+            // bucketed by block time so a delayed or replayed swap lands in the candle it belongs to
+            date: chrono::DateTime::from_timestamp_millis(txn_meta.order.block_time)
+                .filter(|_| txn_meta.order.block_time > 0)
+                .unwrap_or_else(chrono::Utc::now),
+            purchase_type,
+            usd_value,
+            token_quantity: token_amount_change,
+            quote_quantity: sol_amount_abs,
+            token_price,
+            token_pair,
+            token_name,
+            token_mint: analysis.token_mint,
+            supply_changed,
+            owner: analysis.user_owner,
+            dex_type: dex_type.to_string(),
+            dex_tag: dex_tag.to_string(),
+            order: txn_meta.order,
+            signature: txn_meta.signature.clone(),
+            // one swap is parsed per transaction for now
+            leg: 0,
+        })
+    }
+
+    /// The pair a mint's market is keyed by, `None` while the swap should wait for the mint's metadata.
+    ///
+    /// The first pair a mint trades under is pinned in redis, so candles, stats, positions and swaps of a
+    /// market never split across a `MINT/SOL` and a `SYMBOL/SOL` key when the symbol shows up later.
+    /// A mint without a symbol is keyed by its address once the metadata is known to be missing or the
+    /// swap has been held for `metadata_hold_secs`.
+    async fn token_pair(&self, analysis: &SwapAnalysis, released: bool) -> Option<String> {
+        if analysis.token_mint.is_empty() {
+            return Some(format!("{}/SOL", "UNKNOWN"));
+        }
+        if let Some(token_pair) = self.token_manager.get_token_pair(&analysis.token_mint).await {
+            return Some(token_pair);
+        }
+        let token_pair = if !analysis.token_symbol.is_empty() {
+            format!("{}/SOL", analysis.token_symbol)
+        } else if released || self.token_manager.is_metadata_missing(&analysis.token_mint).await {
+            format!("{}/SOL", analysis.token_mint)
         } else {
-            Some(StructeredTransaction {
-                date: chrono::Utc::now(),
-                purchase_type,
-                usd_value: None,
-                token_quantity: token_amount_change,
-                token_price: 0.0,
-                token_pair,
-                token_name,
-                owner: analysis.user_owner,
-                dex_type: dex_type.to_string(),
-                dex_tag: dex_tag.to_string(),
-            })
+            return None;
+        };
+        match self.token_manager.pin_token_pair(&analysis.token_mint, &token_pair).await {
+            Ok(pinned) => Some(pinned),
+            Err(e) => {
+                println!("Error pinning market {} for mint {} : {}", token_pair, analysis.token_mint, e);
+                Some(token_pair)
+            }
         }
     }
+
+    // the pool's vaults are the post balances owned by the pool owner, their amounts after the swap are the reserves.
+    // the dex instruction passing both vaults names the program and the pool state account
+    fn pool_from_balances(
+        &self,
+        txn_meta: &TransactionMetadata,
+        pool_owner: &str,
+        token_mint: &str,
+        token_pair: &str,
+        dex_tag: &str,
+    ) -> Option<(PoolInfo, f64, f64)> {
+        let vault = |mint: &str| {
+            txn_meta
+                .post_token_balances
+                .iter()
+                .find(|balance| balance.owner == pool_owner && balance.mint == mint)
+                .and_then(|balance| {
+                    let address = txn_meta.account_keys.get(balance.account_index as usize)?;
+                    let amount = balance.ui_token_amount.as_ref()?;
+                    Some((address.clone(), amount.ui_amount, amount.decimals))
+                })
+                .filter(|(address, _, _)| !address.is_empty())
+        };
+        let (token_vault, token_reserve, token_decimals) = vault(token_mint)?;
+        let (sol_vault, sol_reserve, sol_decimals) = vault(SOL_MINT)?;
+        let pool_state = txn_meta
+            .dex_instructions
+            .iter()
+            .filter(|instruction| instruction.accounts.contains(&token_vault) && instruction.accounts.contains(&sol_vault))
+            .find_map(|instruction| {
+                let pool_id = pool_state_account(&instruction.program_id, &instruction.accounts, pool_owner)?;
+                Some((pool_id, instruction.program_id.clone()))
+            });
+        let (pool_id, program_id) = pool_state.unwrap_or_else(|| (token_vault.clone(), String::new()));
+
+        Some((
+            PoolInfo {
+                pool_id,
+                pool_owner: pool_owner.to_string(),
+                program_id,
+                dex_tag: dex_tag.to_string(),
+                token_pair: token_pair.to_string(),
+                token_mint: token_mint.to_string(),
+                token_vault,
+                sol_vault,
+                token_decimals,
+                sol_decimals,
+            },
+            token_reserve,
+            sol_reserve,
+        ))
+    }
 }
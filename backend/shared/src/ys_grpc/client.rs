@@ -1,8 +1,132 @@
-use crate::{queues::swap_txn_manager::SwapTxnQueueManager, METEORA_DAMM_V1, METEORA_DAMM_V2, METEORA_DLMM, RADUIM_AMM_V4, RADUIM_CLMM, ORCA_CLMM};
+use crate::{queues::swap_txn_manager::SwapTxnQueueManager, redis::{pool_manager::PoolManager, pubsub_manager::PubSubManager, token_symbol_manager::TokenSymbolManager}, services::{liquidity_service::{LiquidityService, TOKEN_2022_PROGRAM, TOKEN_PROGRAM}, pool_state::is_decodable, metadata_resolver::decode_mint_account, outbound::{host_of, OUTBOUND}, supply_service::apply_mint_account, price_oracle::{decode_pyth_price_update, PYTH_RECEIVER_PROGRAM, PYTH_SOL_USD_ACCOUNT}}, types::{grpc::DexInstruction, worker::TradeOrder}, METEORA_DAMM_V1, METEORA_DAMM_V2, METEORA_DLMM, RADUIM_AMM_V4, RADUIM_CLMM, ORCA_CLMM};
 use futures::{SinkExt, StreamExt}; // used for something that already implement the sink and stream trait. its like an interface for them, which provides them extra methods like .send().await or .next().await() or .map() or .filter()
-use std::collections::HashMap;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+use std::{collections::{BTreeMap, HashMap, HashSet}, str::FromStr, sync::Arc, time::{Duration, Instant}};
+use tokio::sync::mpsc;
 use yellowstone_grpc_client::{ClientTlsConfig, GeyserGrpcClient};
-use yellowstone_grpc_proto::geyser::{SubscribeRequest, SubscribeRequestFilterTransactions};
+use yellowstone_grpc_proto::{geyser::{SubscribeRequest, SubscribeRequestFilterAccounts, SubscribeRequestFilterBlocksMeta, SubscribeRequestFilterTransactions}, solana::storage::confirmed_block::{Message, TransactionStatusMeta}};
+
+const POOL_SUBSCRIPTION_REFRESH: Duration = Duration::from_secs(30);
+const POOL_ACTIVE_WINDOW_SECS: i64 = 86400;
+// how long a trade waits for the block meta of its slot before the block time is asked from the rpc
+const BLOCK_TIME_WAIT: Duration = Duration::from_secs(10);
+const BLOCK_TIME_SLOTS_KEPT: u64 = 512;
+// upper bound on a block time lookup including its retries, the trades fall back to their update time after it
+const BLOCK_TIME_LOOKUP_TIMEOUT: Duration = Duration::from_secs(15);
+const TOKEN_MINTS_FILTER: &str = "token_mints";
+const POOL_STATES_FILTER: &str = "pool_states";
+const DEX_PROGRAMS: [&str; 6] = [RADUIM_AMM_V4, RADUIM_CLMM, METEORA_DLMM, METEORA_DAMM_V2, METEORA_DAMM_V1, ORCA_CLMM];
+
+// a swap transaction waiting for the block time of its slot
+struct HeldTrade {
+    meta: TransactionStatusMeta,
+    account_keys: Vec<String>,
+    dex_instructions: Vec<DexInstruction>,
+    index: u64,
+    signature: String,
+    created_at: Option<i64>, // when the node emitted the update, the last resort for the block time
+}
+
+/// Block times by slot from the block meta updates, and the trades still waiting for theirs.
+///
+/// Transaction updates carry no block time and the block meta of a slot only arrives after its
+/// transactions, so trades are held back per slot until it does. Slots whose block meta never
+/// shows up wait in `looking_up` while their block time is asked from the rpc off the stream loop.
+#[derive(Default)]
+struct BlockClock {
+    block_times: BTreeMap<u64, i64>, // unix millis
+    held: BTreeMap<u64, (Instant, Vec<HeldTrade>)>,
+    looking_up: HashMap<u64, Vec<HeldTrade>>,
+}
+
+impl BlockClock {
+    fn block_time(&self, slot: u64) -> Option<i64> {
+        self.block_times.get(&slot).copied()
+    }
+
+    fn hold(&mut self, slot: u64, trade: HeldTrade) {
+        self.held.entry(slot).or_insert_with(|| (Instant::now(), Vec::new())).1.push(trade);
+    }
+
+    // records the block time of a slot and returns the trades that were waiting for it
+    fn record(&mut self, slot: u64, block_time: i64) -> Vec<HeldTrade> {
+        self.block_times.insert(slot, block_time);
+        self.block_times = self.block_times.split_off(&slot.saturating_sub(BLOCK_TIME_SLOTS_KEPT));
+        self.held.remove(&slot).map(|(_, trades)| trades).unwrap_or_default()
+    }
+
+    // slots whose block meta did not show up within BLOCK_TIME_WAIT
+    fn take_expired(&mut self) -> Vec<(u64, Vec<HeldTrade>)> {
+        let expired: Vec<u64> = self
+            .held
+            .iter()
+            .filter(|(_, (held_at, _))| held_at.elapsed() >= BLOCK_TIME_WAIT)
+            .map(|(slot, _)| *slot)
+            .collect();
+        expired
+            .into_iter()
+            .filter_map(|slot| self.held.remove(&slot).map(|(_, trades)| (slot, trades)))
+            .collect()
+    }
+
+    fn start_lookup(&mut self, slot: u64, trades: Vec<HeldTrade>) {
+        self.looking_up.entry(slot).or_default().extend(trades);
+    }
+
+    // trades of a slot whose rpc lookup returned, the block time is recorded when there is one
+    fn finish_lookup(&mut self, slot: u64, block_time: Option<i64>) -> Vec<HeldTrade> {
+        if let Some(block_time) = block_time {
+            self.block_times.insert(slot, block_time);
+        }
+        self.looking_up.remove(&slot).unwrap_or_default()
+    }
+
+    fn take_all(&mut self) -> Vec<(u64, Vec<HeldTrade>)> {
+        let held = std::mem::take(&mut self.held).into_iter().map(|(slot, (_, trades))| (slot, trades));
+        held.chain(std::mem::take(&mut self.looking_up)).collect()
+    }
+}
+
+async fn enqueue_trade(queue: &SwapTxnQueueManager, slot: u64, block_time: i64, trade: HeldTrade) {
+    let order = TradeOrder {
+        block_time,
+        slot,
+        index: trade.index,
+    };
+    match queue.enqueue_message(trade.meta, trade.account_keys, order, trade.signature, trade.dex_instructions).await {
+        Ok(_) => {
+            print!("Metadata pushed to queue")
+        }
+        Err(e) => {
+            println!("Redis error occured while pushing: {}", e)
+        }
+    };
+}
+
+// instructions of the supported dex programs with their accounts resolved, the pool state account is among them
+fn dex_instructions(message: Option<&Message>, meta: &TransactionStatusMeta, account_keys: &[String]) -> Vec<DexInstruction> {
+    let resolve = |program_id_index: u32, accounts: &[u8]| {
+        let program_id = account_keys.get(program_id_index as usize)?;
+        if !DEX_PROGRAMS.contains(&program_id.as_str()) {
+            return None;
+        }
+        Some(DexInstruction {
+            program_id: program_id.clone(),
+            accounts: accounts.iter().filter_map(|index| account_keys.get(*index as usize).cloned()).collect(),
+        })
+    };
+    let top_level = message
+        .into_iter()
+        .flat_map(|message| &message.instructions)
+        .filter_map(|instruction| resolve(instruction.program_id_index, &instruction.accounts));
+    let inner = meta
+        .inner_instructions
+        .iter()
+        .flat_map(|inner| &inner.instructions)
+        .filter_map(|instruction| resolve(instruction.program_id_index, &instruction.accounts));
+    top_level.chain(inner).collect()
+}
 
 #[derive(Debug, Clone)]
 pub struct GrpcClient {
@@ -15,7 +139,7 @@ impl GrpcClient {
         println!("Initializing grpc endpoint along with token access...");
         Self {
             grpc_url: endpoint,
-            token: token,
+            token,
         }
     }
 
@@ -34,7 +158,10 @@ impl GrpcClient {
         Ok(client)
     }
 
-    fn create_subscription(&self) -> SubscribeRequest {
+    // `pool_vaults` are the token vaults of recently traded pools, their balances are the pool reserves.
+    // `pool_states` are their decodable state accounts, which hold the fees and active liquidity.
+    // `token_mints` are the mints traded in those pools, their account updates carry mints and burns
+    fn create_subscription(&self, pool_vaults: Vec<String>, pool_states: Vec<String>, token_mints: Vec<String>) -> SubscribeRequest {
         let mut transactions = HashMap::new();
         transactions.insert(
             "raduim_swap_transactions".to_string(),
@@ -52,15 +179,63 @@ impl GrpcClient {
             },
         );
 
+        // block metas carry the block time transaction updates lack
+        let mut blocks_meta = HashMap::new();
+        blocks_meta.insert("block_times".to_string(), SubscribeRequestFilterBlocksMeta {});
+
+        let mut accounts = HashMap::new();
+        accounts.insert(
+            "pyth_sol_usd".to_string(),
+            SubscribeRequestFilterAccounts {
+                account: vec![std::env::var("PYTH_SOL_USD_ACCOUNT").unwrap_or_else(|_| PYTH_SOL_USD_ACCOUNT.to_string())],
+                owner: vec![],
+                filters: vec![],
+                nonempty_txn_signature: None,
+            },
+        );
+        if !pool_vaults.is_empty() {
+            accounts.insert(
+                "pool_vaults".to_string(),
+                SubscribeRequestFilterAccounts {
+                    account: pool_vaults,
+                    owner: vec![],
+                    filters: vec![],
+                    nonempty_txn_signature: None,
+                },
+            );
+        }
+        if !pool_states.is_empty() {
+            accounts.insert(
+                POOL_STATES_FILTER.to_string(),
+                SubscribeRequestFilterAccounts {
+                    account: pool_states,
+                    owner: vec![],
+                    filters: vec![],
+                    nonempty_txn_signature: None,
+                },
+            );
+        }
+        if !token_mints.is_empty() {
+            accounts.insert(
+                TOKEN_MINTS_FILTER.to_string(),
+                SubscribeRequestFilterAccounts {
+                    account: token_mints,
+                    owner: vec![],
+                    filters: vec![],
+                    nonempty_txn_signature: None,
+                },
+            );
+        }
+
         println!("created subscription for grpc stream");
 
         SubscribeRequest {
-            accounts: HashMap::new(),
+            accounts,
             slots: HashMap::new(),
             transactions,
             transactions_status: HashMap::new(),
             blocks: HashMap::new(),
-            blocks_meta: HashMap::new(),
+            blocks_meta,
             entry: HashMap::new(),
             commitment: None,
             accounts_data_slice: vec![],
@@ -70,46 +245,239 @@ impl GrpcClient {
     }
     pub async fn start_listening(&self) -> Result<(), anyhow::Error> {
         let mut client = self.client_connection().await?;
-        let subcription = self.create_subscription();
+        let pool_manager = PoolManager::new().expect("error initializing pool manager");
+        let pool_limit = std::env::var("POOL_SUBSCRIPTION_LIMIT")
+            .ok()
+            .and_then(|limit| limit.parse::<isize>().ok())
+            .unwrap_or(2000);
+        let mut subscribed_vaults = self.active_pool_vaults(&pool_manager, pool_limit).await;
+        let mut subscribed_states = self.active_pool_states(&pool_manager, pool_limit).await;
+        let mut subscribed_mints = self.active_mints(&pool_manager, pool_limit).await;
+        let subcription = self.create_subscription(
+            subscribed_vaults.iter().cloned().collect(),
+            subscribed_states.iter().cloned().collect(),
+            subscribed_mints.iter().cloned().collect(),
+        );
         let (mut sink, mut stream) = client.subscribe().await?; //stream is nothing but the data (multiple items) you get from a source asynchronously.
 
         sink.send(subcription).await?;
         let queue = SwapTxnQueueManager::new().expect("error initializing queue");
+        let token_manager = TokenSymbolManager::new().expect("error initializing token manager for oracle updates");
+        let liquidity_service = LiquidityService::new(
+            pool_manager.clone(),
+            token_manager.clone(),
+            PubSubManager::new().expect("error initializing pubsub manager for liquidity updates"),
+        );
+        let pyth_receiver = Pubkey::from_str(PYTH_RECEIVER_PROGRAM)?;
+        let token_programs = [Pubkey::from_str(TOKEN_PROGRAM)?.to_bytes(), Pubkey::from_str(TOKEN_2022_PROGRAM)?.to_bytes()];
+        let mut refresh = tokio::time::interval(POOL_SUBSCRIPTION_REFRESH);
+        refresh.tick().await;
+        let mut block_clock = BlockClock::default();
+        let rpc_client = std::env::var("HELIUS_URL").ok().map(|rpc_url| (host_of(&rpc_url), Arc::new(RpcClient::new(rpc_url))));
+        let (block_time_tx, mut block_time_rx) = mpsc::unbounded_channel::<(u64, Option<i64>)>();
+        let mut block_time_expiry = tokio::time::interval(BLOCK_TIME_WAIT / 2);
 
         println!("Listening for transactions from grpc...");
 
-        while let Some(update) = stream.next().await {
-            match update {
-                Ok(update_item) => {
-                    if let Some(item) = update_item.update_oneof {
-                        match item {
-                        yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof::Transaction(txn_item) => {
-                            if let Some(txn) = txn_item.transaction{
-                                if let Some(txn_meta) = txn.meta{
-                                    match queue.enqueue_message(txn_meta).await{
-                                        Ok(_) => {
-                                            print!("Metadata pushed to queue")
+        loop {
+            tokio::select! {
+                update = stream.next() => {
+                    let Some(update) = update else { break };
+                    match update {
+                        Ok(update_item) => {
+                            let created_at = update_item.created_at.map(|created_at| created_at.seconds * 1000 + created_at.nanos as i64 / 1_000_000);
+                            let mint_update = update_item.filters.iter().any(|filter| filter == TOKEN_MINTS_FILTER);
+                            let pool_state_update = update_item.filters.iter().any(|filter| filter == POOL_STATES_FILTER);
+                            if let Some(item) = update_item.update_oneof {
+                                match item {
+                                yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof::Transaction(txn_item) => {
+                                    let slot = txn_item.slot;
+                                    if let Some(txn) = txn_item.transaction{
+                                        let signature = bs58::encode(&txn.signature).into_string();
+                                        if let Some(txn_meta) = txn.meta{
+                                            // token balances and instructions reference accounts by index into the static then loaded keys
+                                            let message = txn.transaction.and_then(|transaction| transaction.message);
+                                            let account_keys: Vec<String> = message
+                                                .as_ref()
+                                                .map(|message| message.account_keys.as_slice())
+                                                .unwrap_or_default()
+                                                .iter()
+                                                .chain(txn_meta.loaded_writable_addresses.iter())
+                                                .chain(txn_meta.loaded_readonly_addresses.iter())
+                                                .map(|key| Pubkey::try_from(key.as_slice()).map(|key| key.to_string()).unwrap_or_default())
+                                                .collect();
+                                            let dex_instructions = dex_instructions(message.as_ref(), &txn_meta, &account_keys);
+                                            let trade = HeldTrade { meta: txn_meta, account_keys, dex_instructions, index: txn.index, signature, created_at };
+                                            match block_clock.block_time(slot) {
+                                                Some(block_time) => enqueue_trade(&queue, slot, block_time, trade).await,
+                                                None => block_clock.hold(slot, trade),
+                                            }
                                         }
-                                        Err(e) => {
-                                            println!("Redis error occured while pushing: {}",e)
+                                        else {
+                                            println!("Metadata doesn't exist")
                                         }
-                                    };
+                                    }
                                 }
-                                else {
-                                    println!("Metadata doesn't exist")
+                                yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof::BlockMeta(block_meta) => {
+                                    let Some(block_time) = block_meta.block_time else { continue };
+                                    for trade in block_clock.record(block_meta.slot, block_time.timestamp * 1000) {
+                                        enqueue_trade(&queue, block_meta.slot, block_time.timestamp * 1000, trade).await;
+                                    }
                                 }
+                                yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof::Account(account_item) => {
+                                    if let Some(account) = account_item.account{
+                                        if mint_update {
+                                            let mint_address = Pubkey::try_from(account.pubkey.as_slice()).map(|key| key.to_string()).unwrap_or_default();
+                                            match decode_mint_account(&account.data) {
+                                                Some(mint_account) => {
+                                                    if let Err(e) = apply_mint_account(&token_manager, &mint_address, &mint_account).await {
+                                                        println!("Error updating supply of mint {} : {}", mint_address, e);
+                                                    }
+                                                }
+                                                None => println!("Unable to decode mint account {}", mint_address),
+                                            }
+                                            continue;
+                                        }
+                                        if pool_state_update {
+                                            let pool_id = Pubkey::try_from(account.pubkey.as_slice()).map(|key| key.to_string()).unwrap_or_default();
+                                            let program_id = Pubkey::try_from(account.owner.as_slice()).map(|key| key.to_string()).unwrap_or_default();
+                                            if let Err(e) = liquidity_service.handle_pool_state(&pool_id, &program_id, &account.data).await {
+                                                println!("Error updating liquidity for pool state {} : {}", pool_id, e);
+                                            }
+                                            continue;
+                                        }
+                                        if token_programs.iter().any(|program| account.owner == program) {
+                                            let vault = Pubkey::try_from(account.pubkey.as_slice()).map(|key| key.to_string()).unwrap_or_default();
+                                            if let Err(e) = liquidity_service.handle_vault_account(&vault, &account.data).await {
+                                                println!("Error updating liquidity for vault {} : {}", vault, e);
+                                            }
+                                            continue;
+                                        }
+                                        if account.owner != pyth_receiver.to_bytes() {
+                                            println!("Ignoring price account not owned by the pyth receiver program");
+                                            continue;
+                                        }
+                                        match decode_pyth_price_update(&account.data) {
+                                            Ok(quote) => {
+                                                if let Err(e) = token_manager.store_oracle_quote(&quote).await{
+                                                    println!("Redis error occured while storing pyth quote: {}",e)
+                                                }
+                                            }
+                                            Err(e) => {
+                                                println!("Unable to decode pyth price account : {}",e)
+                                            }
+                                        }
+                                    }
+                                }
+                                _ => {println!("recieved non-transaction item")}
+                            }
                             }
                         }
-                        _ => {println!("recieved non-transaction item")}
+                        Err(e) => {
+                            println!("Stream error : {}", e);
+                            break;
+                        }
                     }
+                }
+                _ = block_time_expiry.tick() => {
+                    for (slot, trades) in block_clock.take_expired() {
+                        println!("No block meta for slot {}, looking up the block time of {} trades", slot, trades.len());
+                        let Some((rpc_host, rpc_client)) = &rpc_client else {
+                            for trade in trades {
+                                let block_time = trade.created_at.unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+                                enqueue_trade(&queue, slot, block_time, trade).await;
+                            }
+                            continue;
+                        };
+                        block_clock.start_lookup(slot, trades);
+                        // looked up off the loop so a slow rpc does not stall the stream
+                        let (rpc_host, rpc_client, block_time_tx) = (rpc_host.clone(), Arc::clone(rpc_client), block_time_tx.clone());
+                        tokio::spawn(async move {
+                            let lookup = OUTBOUND.call(&rpc_host, || rpc_client.get_block_time(slot));
+                            let block_time = match tokio::time::timeout(BLOCK_TIME_LOOKUP_TIMEOUT, lookup).await {
+                                Ok(Ok(block_time)) => Some(block_time * 1000),
+                                Ok(Err(e)) => {
+                                    println!("Error getting block time of slot {} : {}", slot, e);
+                                    None
+                                }
+                                Err(_) => {
+                                    println!("Block time lookup of slot {} timed out", slot);
+                                    None
+                                }
+                            };
+                            let _ = block_time_tx.send((slot, block_time));
+                        });
                     }
                 }
-                Err(e) => {
-                    println!("Stream error : {}", e);
-                    break;
+                Some((slot, block_time)) = block_time_rx.recv() => {
+                    // the block meta may still have shown up while the lookup ran
+                    let block_time = block_time.or(block_clock.block_time(slot));
+                    let trades = block_clock.finish_lookup(slot, block_time);
+                    println!("Releasing {} trades of slot {}", trades.len(), slot);
+                    for trade in trades {
+                        let block_time = block_time.or(trade.created_at).unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+                        enqueue_trade(&queue, slot, block_time, trade).await;
+                    }
                 }
+                _ = refresh.tick() => {
+                    // resending the request replaces the filters on the open stream, so new pools are picked up without reconnecting
+                    let vaults = self.active_pool_vaults(&pool_manager, pool_limit).await;
+                    let states = self.active_pool_states(&pool_manager, pool_limit).await;
+                    let mints = self.active_mints(&pool_manager, pool_limit).await;
+                    if vaults != subscribed_vaults || states != subscribed_states || mints != subscribed_mints {
+                        println!("Updating pool subscription : {} vaults, {} pool states, {} mints", vaults.len(), states.len(), mints.len());
+                        sink.send(self.create_subscription(
+                            vaults.iter().cloned().collect(),
+                            states.iter().cloned().collect(),
+                            mints.iter().cloned().collect(),
+                        )).await?;
+                        subscribed_vaults = vaults;
+                        subscribed_states = states;
+                        subscribed_mints = mints;
+                    }
+                }
+            }
+        }
+        // the stream ended, trades still waiting keep the time their update was emitted rather than being lost
+        for (slot, trades) in block_clock.take_all() {
+            for trade in trades {
+                let block_time = trade.created_at.unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+                enqueue_trade(&queue, slot, block_time, trade).await;
             }
         }
         Ok(())
     }
+
+    async fn active_pool_vaults(&self, pool_manager: &PoolManager, limit: isize) -> HashSet<String> {
+        let active_since = chrono::Utc::now().timestamp() - POOL_ACTIVE_WINDOW_SECS;
+        match pool_manager.get_active_vaults(active_since, limit).await {
+            Ok(vaults) => vaults.into_iter().collect(),
+            Err(e) => {
+                println!("Error getting active pool vaults : {}", e);
+                HashSet::new()
+            }
+        }
+    }
+
+    async fn active_pool_states(&self, pool_manager: &PoolManager, limit: isize) -> HashSet<String> {
+        let active_since = chrono::Utc::now().timestamp() - POOL_ACTIVE_WINDOW_SECS;
+        match pool_manager.get_active_pools(active_since, limit).await {
+            Ok(pools) => pools.into_iter().filter(|pool| is_decodable(&pool.program_id)).map(|pool| pool.pool_id).collect(),
+            Err(e) => {
+                println!("Error getting active pool states : {}", e);
+                HashSet::new()
+            }
+        }
+    }
+
+    async fn active_mints(&self, pool_manager: &PoolManager, limit: isize) -> HashSet<String> {
+        let active_since = chrono::Utc::now().timestamp() - POOL_ACTIVE_WINDOW_SECS;
+        match pool_manager.get_active_mints(active_since, limit).await {
+            Ok(mints) => mints.into_iter().collect(),
+            Err(e) => {
+                println!("Error getting active token mints : {}", e);
+                HashSet::new()
+            }
+        }
+    }
 }
@@ -24,6 +24,20 @@ echo ""
 # Create log directory
 mkdir -p /tmp/logs
 
+# Bring the schema up to date before anything writes to postgres
+if [ -n "$DATABASE_URL" ]; then
+    echo "Running database migrations..."
+    if ! ./migration up > /tmp/logs/migration.log 2>&1; then
+        echo "❌ Database migrations failed!"
+        echo "Logs:"
+        cat /tmp/logs/migration.log
+        exit 1
+    fi
+    echo "✅ Database schema up to date"
+else
+    echo "⚠️ DATABASE_URL not set, skipping migrations"
+fi
+
 # Start services with output redirection
 echo "Starting API Server..."
 ./api_server > /tmp/logs/api_server.log 2>&1 &
@@ -91,6 +105,24 @@ if ! kill -0 $OHLCV_PID 2>/dev/null; then
 fi
 echo "✅ OHLCV Worker started (PID: $OHLCV_PID)"
 
+# the swap worker only writes to postgres, without it there is nothing to run
+if [ -n "$DATABASE_URL" ]; then
+    echo "Starting Swap Worker..."
+    ./swap_worker > /tmp/logs/swap_worker.log 2>&1 &
+    SWAP_PID=$!
+    sleep 1
+
+    if ! kill -0 $SWAP_PID 2>/dev/null; then
+        echo "❌ Swap Worker failed to start!"
+        echo "Logs:"
+        cat /tmp/logs/swap_worker.log
+        exit 1
+    fi
+    echo "✅ Swap Worker started (PID: $SWAP_PID)"
+else
+    echo "⚠️ DATABASE_URL not set, skipping the Swap Worker"
+fi
+
 echo "Starting Metrics Service..."
 ./metrics_service > /tmp/logs/metrics_service.log 2>&1 &
 SERVICE_PID=$!
@@ -114,6 +146,7 @@ echo "   gRPC Server: $GRPC_PID"
 echo "   Txn Worker: $TXN_PID"
 echo "   Metrics Worker: $METRICS_PID"
 echo "   OHLCV Worker: $OHLCV_PID"
+echo "   Swap Worker: ${SWAP_PID:-not running}"
 echo "   Metrics Service: $SERVICE_PID"
 echo ""
 echo "🌐 Listening on http://0.0.0.0:8080"
@@ -125,6 +158,7 @@ echo "   /tmp/logs/grpc_server.log"
 echo "   /tmp/logs/txn_worker.log"
 echo "   /tmp/logs/metrics_worker.log"
 echo "   /tmp/logs/ohlcv_worker.log"
+echo "   /tmp/logs/swap_worker.log"
 echo "   /tmp/logs/metrics_service.log"
 echo ""
 echo "⏳ Monitoring services... (Ctrl+C to stop)"
@@ -132,7 +166,7 @@ echo ""
 
 # Monitor processes and restart if they die
 while true; do
-    for pid in $API_PID $GRPC_PID $TXN_PID $METRICS_PID $OHLCV_PID $SERVICE_PID; do
+    for pid in $API_PID $GRPC_PID $TXN_PID $METRICS_PID $OHLCV_PID $SWAP_PID $SERVICE_PID; do
         if ! kill -0 $pid 2>/dev/null; then
             echo "❌ ERROR: Process $pid died!"
             echo "Check logs in /tmp/logs/"
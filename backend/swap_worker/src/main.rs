@@ -0,0 +1,13 @@
+use dotenvy::dotenv;
+use shared::workers::swap_worker::SwapWorker;
+
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    dotenv().ok();
+    println!(" Starting swap worker...");
+
+    let swap_worker = SwapWorker::new().await?;
+    swap_worker.start_processing(std::env::var("SWAP_CONSUMER_GROUP").expect("unable to find consumer group from env"), std::env::var("SWAP_WORKER").expect("unable to find consume name from env")).await;
+
+    Ok(())
+}
@@ -227,7 +227,7 @@ export const TradingViewChart: React.FC<TradingViewChartProps> = ({
 
       const formattedVolume = sortedCandles.map((c) => ({
         time: Math.floor(c.timestamp / 1000) as UTCTimestamp,
-        value: c.volume ?? 0,
+        value: c.usd_volume ?? c.volume ?? 0,
         color: (c.buy_volume ?? 0) > (c.sell_volume ?? 0) ? "#22c55e80" : "#ef444480",
       }));
 
@@ -263,7 +263,7 @@ export const TradingViewChart: React.FC<TradingViewChartProps> = ({
 
         const formattedVolume = {
           time: Math.floor(latestCandle.timestamp / 1000) as UTCTimestamp,
-          value: latestCandle.volume ?? 0,
+          value: latestCandle.usd_volume ?? latestCandle.volume ?? 0,
           color: (latestCandle.buy_volume ?? 0) > (latestCandle.sell_volume ?? 0) 
             ? "#22c55e80" 
             : "#ef444480",
@@ -302,7 +302,7 @@ export const TradingViewChart: React.FC<TradingViewChartProps> = ({
 
           const formattedVolume = {
             time: Math.floor(candle.timestamp / 1000) as UTCTimestamp,
-            value: candle.volume ?? 0,
+            value: candle.usd_volume ?? candle.volume ?? 0,
             color: (candle.buy_volume ?? 0) > (candle.sell_volume ?? 0) 
               ? "#22c55e80" 
               : "#ef444480",
@@ -333,7 +333,7 @@ export const TradingViewChart: React.FC<TradingViewChartProps> = ({
         
         updateOHLCVDisplay(formattedLatest, prevFormatted, [{
           time: formattedLatest.time,
-          value: latestCandle.volume ?? 0,
+          value: latestCandle.usd_volume ?? latestCandle.volume ?? 0,
           color: (latestCandle.buy_volume ?? 0) > (latestCandle.sell_volume ?? 0) 
             ? "#22c55e80" 
             : "#ef444480",
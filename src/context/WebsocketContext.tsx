@@ -21,10 +21,17 @@ export interface CandleData {
   high: number;
   low: number;
   close: number;
-  volume: number;
+  volume: number; // base token units
   buy_volume: number;
   sell_volume: number;
   trade_count?: number;
+  quote_open?: number; // OHLC in the quote token (SOL), open to close above are USD
+  quote_high?: number;
+  quote_low?: number;
+  quote_close?: number;
+  quote_volume?: number;
+  usd_volume?: number;
+  revision?: number; // bumped when a late trade corrects a closed candle, the same timestamp replaces it
 }
 
 export interface PeriodStats {
@@ -41,7 +48,7 @@ export interface PeriodStats {
 
 export interface PeriodStatsUpdate {
   token_pair: string;
-  timeframe: 'FiveMins' | 'OneHour' | 'SixHours' | 'TwentyFourHours';
+  timeframe: 'OneMin' | 'FiveMins' | 'FifteenMins' | 'OneHour' | 'FourHours' | 'SixHours' | 'TwentyFourHours' | 'SevenDays';
   price_change: number;
   period_stats: PeriodStats | null;
 }
@@ -52,11 +59,60 @@ export interface PriceInfo {
   token_pair?: string;
 }
 
+export interface LiquidityUpdate {
+  token_pair: string;
+  pool_id: string;
+  dex_tag: string;
+  token_reserve: number;
+  sol_reserve: number;
+  liquidity_usd: number;
+  pair_liquidity_usd: number;
+  updated_at: number;
+}
+
+export interface LeaderboardEntry {
+  rank: number;
+  token_pair: string;
+  score: number;
+}
+
+export interface LeaderboardUpdate {
+  timeframe: PeriodStatsUpdate['timeframe'];
+  kind: 'Trending' | 'Gainers' | 'Losers' | 'Volume' | 'Newest';
+  entries: LeaderboardEntry[];
+  updated_at: number;
+}
+
+export interface AlertNotification {
+  alert_id: string;
+  owner: string;
+  token_pair: string;
+  condition: { type: 'price_above' | 'price_below' | 'price_change' | 'volume_spike' | 'large_trade'; [key: string]: unknown };
+  value: number;
+  message: string;
+  triggered_at: number;
+}
+
+export interface MarketEvent {
+  token_pair: string;
+  event:
+    | { type: 'pump' | 'dump'; sigma: number; change: number }
+    | { type: 'volume_spike'; ratio: number; minute_volume: number }
+    | { type: 'whale_trade'; side: 'Buy' | 'Sell'; usd_value: number; wallet: string }
+    | { type: 'liquidity_pull'; previous_usd: number; current_usd: number; drop: number };
+  price: number | null;
+  detected_at: number;
+}
+
 export interface TokenMetrics {
+  '1m': { price_change: number; stats: PeriodStats | null };
   '5m': { price_change: number; stats: PeriodStats | null };
+  '15m': { price_change: number; stats: PeriodStats | null };
   '1h': { price_change: number; stats: PeriodStats | null };
+  '4h': { price_change: number; stats: PeriodStats | null };
   '6h': { price_change: number; stats: PeriodStats | null };
   '24h': { price_change: number; stats: PeriodStats | null };
+  '7d': { price_change: number; stats: PeriodStats | null };
   currentPriceUSD?: number;
   currentPriceSOL?: number;
   marketCap?: number;
@@ -85,6 +141,10 @@ export interface GlobalWebSocketContextType {
   connect: () => void;
   disconnect: () => void;
   getAllTransactions: () => TransactionData[];
+  getLeaderboard: (timeframe: LeaderboardUpdate['timeframe'], kind: LeaderboardUpdate['kind']) => LeaderboardEntry[];
+  getAlerts: (owner: string) => AlertNotification[];
+  subscribeAlerts: (token: string) => void; // the owner token used with /api/alerts, at least 32 characters
+  getMarketEvents: (tokenPair?: string) => MarketEvent[];
 }
 
 const WebSocketContext = createContext<GlobalWebSocketContextType | null>(null);
@@ -101,8 +161,12 @@ export const WebSocketProvider: React.FC<WebSocketProviderProps> = ({
   const [connectionStatus, setConnectionStatus] = useState<'connecting' | 'connected' | 'disconnected' | 'error'>('disconnected');
   const [rooms, setRooms] = useState<Map<string, WebSocketRoom>>(new Map());
   const [allTransactions, setAllTransactions] = useState<TransactionData[]>([]);
+  const [leaderboards, setLeaderboards] = useState<Map<string, LeaderboardUpdate>>(new Map());
+  const [alerts, setAlerts] = useState<AlertNotification[]>([]);
+  const [marketEvents, setMarketEvents] = useState<MarketEvent[]>([]);
   
   const wsRef = useRef<WebSocket | null>(null);
+  const alertTokenRef = useRef<string | null>(null);
   const isConnectingRef = useRef(false);
 
   const extractTokenPair = useCallback((transaction: TransactionData): string => {
@@ -115,10 +179,14 @@ export const WebSocketProvider: React.FC<WebSocketProviderProps> = ({
       name: tokenPair,
       transactions: [],
       metrics: {
+        '1m': { price_change: 0, stats: null },
         '5m': { price_change: 0, stats: null },
+        '15m': { price_change: 0, stats: null },
         '1h': { price_change: 0, stats: null },
+        '4h': { price_change: 0, stats: null },
         '6h': { price_change: 0, stats: null },
         '24h': { price_change: 0, stats: null },
+        '7d': { price_change: 0, stats: null },
         currentPriceUSD: 0,
         currentPriceSOL: 0,
         marketCap: 0,
@@ -168,15 +236,19 @@ export const WebSocketProvider: React.FC<WebSocketProviderProps> = ({
       
       const room = newRooms.get(update.token_pair)!;
       
-      const timeframeMap: Record<string, keyof TokenMetrics> = {
+      const timeframeMap: Record<string, '1m' | '5m' | '15m' | '1h' | '4h' | '6h' | '24h' | '7d'> = {
+        'OneMin': '1m',
         'FiveMins': '5m',
+        'FifteenMins': '15m',
         'OneHour': '1h',
+        'FourHours': '4h',
         'SixHours': '6h',
-        'TwentyFourHours': '24h'
+        'TwentyFourHours': '24h',
+        'SevenDays': '7d'
       };
       
       const key = timeframeMap[update.timeframe];
-      if (key && (key === '5m' || key === '1h' || key === '6h' || key === '24h')) {
+      if (key) {
         room.metrics[key] = {
           price_change: update.price_change,
           stats: update.period_stats
@@ -208,6 +280,24 @@ export const WebSocketProvider: React.FC<WebSocketProviderProps> = ({
     });
   }, []);
 
+  const updateRoomLiquidity = useCallback((update: LiquidityUpdate) => {
+    setRooms(prevRooms => {
+      const newRooms = new Map(prevRooms);
+      
+      if (!newRooms.has(update.token_pair)) {
+        newRooms.set(update.token_pair, initializeRoom(update.token_pair));
+        console.log(`🏠 Created new room for liquidity: ${update.token_pair}`);
+      }
+      
+      const room = newRooms.get(update.token_pair)!;
+      room.metrics.liquidity = update.pair_liquidity_usd;
+      
+      console.log(`💧 Updated liquidity for ${update.token_pair}: $${update.pair_liquidity_usd}`);
+      
+      return newRooms;
+    });
+  }, []);
+
   const addCandleToRoom = useCallback((candle: CandleData) => {
     setRooms(prevRooms => {
       const newRooms = new Map(prevRooms);
@@ -264,6 +354,10 @@ export const WebSocketProvider: React.FC<WebSocketProviderProps> = ({
         console.log('✅ WebSocket: Global connection established');
         setConnectionStatus('connected');
         isConnectingRef.current = false;
+        // alert subscriptions are per connection, renew it after a reconnect
+        if (alertTokenRef.current) {
+          wsRef.current?.send(JSON.stringify({ type: 'subscribe_alerts', token: alertTokenRef.current }));
+        }
       };
 
       wsRef.current.onmessage = (event) => {
@@ -308,6 +402,27 @@ export const WebSocketProvider: React.FC<WebSocketProviderProps> = ({
             });
             addCandleToRoom(data as CandleData);
           }
+          // Type 5: Pool liquidity
+          else if (data.token_pair && data.pool_id && data.pair_liquidity_usd !== undefined) {
+            console.log('💧 Liquidity Update:', data.token_pair, data.dex_tag);
+            updateRoomLiquidity(data as LiquidityUpdate);
+          }
+          // Type 6: Leaderboard
+          else if (data.kind && data.timeframe && Array.isArray(data.entries)) {
+            console.log('🏆 Leaderboard Update:', data.timeframe, data.kind);
+            const update = data as LeaderboardUpdate;
+            setLeaderboards(prev => new Map(prev).set(`${update.timeframe}:${update.kind}`, update));
+          }
+          // Type 7: Triggered alert, only sent to connections subscribed with the owner's token
+          else if (data.alert_id && data.owner && data.message) {
+            console.log('🔔 Alert:', data.message);
+            setAlerts(prev => [data as AlertNotification, ...prev].slice(0, 100));
+          }
+          // Type 8: Market event (pump, dump, volume spike, whale trade, liquidity pull)
+          else if (data.token_pair && data.event?.type && data.detected_at !== undefined) {
+            console.log('🚨 Market Event:', data.token_pair, data.event.type);
+            setMarketEvents(prev => [data as MarketEvent, ...prev].slice(0, 200));
+          }
           // Unknown message type
           else {
             console.log('❓ Unknown message type:', Object.keys(data).join(', '));
@@ -342,7 +457,7 @@ export const WebSocketProvider: React.FC<WebSocketProviderProps> = ({
       setConnectionStatus('error');
       isConnectingRef.current = false;
     }
-  }, [url, addTransactionToRoom, updateRoomMetrics, updateRoomPrice, addCandleToRoom]);
+  }, [url, addTransactionToRoom, updateRoomMetrics, updateRoomPrice, updateRoomLiquidity, addCandleToRoom]);
 
   const disconnect = useCallback(() => {
     console.log('🛑 WebSocket: Manual disconnect');
@@ -410,6 +525,25 @@ export const WebSocketProvider: React.FC<WebSocketProviderProps> = ({
     return candles;
   }, [rooms]);
 
+  const getLeaderboard = useCallback((timeframe: LeaderboardUpdate['timeframe'], kind: LeaderboardUpdate['kind']): LeaderboardEntry[] => {
+    return leaderboards.get(`${timeframe}:${kind}`)?.entries || [];
+  }, [leaderboards]);
+
+  const getAlerts = useCallback((owner: string): AlertNotification[] => {
+    return alerts.filter(alert => alert.owner === owner);
+  }, [alerts]);
+
+  const subscribeAlerts = useCallback((token: string) => {
+    alertTokenRef.current = token;
+    if (wsRef.current?.readyState === WebSocket.OPEN) {
+      wsRef.current.send(JSON.stringify({ type: 'subscribe_alerts', token }));
+    }
+  }, []);
+
+  const getMarketEvents = useCallback((tokenPair?: string): MarketEvent[] => {
+    return tokenPair ? marketEvents.filter(event => event.token_pair === tokenPair) : marketEvents;
+  }, [marketEvents]);
+
   const getAllTransactions = useCallback((): TransactionData[] => {
     return allTransactions;
   }, [allTransactions]);
@@ -439,6 +573,10 @@ export const WebSocketProvider: React.FC<WebSocketProviderProps> = ({
     connect,
     disconnect,
     getAllTransactions,
+    getLeaderboard,
+    getAlerts,
+    subscribeAlerts,
+    getMarketEvents,
   };
 
   return (